@@ -6,8 +6,11 @@ use termion::color;
 use termion::cursor;
 
 use tetris::Board;
+use tetris::DigOver;
 use tetris::Game;
 use tetris::Piece;
+use tetris::PuzzleOver;
+use tetris::Replay;
 use tetris::ShapeColor;
 use tetris::State;
 
@@ -26,8 +29,8 @@ const TITLE: &str = r#"
 ║       PRESS        ║
 ║       ENTER        ║
 ║                    ║
-║                    ║
-║                    ║
+║   P FOR PRACTICE   ║
+║     D FOR DIG      ║
 ║                    ║
 ║                    ║
 ║                    ║
@@ -36,6 +39,9 @@ const TITLE: &str = r#"
 ╚════════════════════╝
 "#;
 
+/// Frames per second of the game, used to show how far through a replay we are.
+const FPS: u32 = 60;
+
 const BLOCK_WIDTH: u16 = 2;
 const BLOCK: &str = "▐▉";
 
@@ -46,6 +52,8 @@ const BL_BORDER: &str = "╚";
 const TR_BORDER: &str = "╗";
 const BR_BORDER: &str = "╝";
 
+const RIGHT_BORDER_COLUMN: u16 = (Board::WIDTH as u16 * BLOCK_WIDTH) + 2;
+
 pub fn draw<W: Write>(stdout: &mut W, state: &mut State) -> Result<()> {
     let mut buffer = io::BufWriter::new(stdout);
 
@@ -54,7 +62,7 @@ pub fn draw<W: Write>(stdout: &mut W, state: &mut State) -> Result<()> {
             draw_title(&mut buffer)?;
         }
         State::Play(game) => {
-            draw_game(&mut buffer, game)?;
+            draw_live_game(&mut buffer, game)?;
         }
         State::Paused(_) => {
             // TODO
@@ -64,6 +72,23 @@ pub fn draw<W: Write>(stdout: &mut W, state: &mut State) -> Result<()> {
             // TODO
             write!(buffer, "{}Not implemented", cursor::Goto(1, 1))?;
         }
+        State::Replay(replay) => {
+            draw_replay(&mut buffer, replay)?;
+        }
+        State::PuzzleOver(puzzle_over) => {
+            draw_puzzle_over(&mut buffer, puzzle_over)?;
+        }
+        State::DigOver(dig_over) => {
+            draw_dig_over(&mut buffer, dig_over)?;
+        }
+        State::Versus(_)
+        | State::VersusOver(_)
+        | State::Lobby(_)
+        | State::Online(_)
+        | State::OnlineOver(_) => {
+            // TODO
+            write!(buffer, "{}Not implemented", cursor::Goto(1, 1))?;
+        }
     }
 
     buffer.flush()
@@ -76,10 +101,118 @@ fn draw_title<W: Write>(stdout: &mut W) -> Result<()> {
     Ok(())
 }
 
-fn draw_game<W: Write>(stdout: &mut W, game: &Game) -> Result<()> {
+fn draw_game<W: Write>(stdout: &mut W, board: &Board, piece: &Piece) -> Result<()> {
     draw_border(stdout)?;
-    draw_board(stdout, game.board())?;
-    draw_piece(stdout, game.piece())
+    draw_board(stdout, board)?;
+    draw_piece(stdout, piece)
+}
+
+fn draw_live_game<W: Write>(stdout: &mut W, game: &Game) -> Result<()> {
+    draw_game(stdout, game.board(), game.piece())?;
+
+    if let Some(puzzle) = game.current_puzzle() {
+        let pieces_left = puzzle.pieces().len() as u32 - game.pieces_placed();
+
+        let lines = [
+            puzzle.name().to_uppercase(),
+            puzzle.objective().to_string().to_uppercase(),
+            String::new(),
+            format!("PIECES {}", pieces_left),
+        ];
+
+        draw_status(stdout, &lines)?;
+    }
+
+    if game.current_dig().is_some() {
+        let seconds = game.statistics().frames() / FPS;
+
+        let lines = [
+            "DIG".to_string(),
+            format!("{}:{:02}", seconds / 60, seconds % 60),
+            String::new(),
+            format!("GARBAGE {}", game.board().garbage_rows()),
+        ];
+
+        draw_status(stdout, &lines)?;
+    }
+
+    Ok(())
+}
+
+fn draw_dig_over<W: Write>(stdout: &mut W, dig_over: &DigOver) -> Result<()> {
+    let result = if dig_over.finished {
+        "CLEARED"
+    } else {
+        "TOPPED OUT"
+    };
+    let seconds = dig_over.time.frames / FPS;
+
+    let lines = [
+        result.to_string(),
+        format!("{}:{:02}", seconds / 60, seconds % 60),
+        String::new(),
+        "ENTER TO RETRY".to_string(),
+        "BACKSPACE FOR TITLE".to_string(),
+    ];
+
+    draw_status(stdout, &lines)
+}
+
+fn draw_puzzle_over<W: Write>(stdout: &mut W, puzzle_over: &PuzzleOver) -> Result<()> {
+    let result = if puzzle_over.is_solved() {
+        "SOLVED"
+    } else {
+        "FAILED"
+    };
+
+    let lines = [
+        result.to_string(),
+        puzzle_over.puzzle().name().to_uppercase(),
+        String::new(),
+        "ENTER TO RETRY".to_string(),
+        "BACKSPACE FOR TITLE".to_string(),
+    ];
+
+    draw_status(stdout, &lines)
+}
+
+fn draw_replay<W: Write>(stdout: &mut W, replay: &Replay) -> Result<()> {
+    let player = replay.player();
+
+    draw_game(stdout, player.board(), player.piece())?;
+
+    let seconds = player.tick() / FPS;
+
+    let status = if player.is_finished() {
+        "END".to_string()
+    } else if replay.is_paused() {
+        "PAUSED".to_string()
+    } else {
+        format!("x{}", replay.speed())
+    };
+
+    let lines = [
+        "REPLAY".to_string(),
+        format!("{}:{:02}", seconds / 60, seconds % 60),
+        status,
+        String::new(),
+        format!("SCORE {}", player.score()),
+        format!("LINES {}", player.lines_cleared()),
+    ];
+
+    draw_status(stdout, &lines)
+}
+
+/// Draw lines of text in a column to the right of the board.
+fn draw_status<W: Write>(stdout: &mut W, lines: &[String]) -> Result<()> {
+    write!(stdout, "{}", color::Fg(color::White))?;
+
+    for (row, line) in lines.iter().enumerate() {
+        let cursor = cursor::Goto(RIGHT_BORDER_COLUMN + 2, row as u16 + 2);
+        write!(stdout, "{}{}{}", cursor, termion::clear::UntilNewline, line)?;
+    }
+
+    Ok(())
 }
 
 fn draw_board<W: Write>(stdout: &mut W, board: &Board) -> Result<()> {
@@ -116,8 +249,6 @@ fn draw_border<W: Write>(stdout: &mut W) -> Result<()> {
         TR_BORDER
     )?;
 
-    const RIGHT_BORDER_COLUMN: u16 = (Board::WIDTH as u16 * BLOCK_WIDTH) + 2;
-
     for row in 0..u16::from(Board::VISIBLE_ROWS) {
         write!(stdout, "{}{}", cursor::Goto(1, row + 2), VERT_BORDER)?;
         write!(
@@ -161,5 +292,6 @@ fn set_shape_color<W: Write>(stdout: &mut W, shape_color: ShapeColor) -> Result<
         ShapeColor::S => write!(stdout, "{}", color::Fg(color::Green)),
         ShapeColor::T => write!(stdout, "{}", color::Fg(color::Magenta)),
         ShapeColor::Z => write!(stdout, "{}", color::Fg(color::Red)),
+        ShapeColor::Garbage => write!(stdout, "{}", color::Fg(color::LightBlack)),
     }
 }
@@ -1,7 +1,9 @@
+use std::fs::OpenOptions;
 use std::io;
 use std::io::Result;
 use std::io::Write;
 use std::time::Duration;
+use std::time::Instant;
 
 use termion::color;
 use termion::cursor;
@@ -9,17 +11,47 @@ use termion::event::Key;
 use termion::input::TermRead;
 use termion::raw::IntoRawMode;
 
+use tetris::BotProcess;
+use tetris::DigOver;
+use tetris::DigRace;
+use tetris::Fumen;
 use tetris::Game;
+use tetris::History;
+use tetris::Player;
+use tetris::Puzzle;
+use tetris::PuzzleOver;
+use tetris::Replay;
 use tetris::State;
+use tetris::TbpBot;
 
 mod draw;
 
+/// How far to rewind in practice mode, in frames.
+const REWIND_FRAMES: u32 = 5 * 60;
+
+/// How far to skip forwards or backwards in a replay, in frames.
+const REPLAY_SKIP_FRAMES: i32 = 5 * 60;
+
+/// How long each frame of a recorded game lasts, which replays are played back at.
+const REPLAY_FRAME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// The file that fumens are exported to, in the current directory.
+const FUMEN_PATH: &str = "fumens.txt";
+
+/// An external bot playing the game for the user.
+type Bot = Player<TbpBot<BotProcess>>;
+
 fn main() -> Result<()> {
     let stdout = io::stdout();
     let mut stdout = stdout.lock().into_raw_mode()?;
     let mut stdin = termion::async_stdin().keys();
 
-    let mut state = State::default();
+    let mut state = initial_state()?;
+    let mut bot = initial_bot()?;
+    let mut bot_error = None;
+    let mut export_error = None;
+    let mut last_update = Instant::now();
+    let mut replay_lag = Duration::ZERO;
 
     write!(stdout, "{}{}", cursor::Hide, termion::clear::All)?;
 
@@ -44,23 +76,209 @@ fn main() -> Result<()> {
                 break;
             }
 
+            if let Key::Char('f') = key {
+                if let Err(e) = export_fumen(&state) {
+                    export_error = Some(e);
+                }
+            }
+
             state = match state {
                 State::Title(title) => match key {
                     Key::Char('\n') => title.start_game(),
+                    Key::Char('p') => title.start_practice(),
+                    Key::Char('d') => title.start_dig(),
                     _ => State::Title(title),
                 },
                 State::Play(game) => handle_key_in_game(game, key),
                 State::Paused(paused) => paused.unpause(),
                 State::GameOver(game_over) => State::GameOver(game_over),
+                State::Replay(replay) => handle_key_in_replay(replay, key),
+                State::PuzzleOver(puzzle_over) => handle_key_in_puzzle_over(puzzle_over, key),
+                // Versus is only played in tetris-sdl, which can read both players' keys at once
+                State::Versus(versus) => State::Versus(versus),
+                State::VersusOver(versus_over) => versus_over.exit(),
+                State::DigOver(dig_over) => handle_key_in_dig_over(dig_over, key),
+                // Online matches are only played in tetris-sdl, alongside versus
+                State::Lobby(lobby) => lobby.exit(),
+                State::Online(online) => State::Online(online),
+                State::OnlineOver(online_over) => online_over.exit(),
             };
         }
 
-        state = state.update();
+        if let State::Play(game) = &mut state {
+            if let Some(error) = play_bot(&mut bot, game) {
+                bot_error = Some(error);
+            }
+        }
+
+        let now = Instant::now();
+        let elapsed = now - last_update;
+        last_update = now;
+
+        state = match state {
+            State::Replay(replay) => update_replay(replay, elapsed, &mut replay_lag),
+            state => {
+                replay_lag = Duration::ZERO;
+                state.update()
+            }
+        };
+    }
+
+    if let Some(error) = bot_error {
+        eprintln!("{}", error);
+    }
+
+    if let Some(error) = export_error {
+        eprintln!("Failed to export fumen to {}: {}", FUMEN_PATH, error);
     }
 
     Ok(())
 }
 
+/// Choose the state to start in from the command-line arguments:
+///
+/// - `tetris-console replay <file>` plays back a saved game.
+/// - `tetris-console puzzle <file>` starts a puzzle.
+/// - `tetris-console practice <fumen>` starts a practice game from a fumen's board.
+/// - `tetris-console dig <rows> <messiness>` starts a dig race.
+/// - `tetris-console bot <command> [args...]` starts a game played by a bot.
+///
+/// Otherwise the game starts at the title screen.
+fn initial_state() -> Result<State> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match args.as_slice() {
+        [command, path] if command == "replay" => {
+            let json = std::fs::read_to_string(path)?;
+            let history = History::from_json(&json)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            Ok(State::replay(history))
+        }
+        [command, path] if command == "puzzle" => {
+            let json = std::fs::read_to_string(path)?;
+            let puzzle = Puzzle::from_json(&json)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            Ok(State::puzzle(puzzle))
+        }
+        [command, fumen] if command == "practice" => {
+            let fumen: Fumen = fumen
+                .parse()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            Ok(State::practice_with_board(fumen.pages()[0].board.clone()))
+        }
+        [command, rows, messiness] if command == "dig" => {
+            let parse = |arg: &str| {
+                arg.parse()
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+            };
+            Ok(State::dig(DigRace {
+                rows: parse(rows)?,
+                messiness: parse(messiness)?,
+            }))
+        }
+        [command, _, ..] if command == "bot" => Ok(State::Play(Game::default())),
+        _ => Ok(State::default()),
+    }
+}
+
+/// Start the bot given by `tetris-console bot <command> [args...]`, which speaks the Tetris Bot
+/// Protocol over stdin and stdout.
+fn initial_bot() -> Result<Option<Bot>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match args.as_slice() {
+        [command, program, bot_args @ ..] if command == "bot" => {
+            let bot = TbpBot::spawn(std::process::Command::new(program).args(bot_args))
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            Ok(Some(Player::new(bot)))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Let the bot play the game, if there is one.
+///
+/// If something goes wrong talking to the bot, the bot is stopped and the error is returned.
+fn play_bot(bot: &mut Option<Bot>, game: &mut Game) -> Option<String> {
+    let player = bot.as_mut()?;
+    player.play(game);
+
+    let error = player.controller().error()?.to_string();
+    *bot = None;
+    Some(error)
+}
+
+/// Export the current position as a fumen, if in a game or a replay.
+///
+/// The fumen is added as a new line at the end of the file at `FUMEN_PATH`, so it can be copied
+/// into a fumen editor.
+fn export_fumen(state: &State) -> Result<()> {
+    let fumen = match state {
+        State::Play(game) => game.to_fumen(),
+        State::Replay(replay) => replay.player().to_fumen(),
+        _ => return Ok(()),
+    };
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(FUMEN_PATH)?;
+    writeln!(file, "{}", fumen)
+}
+
+/// Update a replay once for each recorded frame that fits in the time since the last update.
+///
+/// The console redraws far less often than games are recorded, so a replay that updated once per
+/// redraw would play back slower than the game was played. `lag` carries the time left over
+/// towards the next frame.
+fn update_replay(replay: Replay, elapsed: Duration, lag: &mut Duration) -> State {
+    let mut state = State::Replay(replay);
+    *lag += elapsed;
+
+    while *lag >= REPLAY_FRAME {
+        *lag -= REPLAY_FRAME;
+        state = match state {
+            State::Replay(replay) => replay.update(),
+            state => return state,
+        };
+    }
+
+    state
+}
+
+fn handle_key_in_replay(mut replay: Replay, key: Key) -> State {
+    match key {
+        Key::Char(' ') => replay.toggle_pause(),
+        Key::Char('.') => replay.step_frame(),
+        Key::Up | Key::Char('+') => replay.speed_up(),
+        Key::Down | Key::Char('-') => replay.slow_down(),
+        Key::Left => replay.skip(-REPLAY_SKIP_FRAMES),
+        Key::Right => replay.skip(REPLAY_SKIP_FRAMES),
+        Key::Home => replay.seek(0),
+        Key::Char('\t') => return replay.take_over(),
+        Key::Char('\n') => return replay.exit(),
+        _ => {}
+    };
+
+    State::Replay(replay)
+}
+
+fn handle_key_in_puzzle_over(puzzle_over: PuzzleOver, key: Key) -> State {
+    match key {
+        Key::Char('\n') => puzzle_over.retry(),
+        Key::Backspace => puzzle_over.exit(),
+        _ => State::PuzzleOver(puzzle_over),
+    }
+}
+
+fn handle_key_in_dig_over(dig_over: DigOver, key: Key) -> State {
+    match key {
+        Key::Char('\n') => dig_over.retry(),
+        Key::Backspace => dig_over.exit(),
+        _ => State::DigOver(dig_over),
+    }
+}
+
 fn handle_key_in_game(mut game: Game, key: Key) -> State {
     match key {
         Key::Up => game.rotate(),
@@ -68,6 +286,8 @@ fn handle_key_in_game(mut game: Game, key: Key) -> State {
         Key::Right => game.move_right(),
         Key::Down => game.start_soft_drop(),
         Key::Char(' ') => game.start_hard_drop(),
+        Key::Char('z') => game.undo(),
+        Key::Char('r') => game.rewind(REWIND_FRAMES),
         Key::Char('\n') => return game.pause(),
         _ => {}
     };
@@ -1,6 +1,8 @@
 use std::i16;
 use std::i32;
 
+use std::time::Duration;
+
 use lazy_static::lazy_static;
 use sdl2::pixels::Color;
 use sdl2::rect::Point;
@@ -11,16 +13,32 @@ use sdl2::ttf::Font;
 use sdl2::video::Window;
 
 use tetris::Board;
+use tetris::Cpu;
+use tetris::Difficulty;
+use tetris::DigOver;
+use tetris::DigTime;
 use tetris::Game;
 use tetris::GameOver;
 use tetris::HighScores;
+use tetris::Lobby;
+use tetris::Online;
+use tetris::OnlineOver;
 use tetris::Piece;
 use tetris::Pos;
+use tetris::PuzzleOver;
+use tetris::Replay;
+use tetris::ReplayPlayer;
 use tetris::Rotation;
 use tetris::Score;
 use tetris::Shape;
 use tetris::ShapeColor;
 use tetris::State;
+use tetris::Statistics;
+use tetris::Versus;
+use tetris::VersusOver;
+use tetris::DIG_LEADERBOARD_SIZE;
+
+use crate::FPS;
 
 const INNER_BLOCK_SIZE: u8 = 22;
 const BLOCK_BORDER: u8 = 1;
@@ -34,9 +52,65 @@ const BORDER_COLOR: Color = Color {
     a: 255,
 };
 
+/// A game that can be drawn in progress, either being played live or replayed.
+pub trait GameView {
+    fn board(&self) -> &Board;
+    fn piece(&self) -> &Piece;
+    fn next_shape(&self) -> Shape;
+    fn lines_cleared(&self) -> u32;
+    fn score(&self) -> u32;
+}
+
+impl GameView for Game {
+    fn board(&self) -> &Board {
+        Game::board(self)
+    }
+
+    fn piece(&self) -> &Piece {
+        Game::piece(self)
+    }
+
+    fn next_shape(&self) -> Shape {
+        Game::next_shape(self)
+    }
+
+    fn lines_cleared(&self) -> u32 {
+        Game::lines_cleared(self)
+    }
+
+    fn score(&self) -> u32 {
+        Game::score(self)
+    }
+}
+
+impl GameView for ReplayPlayer {
+    fn board(&self) -> &Board {
+        ReplayPlayer::board(self)
+    }
+
+    fn piece(&self) -> &Piece {
+        ReplayPlayer::piece(self)
+    }
+
+    fn next_shape(&self) -> Shape {
+        ReplayPlayer::next_shape(self)
+    }
+
+    fn lines_cleared(&self) -> u32 {
+        ReplayPlayer::lines_cleared(self)
+    }
+
+    fn score(&self) -> u32 {
+        ReplayPlayer::score(self)
+    }
+}
+
 pub struct Drawer<'a> {
     canvas: Canvas<Window>,
     font: Font<'a, 'a>,
+
+    /// How far right viewports are moved, to draw the second player's game in versus mode.
+    offset_x: i32,
 }
 
 fn shape_color_to_rgb(color: ShapeColor) -> Color {
@@ -48,23 +122,59 @@ fn shape_color_to_rgb(color: ShapeColor) -> Color {
         ShapeColor::S => Color::RGB(0, 255, 0),
         ShapeColor::T => Color::RGB(255, 0, 255),
         ShapeColor::Z => Color::RGB(255, 0, 0),
+        ShapeColor::Garbage => Color::RGB(160, 160, 160),
     }
 }
 
 impl<'a> Drawer<'a> {
     pub fn new(canvas: Canvas<Window>, font: Font<'a, 'a>) -> Self {
-        Drawer { canvas, font }
+        Drawer {
+            canvas,
+            font,
+            offset_x: 0,
+        }
     }
 
     pub fn draw_state(&mut self, state: &State) {
+        let players = match state {
+            State::Versus(_) | State::VersusOver(_) | State::Online(_) | State::OnlineOver(_) => 2,
+            _ => 1,
+        };
+        self.fit_window(players);
+
         match state {
             State::Title(_) => self.title_draw(),
-            State::Play(game) => self.draw_game(game),
+            State::Play(game) => self.draw_live_game(game),
             State::Paused(_) => self.pause_draw(),
             State::GameOver(game_over) => self.draw_game_over(game_over),
+            State::Replay(replay) => self.draw_replay(replay),
+            State::PuzzleOver(puzzle_over) => self.draw_puzzle_over(puzzle_over),
+            State::Versus(versus) => self.draw_versus(versus),
+            State::VersusOver(versus_over) => self.draw_versus_over(versus_over),
+            State::DigOver(dig_over) => self.draw_dig_over(dig_over),
+            State::Lobby(lobby) => self.draw_lobby(lobby),
+            State::Online(online) => self.draw_online(online),
+            State::OnlineOver(online_over) => self.draw_online_over(online_over),
         }
     }
 
+    /// Resize the window to fit the given number of games side by side.
+    fn fit_window(&mut self, players: u32) {
+        let width = WINDOW_WIDTH * players;
+        if self.canvas.window().size().0 != width {
+            let _ = self.canvas.window_mut().set_size(width, WINDOW_HEIGHT);
+        }
+    }
+
+    /// Draw each player's game in their own half of the window.
+    fn for_each_player(&mut self, mut draw: impl FnMut(&mut Self, usize)) {
+        for player in 0..2 {
+            self.offset_x = player as i32 * WINDOW_WIDTH as i32;
+            draw(self, player);
+        }
+        self.offset_x = 0;
+    }
+
     fn title_draw(&mut self) {
         self.text()
             .size(4)
@@ -73,13 +183,24 @@ impl<'a> Drawer<'a> {
             .size(1)
             .under()
             .offset(0, 10)
-            .draw("[ Press Enter ]");
+            .draw("[ Press Enter ]")
+            .under()
+            .draw("[ P to Practice ]")
+            .under()
+            .draw("[ D to Dig ]")
+            .under()
+            .draw("[ V for Versus ]")
+            .under()
+            .draw("[ 1-3 for Versus CPU ]")
+            .under()
+            .draw("[ O to Play Online ]");
     }
 
     fn pause_draw(&mut self) {
         self.text().centered().draw("Paused");
     }
 
+    #[allow(clippy::unnecessary_cast)]
     pub fn draw_block(&mut self, pos: Pos, col: Color) {
         let x = pos.x() as i16;
         let y = pos.y() as i16;
@@ -106,7 +227,10 @@ impl<'a> Drawer<'a> {
             .draw("final score")
             .under()
             .size(3)
-            .draw(&game_over.score.value.to_string());
+            .draw(&game_over.score.value.to_string())
+            .size(1);
+
+        text = game_over.statistics.draw(text);
 
         text = game_over.draw(text);
 
@@ -117,13 +241,279 @@ impl<'a> Drawer<'a> {
         }
     }
 
-    pub fn draw_game(&mut self, game: &Game) {
+    pub fn draw_game(&mut self, game: &impl GameView) {
         self.draw_board(game.board());
         self.draw_piece(game.piece());
         self.draw_next(game.next_shape());
         self.draw_game_score(game);
     }
 
+    fn draw_live_game(&mut self, game: &Game) {
+        self.draw_game(game);
+
+        self.set_viewport(*SCORE_VIEW);
+        self.text()
+            .offset(0, FINESSE_Y)
+            .draw("finesse")
+            .size(2)
+            .left()
+            .draw(&game.statistics().finesse().faults().to_string());
+
+        if game.is_practice() {
+            self.set_viewport(*SCORE_VIEW);
+            self.text()
+                .offset(0, STATUS_Y)
+                .draw("practice")
+                .under()
+                .draw("z undo")
+                .under()
+                .draw("r rewind");
+        }
+
+        if let Some(puzzle) = game.current_puzzle() {
+            let pieces_left = puzzle.pieces().len() as u32 - game.pieces_placed();
+
+            self.set_viewport(*SCORE_VIEW);
+            self.text()
+                .offset(0, STATUS_Y)
+                .draw(&puzzle.name().to_lowercase())
+                .under()
+                .draw(&puzzle.objective().to_string().to_lowercase())
+                .under()
+                .offset(0, PAD)
+                .draw("pieces")
+                .size(2)
+                .left()
+                .draw(&pieces_left.to_string());
+        }
+
+        if game.current_dig().is_some() {
+            self.set_viewport(*SCORE_VIEW);
+            self.text()
+                .offset(0, STATUS_Y)
+                .draw("garbage")
+                .size(2)
+                .left()
+                .draw(&game.board().garbage_rows().to_string())
+                .size(1)
+                .left()
+                .offset(0, PAD)
+                .draw("time")
+                .size(2)
+                .left()
+                .draw(&format_time(game.statistics().time_played()));
+        }
+    }
+
+    pub fn draw_dig_over(&mut self, dig_over: &DigOver) {
+        let result = if dig_over.finished {
+            "Cleared"
+        } else {
+            "Topped Out"
+        };
+
+        let mut text = self
+            .text()
+            .top()
+            .offset(0, 50)
+            .size(3)
+            .draw(result)
+            .under()
+            .offset(0, 10)
+            .size(1)
+            .draw(&format!(
+                "{} rows  messiness {}%",
+                dig_over.race.rows, dig_over.race.messiness
+            ))
+            .under()
+            .offset(0, 10)
+            .draw("time")
+            .under()
+            .size(3)
+            .draw(&format_time(dig_over.time.duration()))
+            .size(1);
+
+        text = dig_over.statistics.draw(text);
+
+        text = dig_over.draw(text);
+
+        if dig_over.posting_time() {
+            text.size(1).draw("[ Enter Name and Press Enter ]");
+        } else {
+            text.size(1)
+                .draw("[ Press Enter to Retry ]")
+                .under()
+                .draw("[ Backspace for Title ]");
+        }
+    }
+
+    fn draw_puzzle_over(&mut self, puzzle_over: &PuzzleOver) {
+        let result = if puzzle_over.is_solved() {
+            "Solved"
+        } else {
+            "Failed"
+        };
+
+        self.text()
+            .top()
+            .offset(0, 50)
+            .size(3)
+            .draw(result)
+            .under()
+            .offset(0, 10)
+            .size(1)
+            .draw(&puzzle_over.puzzle().name().to_lowercase())
+            .under()
+            .offset(0, 10)
+            .draw("[ Press Enter to Retry ]")
+            .under()
+            .draw("[ Backspace for Title ]");
+    }
+
+    fn draw_versus(&mut self, versus: &Versus) {
+        let cpu = versus.cpu().map(Cpu::difficulty);
+
+        self.for_each_player(|drawer, player| {
+            drawer.draw_game(&versus.games()[player]);
+
+            drawer.set_viewport(*SCORE_VIEW);
+            drawer
+                .text()
+                .offset(0, STATUS_Y)
+                .draw(&player_name(player, cpu))
+                .under()
+                .offset(0, PAD)
+                .draw("garbage")
+                .size(2)
+                .left()
+                .draw(&versus.incoming_garbage(player).to_string());
+        });
+    }
+
+    fn draw_versus_over(&mut self, versus_over: &VersusOver) {
+        self.for_each_player(|drawer, player| {
+            let game = &versus_over.games()[player];
+            let result = match versus_over.winner() {
+                Some(winner) if winner == player => "Winner",
+                Some(_) => "Loser",
+                None => "Draw",
+            };
+
+            drawer.set_viewport(Rect::new(0, 0, WINDOW_WIDTH, WINDOW_HEIGHT));
+            let text = drawer
+                .text()
+                .top()
+                .offset(0, 50)
+                .size(3)
+                .draw(result)
+                .under()
+                .offset(0, 10)
+                .size(1)
+                .draw(&player_name(player, versus_over.cpu()))
+                .under()
+                .offset(0, 10)
+                .draw("score")
+                .under()
+                .size(2)
+                .draw(&game.score().to_string())
+                .size(1)
+                .under()
+                .draw("lines")
+                .under()
+                .size(2)
+                .draw(&game.lines_cleared().to_string())
+                .size(1);
+
+            game.statistics()
+                .draw(text)
+                .under()
+                .offset(0, 20)
+                .draw("[ Press Enter for Rematch ]")
+                .under()
+                .draw("[ Backspace for Title ]");
+        });
+    }
+
+    fn draw_lobby(&mut self, lobby: &Lobby) {
+        let status = match lobby.error() {
+            Some(_) => "Can't Connect",
+            None => "Finding Opponent",
+        };
+
+        self.text()
+            .centered()
+            .size(2)
+            .draw(status)
+            .under()
+            .offset(0, 10)
+            .size(1)
+            .draw("[ Backspace for Title ]");
+    }
+
+    fn draw_online(&mut self, online: &Online) {
+        self.for_each_player(|drawer, player| {
+            if player == 0 {
+                drawer.draw_game(online.game());
+                drawer.set_viewport(*SCORE_VIEW);
+                drawer
+                    .text()
+                    .offset(0, STATUS_Y)
+                    .draw("you")
+                    .under()
+                    .offset(0, PAD)
+                    .draw("garbage")
+                    .size(2)
+                    .left()
+                    .draw(&online.incoming_garbage().to_string());
+            } else {
+                drawer.draw_game(online.opponent());
+                drawer.set_viewport(*SCORE_VIEW);
+                drawer.text().offset(0, STATUS_Y).draw("opponent");
+            }
+        });
+    }
+
+    fn draw_online_over(&mut self, online_over: &OnlineOver) {
+        self.for_each_player(|drawer, player| {
+            if player == 1 {
+                drawer.draw_game(online_over.opponent());
+                return;
+            }
+
+            let game = online_over.game();
+
+            drawer.set_viewport(Rect::new(0, 0, WINDOW_WIDTH, WINDOW_HEIGHT));
+            let text = drawer
+                .text()
+                .top()
+                .offset(0, 50)
+                .size(3)
+                .draw(&online_over.result().to_string())
+                .under()
+                .offset(0, 10)
+                .size(1)
+                .draw("score")
+                .under()
+                .size(2)
+                .draw(&game.score().to_string())
+                .size(1)
+                .under()
+                .draw("lines")
+                .under()
+                .size(2)
+                .draw(&game.lines_cleared().to_string())
+                .size(1);
+
+            game.statistics()
+                .draw(text)
+                .under()
+                .offset(0, 20)
+                .draw("[ Press Enter for Rematch ]")
+                .under()
+                .draw("[ Backspace for Title ]");
+        });
+    }
+
     fn draw_board(&mut self, board: &Board) {
         self.set_viewport(*BOARD_BORDER_VIEW);
         self.draw_border(Pos::new(
@@ -151,7 +541,7 @@ impl<'a> Drawer<'a> {
         self.draw_shape(next, Rotation::default(), Pos::new(1, 1));
     }
 
-    fn draw_game_score(&mut self, game: &Game) {
+    fn draw_game_score(&mut self, game: &impl GameView) {
         self.set_viewport(*SCORE_VIEW);
 
         self.text()
@@ -168,6 +558,37 @@ impl<'a> Drawer<'a> {
             .draw(&game.score().to_string());
     }
 
+    pub fn draw_replay(&mut self, replay: &Replay) {
+        self.draw_game(replay.player());
+        self.draw_replay_status(replay);
+    }
+
+    fn draw_replay_status(&mut self, replay: &Replay) {
+        self.set_viewport(*SCORE_VIEW);
+
+        let player = replay.player();
+        let seconds = player.tick() / u32::from(FPS);
+
+        let status = if player.is_finished() {
+            "end".to_string()
+        } else if replay.is_paused() {
+            "paused".to_string()
+        } else {
+            format!("x{}", replay.speed())
+        };
+
+        self.text()
+            .offset(0, STATUS_Y)
+            .draw("replay")
+            .size(2)
+            .left()
+            .draw(&format!("{}:{:02}", seconds / 60, seconds % 60))
+            .size(1)
+            .left()
+            .offset(0, PAD)
+            .draw(&status);
+    }
+
     fn draw_piece(&mut self, piece: &Piece) {
         self.draw_shape(
             piece.shape,
@@ -222,7 +643,8 @@ impl<'a> Drawer<'a> {
         }
     }
 
-    pub fn set_viewport(&mut self, rect: Rect) {
+    pub fn set_viewport(&mut self, mut rect: Rect) {
+        rect.offset(self.offset_x, 0);
         self.canvas.set_viewport(Some(rect));
     }
 
@@ -355,6 +777,42 @@ impl Drawable for Score {
     }
 }
 
+impl Drawable for Statistics {
+    fn draw<'a, 'b>(&self, text: TextDrawer<'a, 'b>) -> TextDrawer<'a, 'b> {
+        let seconds = self.time_played().as_secs();
+
+        text.under()
+            .draw(&format!(
+                "time {}:{:02}  pieces {}",
+                seconds / 60,
+                seconds % 60,
+                self.pieces()
+            ))
+            .under()
+            .draw(&format!(
+                "pps {:.2}  kpp {:.2}",
+                self.pieces_per_second(),
+                self.keys_per_piece()
+            ))
+            .under()
+            .draw(&format!(
+                "singles {}  doubles {}  triples {}  tetrises {}",
+                self.singles(),
+                self.doubles(),
+                self.triples(),
+                self.tetrises()
+            ))
+            .under()
+            .draw(&format!(
+                "t-spins {}  max combo {}",
+                self.t_spins(),
+                self.max_combo()
+            ))
+            .under()
+            .draw(&format!("finesse faults {}", self.finesse().faults()))
+    }
+}
+
 impl Drawable for GameOver {
     fn draw<'a, 'b>(&self, text: TextDrawer<'a, 'b>) -> TextDrawer<'a, 'b> {
         match &self.hiscores {
@@ -394,6 +852,85 @@ impl Drawable for GameOver {
     }
 }
 
+impl Drawable for DigTime {
+    fn draw<'a, 'b>(&self, text: TextDrawer<'a, 'b>) -> TextDrawer<'a, 'b> {
+        let name = if self.name.is_empty() {
+            " "
+        } else {
+            &self.name
+        };
+
+        text.offset(-SCORE_OFFSET, 0)
+            .draw(name)
+            .offset(SCORE_OFFSET * 2, 0)
+            .draw(&format_time(self.duration()))
+            .under()
+            .offset(-SCORE_OFFSET, 10)
+    }
+}
+
+impl Drawable for DigOver {
+    fn draw<'a, 'b>(&self, text: TextDrawer<'a, 'b>) -> TextDrawer<'a, 'b> {
+        let times = match &self.leaderboard {
+            Some(times) => times,
+            None if self.finished && self.race.is_default() => {
+                return text
+                    .size(1)
+                    .under()
+                    .offset(0, 10)
+                    .draw("[ ERROR Failed to retrieve Best Times ]")
+                    .offset(0, 20);
+            }
+            None => return text.under().offset(0, 20),
+        };
+
+        let mut text = text.size(3).under().offset(0, 10).draw("Best Times");
+
+        text = text.size(2).under().offset(0, 10);
+
+        let position = times.partition_point(|time| time <= &self.time);
+        let posting_time = self.posting_time();
+
+        for time in &times[..position] {
+            text = time.draw(text);
+        }
+
+        if posting_time {
+            text = self
+                .time
+                .draw(text.color(Color::RGB(255, 255, 100)))
+                .reset_color();
+        }
+
+        let shown = if posting_time {
+            DIG_LEADERBOARD_SIZE - 1
+        } else {
+            DIG_LEADERBOARD_SIZE
+        };
+
+        for time in times.iter().take(shown).skip(position) {
+            text = time.draw(text);
+        }
+
+        text.under().offset(-SCORE_OFFSET, 10)
+    }
+}
+
+/// Get the name shown for a player in versus mode, where player two may be a CPU.
+fn player_name(player: usize, cpu: Option<Difficulty>) -> String {
+    match cpu {
+        Some(difficulty) if player == 1 => format!("cpu {}", difficulty),
+        _ => format!("player {}", player + 1),
+    }
+}
+
+/// Format a time as minutes, seconds and hundredths of a second.
+fn format_time(time: Duration) -> String {
+    let seconds = time.as_secs();
+    let hundredths = time.subsec_millis() / 10;
+    format!("{}:{:02}.{:02}", seconds / 60, seconds % 60, hundredths)
+}
+
 lazy_static! {
     static ref PREVIEW_VIEW: Rect = Rect::new(PREVIEW_X, PREVIEW_Y, PREVIEW_WIDTH, PREVIEW_HEIGHT);
     static ref SCORE_VIEW: Rect = Rect::new(SCORE_X, PAD, PREVIEW_WIDTH, BOARD_HEIGHT);
@@ -425,6 +962,10 @@ const SCORE_X: i32 = PREVIEW_X + BOARD_BORDER as i32 + PAD;
 
 const PAD: i32 = BLOCK_SIZE as i32;
 
+const STATUS_Y: i32 = PAD * 6;
+
+const FINESSE_Y: i32 = PAD * 12;
+
 pub const WINDOW_WIDTH: u32 = BOARD_WIDTH + BOARD_BORDER + PREVIEW_WIDTH;
 pub const WINDOW_HEIGHT: u32 = TOTAL_BOARD_HEIGHT;
 pub const WINDOW_RATIO: f32 = WINDOW_HEIGHT as f32 / WINDOW_WIDTH as f32;
@@ -3,17 +3,38 @@ use sdl2::event::WindowEvent;
 use sdl2::keyboard::Keycode;
 use sdl2::EventPump;
 
+use tetris::Difficulty;
+use tetris::DigOver;
+use tetris::Fumen;
 use tetris::Game;
 use tetris::GameOver;
+use tetris::Lobby;
+use tetris::Online;
+use tetris::OnlineOver;
 use tetris::Paused;
+use tetris::PuzzleOver;
+use tetris::Replay;
 use tetris::State;
 use tetris::Title;
+use tetris::Versus;
+use tetris::VersusOver;
 
 use crate::draw::WINDOW_RATIO;
+use crate::FPS;
+
+// how far to skip forwards or backwards in a replay, in frames
+const REPLAY_SKIP_FRAMES: i32 = 5 * FPS as i32;
+
+// how far to rewind in practice mode, in frames
+const REWIND_FRAMES: u32 = 5 * FPS as u32;
 
 // the minimum velocity before movement is registered, in % of screen width per ms
 const FINGER_SENSITIVITY: f32 = 0.0002;
 
+// the file that fumens are exported to, in the current directory
+#[cfg(not(target_os = "emscripten"))]
+const FUMEN_PATH: &str = "fumens.txt";
+
 pub struct EventHandler {
     event_pump: EventPump,
     last_finger_press: Option<FingerPress>,
@@ -53,6 +74,14 @@ impl EventHandler {
             State::Play(game) => self.handle_game(game, event),
             State::Paused(paused) => self.handle_paused(paused, event),
             State::GameOver(game_over) => self.handle_game_over(game_over, event),
+            State::Replay(replay) => self.handle_replay(replay, event),
+            State::PuzzleOver(puzzle_over) => self.handle_puzzle_over(puzzle_over, event),
+            State::Versus(versus) => self.handle_versus(versus, event),
+            State::VersusOver(versus_over) => self.handle_versus_over(versus_over, event),
+            State::DigOver(dig_over) => self.handle_dig_over(dig_over, event),
+            State::Lobby(lobby) => self.handle_lobby(lobby, event),
+            State::Online(online) => self.handle_online(online, event),
+            State::OnlineOver(online_over) => self.handle_online_over(online_over, event),
         }
     }
 
@@ -63,6 +92,34 @@ impl EventHandler {
                 ..
             }
             | Event::FingerUp { .. } => title.start_game(),
+            Event::KeyDown {
+                keycode: Some(Keycode::P),
+                ..
+            } => title.start_practice(),
+            Event::KeyDown {
+                keycode: Some(Keycode::D),
+                ..
+            } => title.start_dig(),
+            Event::KeyDown {
+                keycode: Some(Keycode::V),
+                ..
+            } => title.start_versus(),
+            Event::KeyDown {
+                keycode: Some(Keycode::Num1),
+                ..
+            } => title.start_versus_cpu(Difficulty::Easy),
+            Event::KeyDown {
+                keycode: Some(Keycode::Num2),
+                ..
+            } => title.start_versus_cpu(Difficulty::Medium),
+            Event::KeyDown {
+                keycode: Some(Keycode::Num3),
+                ..
+            } => title.start_versus_cpu(Difficulty::Hard),
+            Event::KeyDown {
+                keycode: Some(Keycode::O),
+                ..
+            } => title.start_online(),
             _ => State::Title(title),
         }
     }
@@ -82,6 +139,9 @@ impl EventHandler {
                 Keycode::Up => game.rotate(),
                 Keycode::Down => game.start_soft_drop(),
                 Keycode::Space => game.start_hard_drop(),
+                Keycode::Z => game.undo(),
+                Keycode::R => game.rewind(REWIND_FRAMES),
+                Keycode::F => export_fumen(game.to_fumen()),
                 _ => {}
             },
             Event::KeyUp {
@@ -152,6 +212,200 @@ impl EventHandler {
 
         State::GameOver(game_over)
     }
+
+    fn handle_replay(&mut self, mut replay: Replay, event: &Event) -> State {
+        if let Event::KeyDown {
+            keycode: Some(keycode),
+            ..
+        } = *event
+        {
+            match keycode {
+                Keycode::Space => replay.toggle_pause(),
+                Keycode::Period => replay.step_frame(),
+                Keycode::Up => replay.speed_up(),
+                Keycode::Down => replay.slow_down(),
+                Keycode::Left => replay.skip(-REPLAY_SKIP_FRAMES),
+                Keycode::Right => replay.skip(REPLAY_SKIP_FRAMES),
+                Keycode::Home => replay.seek(0),
+                Keycode::F => export_fumen(replay.player().to_fumen()),
+                Keycode::Tab => return replay.take_over(),
+                Keycode::Return => return replay.exit(),
+                _ => {}
+            }
+        }
+
+        State::Replay(replay)
+    }
+
+    fn handle_puzzle_over(&mut self, puzzle_over: PuzzleOver, event: &Event) -> State {
+        match event {
+            Event::KeyDown {
+                keycode: Some(Keycode::Return),
+                ..
+            }
+            | Event::FingerUp { .. } => puzzle_over.retry(),
+            Event::KeyDown {
+                keycode: Some(Keycode::Backspace),
+                ..
+            } => puzzle_over.exit(),
+            _ => State::PuzzleOver(puzzle_over),
+        }
+    }
+
+    /// Handle both players' keys, with player one on the left of the keyboard and player two on
+    /// the right.
+    ///
+    /// Against the CPU there's only one person playing, so both sides of the keyboard control
+    /// player one.
+    fn handle_versus(&mut self, mut versus: Versus, event: &Event) -> State {
+        let right = if versus.cpu().is_some() { 0 } else { 1 };
+
+        match *event {
+            Event::KeyDown {
+                keycode: Some(keycode),
+                ..
+            } => match keycode {
+                Keycode::A => versus.game_mut(0).move_left(),
+                Keycode::D => versus.game_mut(0).move_right(),
+                Keycode::W => versus.game_mut(0).rotate(),
+                Keycode::S => versus.game_mut(0).start_soft_drop(),
+                Keycode::Space => versus.game_mut(0).start_hard_drop(),
+                Keycode::Left => versus.game_mut(right).move_left(),
+                Keycode::Right => versus.game_mut(right).move_right(),
+                Keycode::Up => versus.game_mut(right).rotate(),
+                Keycode::Down => versus.game_mut(right).start_soft_drop(),
+                Keycode::Return => versus.game_mut(right).start_hard_drop(),
+                _ => {}
+            },
+            Event::KeyUp {
+                keycode: Some(Keycode::S),
+                ..
+            } => versus.game_mut(0).stop_drop(),
+            Event::KeyUp {
+                keycode: Some(Keycode::Down),
+                ..
+            } => versus.game_mut(right).stop_drop(),
+            _ => {}
+        }
+        State::Versus(versus)
+    }
+
+    fn handle_versus_over(&mut self, versus_over: VersusOver, event: &Event) -> State {
+        match event {
+            Event::KeyDown {
+                keycode: Some(Keycode::Return),
+                ..
+            } => versus_over.rematch(),
+            Event::KeyDown {
+                keycode: Some(Keycode::Backspace),
+                ..
+            } => versus_over.exit(),
+            _ => State::VersusOver(versus_over),
+        }
+    }
+
+    fn handle_lobby(&mut self, lobby: Lobby, event: &Event) -> State {
+        match event {
+            Event::KeyDown {
+                keycode: Some(Keycode::Backspace),
+                ..
+            } => lobby.exit(),
+            _ => State::Lobby(lobby),
+        }
+    }
+
+    /// Handle the player's keys in an online match, which can't be paused, undone or rewound.
+    fn handle_online(&mut self, mut online: Online, event: &Event) -> State {
+        let game = online.game_mut();
+
+        match *event {
+            Event::KeyDown {
+                keycode: Some(keycode),
+                ..
+            } => match keycode {
+                Keycode::Left => game.move_left(),
+                Keycode::Right => game.move_right(),
+                Keycode::Up => game.rotate(),
+                Keycode::Down => game.start_soft_drop(),
+                Keycode::Space => game.start_hard_drop(),
+                _ => {}
+            },
+            Event::KeyUp {
+                keycode: Some(Keycode::Down),
+                ..
+            } => game.stop_drop(),
+            _ => {}
+        }
+        State::Online(online)
+    }
+
+    fn handle_online_over(&mut self, online_over: OnlineOver, event: &Event) -> State {
+        match event {
+            Event::KeyDown {
+                keycode: Some(Keycode::Return),
+                ..
+            } => online_over.rematch(),
+            Event::KeyDown {
+                keycode: Some(Keycode::Backspace),
+                ..
+            } => online_over.exit(),
+            _ => State::OnlineOver(online_over),
+        }
+    }
+
+    fn handle_dig_over(&mut self, mut dig_over: DigOver, event: &Event) -> State {
+        match event {
+            Event::KeyDown {
+                keycode: Some(keycode),
+                ..
+            } => match keycode {
+                Keycode::Return => {
+                    return dig_over.submit();
+                }
+                Keycode::Backspace if !dig_over.posting_time() => {
+                    return dig_over.exit();
+                }
+                Keycode::Backspace => {
+                    dig_over.backspace();
+                }
+                k => {
+                    dig_over.push_name(&k.name());
+                }
+            },
+            Event::FingerUp { .. } => {
+                if !dig_over.posting_time() {
+                    return dig_over.retry();
+                }
+            }
+            _ => {}
+        }
+
+        State::DigOver(dig_over)
+    }
+}
+
+/// Export a fumen by adding it as a new line at the end of the file at `FUMEN_PATH`, reporting if
+/// it couldn't be written.
+#[cfg(not(target_os = "emscripten"))]
+fn export_fumen(fumen: Fumen) {
+    use std::fs::OpenOptions;
+    use std::io::Write;
+
+    let result = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(FUMEN_PATH)
+        .and_then(|mut file| writeln!(file, "{}", fumen));
+
+    if let Err(e) = result {
+        println!("Failed to export fumen to {}: {}", FUMEN_PATH, e);
+    }
+}
+
+/// Export a fumen by printing it to the browser console, as there's no file to add it to.
+#[cfg(target_os = "emscripten")]
+fn export_fumen(fumen: Fumen) {
+    println!("{}", fumen);
 }
 
 fn exit() -> ! {
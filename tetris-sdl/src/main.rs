@@ -10,7 +10,16 @@ use sdl2::ttf;
 use sdl2::video::Window;
 use sdl2::Sdl;
 
+use tetris::BotProcess;
+use tetris::Difficulty;
+use tetris::DigRace;
+use tetris::Fumen;
+use tetris::Game;
+use tetris::History;
+use tetris::Player;
+use tetris::Puzzle;
 use tetris::State;
+use tetris::TbpBot;
 
 use crate::draw::Drawer;
 use crate::draw::WINDOW_HEIGHT;
@@ -33,6 +42,9 @@ const FONT_MULTIPLE: u16 = 9;
 // Funny division is done here to round to nearest multiple of FONT_MULTIPLE
 const FONT_SIZE: u16 = (WINDOW_HEIGHT / 32) as u16 / FONT_MULTIPLE * FONT_MULTIPLE;
 
+/// An external bot playing the game for the user.
+type Bot = Player<TbpBot<BotProcess>>;
+
 struct Context<'a> {
     drawer: Drawer<'a>,
     event_handler: EventHandler,
@@ -43,6 +55,9 @@ struct Context<'a> {
 
     /// The last time the game was stepped forward
     last_update: Instant,
+
+    /// The bot playing the game, if the user isn't.
+    bot: Option<Bot>,
 }
 
 fn main() {
@@ -73,13 +88,87 @@ fn main() {
     let context = Context {
         drawer: Drawer::new(window.into_canvas().build().unwrap(), font),
         event_handler,
-        state: Some(State::default()),
+        state: Some(initial_state()),
         last_update: Instant::now(),
+        bot: initial_bot(),
     };
 
     play_tetris(context);
 }
 
+/// Choose the state to start in from the command-line arguments:
+///
+/// - `tetris-sdl replay <file>` plays back a saved game.
+/// - `tetris-sdl puzzle <file>` starts a puzzle.
+/// - `tetris-sdl practice <fumen>` starts a practice game from a fumen's board.
+/// - `tetris-sdl dig <rows> <messiness>` starts a dig race.
+/// - `tetris-sdl cpu <difficulty>` starts a versus match against the CPU.
+/// - `tetris-sdl bot <command> [args...]` starts a game played by a bot.
+///
+/// Otherwise the game starts at the title screen.
+fn initial_state() -> State {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match args.as_slice() {
+        [command, path] if command == "replay" => State::replay(load_history(path)),
+        [command, path] if command == "puzzle" => State::puzzle(load_puzzle(path)),
+        [command, fumen] if command == "practice" => {
+            let fumen: Fumen = fumen
+                .parse()
+                .unwrap_or_else(|e| panic!("Invalid fumen {}: {}", fumen, e));
+            State::practice_with_board(fumen.pages()[0].board.clone())
+        }
+        [command, rows, messiness] if command == "dig" => State::dig(DigRace {
+            rows: parse_arg(rows),
+            messiness: parse_arg(messiness),
+        }),
+        [command, level] if command == "cpu" => {
+            let difficulty = Difficulty::ALL
+                .iter()
+                .find(|difficulty| difficulty.to_string() == *level)
+                .unwrap_or_else(|| panic!("Unknown difficulty {}", level));
+            State::versus_cpu(*difficulty)
+        }
+        [command, _, ..] if command == "bot" => State::Play(Game::default()),
+        _ => State::default(),
+    }
+}
+
+/// Start the bot given by `tetris-sdl bot <command> [args...]`, which speaks the Tetris Bot
+/// Protocol over stdin and stdout.
+fn initial_bot() -> Option<Bot> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match args.as_slice() {
+        [command, program, bot_args @ ..] if command == "bot" => {
+            let bot = TbpBot::spawn(std::process::Command::new(program).args(bot_args))
+                .unwrap_or_else(|e| panic!("Failed to start bot {}: {}", program, e));
+            Some(Player::new(bot))
+        }
+        _ => None,
+    }
+}
+
+/// Load a saved game from a JSON file containing a `History` or a `ScoreMessage`.
+fn load_history(path: &str) -> History {
+    let json = std::fs::read_to_string(path)
+        .unwrap_or_else(|e| panic!("Failed to read replay {}: {}", path, e));
+    History::from_json(&json).unwrap_or_else(|e| panic!("Invalid replay {}: {}", path, e))
+}
+
+/// Parse a number from the command-line arguments.
+fn parse_arg(arg: &str) -> u8 {
+    arg.parse()
+        .unwrap_or_else(|e| panic!("Invalid number {}: {}", arg, e))
+}
+
+/// Load a puzzle from a JSON file.
+fn load_puzzle(path: &str) -> Puzzle {
+    let json = std::fs::read_to_string(path)
+        .unwrap_or_else(|e| panic!("Failed to read puzzle {}: {}", path, e));
+    Puzzle::from_json(&json).unwrap_or_else(|e| panic!("Invalid puzzle {}: {}", path, e))
+}
+
 #[cfg(not(target_os = "emscripten"))]
 fn ttf_context() -> ttf::Sdl2TtfContext {
     ttf::init().unwrap()
@@ -130,6 +219,9 @@ impl Context<'_> {
         };
 
         for _ in 0..num_updates {
+            if let State::Play(game) = &mut state {
+                self.play_bot(game);
+            }
             state = state.update();
             if cfg!(not(target_os = "emscripten")) {
                 self.last_update = now;
@@ -142,6 +234,18 @@ impl Context<'_> {
 
         self.state = Some(state);
     }
+
+    /// Let the bot play the game, if there is one, stopping it if something goes wrong.
+    fn play_bot(&mut self, game: &mut Game) {
+        if let Some(player) = &mut self.bot {
+            player.play(game);
+
+            if let Some(error) = player.controller().error() {
+                eprintln!("{}", error);
+                self.bot = None;
+            }
+        }
+    }
 }
 
 fn create_window(sdl_context: &Sdl) -> Window {
@@ -5,80 +5,109 @@ use std::env;
 use std::error::Error;
 use std::fs::{DirBuilder, File};
 use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::thread;
 
+use rocket::serde::{DeserializeOwned, Serialize};
 use rocket::State;
+use tetris::DigTime;
+use tetris::DigTimeMessage;
 use tetris::Score;
 use tetris::ScoreMessage;
+use tetris::DIG_LEADERBOARD_SIZE;
 
 #[macro_use]
 extern crate rocket;
 
-struct ScoresHandler {
-    scores_path: PathBuf,
+mod online;
+
+/// A leaderboard stored in a JSON file, best entry first.
+struct LeaderboardHandler<T> {
+    path: PathBuf,
     lock_path: PathBuf,
+
+    /// The most entries kept on the leaderboard.
+    size: usize,
+
+    /// The entries on the leaderboard before anything is posted.
+    initial: fn() -> Vec<T>,
 }
 
-impl ScoresHandler {
-    fn new(scores_path: PathBuf, lock_path: PathBuf) -> ScoresHandler {
-        ScoresHandler {
-            scores_path,
-            lock_path,
+impl<T: Ord + Serialize + DeserializeOwned> LeaderboardHandler<T> {
+    fn new(conf_dir: &Path, name: &str, size: usize, initial: fn() -> Vec<T>) -> Self {
+        LeaderboardHandler {
+            path: conf_dir.join(format!("{}.json", name)),
+            lock_path: conf_dir.join(format!("{}.json.lock", name)),
+            size,
+            initial,
         }
     }
 
-    fn add_score(&self, message: ScoreMessage) -> Result<Vec<Score>, Box<dyn Error>> {
-        let score = message.score()?;
-
+    fn add(&self, entry: T) -> Result<Vec<T>, Box<dyn Error>> {
         let mut lock = LockFile::open(&self.lock_path)?;
         lock.lock()?;
 
-        let mut scores = self.get_scores();
-        scores.push(score);
-        scores.sort();
-        scores.pop();
+        let mut entries = self.get();
+        entries.push(entry);
+        entries.sort();
+        entries.truncate(self.size);
 
-        let mut file = File::create(&self.scores_path)?;
-        file.write_all(serde_json::to_string(&scores).unwrap().as_bytes())?;
+        let mut file = File::create(&self.path)?;
+        file.write_all(serde_json::to_string(&entries).unwrap().as_bytes())?;
 
         lock.unlock()?;
 
-        Ok(scores)
+        Ok(entries)
     }
 
-    fn get_scores(&self) -> Vec<Score> {
-        match File::open(&self.scores_path) {
+    fn get(&self) -> Vec<T> {
+        match File::open(&self.path) {
             Ok(mut file) => {
-                let mut scores = String::new();
-                file.read_to_string(&mut scores)
-                    .expect("scores file is invalid");
-                serde_json::from_str(&scores).expect("scores file is invalid")
-            }
-            Err(_) => {
-                let mut scores = Vec::new();
-                for _ in 0..10 {
-                    scores.push(Score::new(0, "AEL".to_string()));
-                }
-                scores
+                let mut entries = String::new();
+                file.read_to_string(&mut entries)
+                    .expect("leaderboard file is invalid");
+                serde_json::from_str(&entries).expect("leaderboard file is invalid")
             }
+            Err(_) => (self.initial)(),
         }
     }
 }
 
+fn initial_scores() -> Vec<Score> {
+    let mut scores = Vec::new();
+    for _ in 0..10 {
+        scores.push(Score::new(0, "AEL".to_string()));
+    }
+    scores
+}
+
 #[get("/scores")]
-fn get_scores(scores: &State<ScoresHandler>) -> Json<Vec<Score>> {
-    Json(scores.get_scores())
+fn get_scores(scores: &State<LeaderboardHandler<Score>>) -> Json<Vec<Score>> {
+    Json(scores.get())
 }
 
 #[post("/scores", data = "<message>")]
 fn post_score(
     message: Json<ScoreMessage>,
-    scores: &State<ScoresHandler>,
+    scores: &State<LeaderboardHandler<Score>>,
 ) -> Result<Json<Vec<Score>>, String> {
-    scores
-        .add_score(message.0)
-        .map(Json)
-        .map_err(|e| e.to_string())
+    let score = message.0.score().map_err(|e| e.to_string())?;
+    scores.add(score).map(Json).map_err(|e| e.to_string())
+}
+
+#[get("/dig-times")]
+fn get_dig_times(times: &State<LeaderboardHandler<DigTime>>) -> Json<Vec<DigTime>> {
+    Json(times.get())
+}
+
+#[post("/dig-times", data = "<message>")]
+fn post_dig_time(
+    message: Json<DigTimeMessage>,
+    times: &State<LeaderboardHandler<DigTime>>,
+) -> Result<Json<Vec<DigTime>>, String> {
+    let time = message.0.time().map_err(|e| e.to_string())?;
+    times.add(time).map(Json).map_err(|e| e.to_string())
 }
 
 #[launch]
@@ -86,16 +115,20 @@ fn start() -> _ {
     let mut conf_dir = dirs::home_dir().unwrap();
     conf_dir.push(TETRIS_CONF);
     let _ = DirBuilder::new().create(&conf_dir);
+
+    let online_address = env::var("ONLINE_ADDRESS").unwrap_or_else(|_| ONLINE_ADDRESS.to_string());
+    let listener = TcpListener::bind(&online_address)
+        .unwrap_or_else(|e| panic!("Failed to listen on {}: {}", online_address, e));
+    thread::spawn(move || online::serve(listener));
+
     rocket(conf_dir)
 }
 
 fn rocket(conf_dir: impl Into<PathBuf>) -> rocket::Rocket<rocket::Build> {
     let conf_dir = conf_dir.into();
-    let mut scores_path = conf_dir.clone();
-    scores_path.push("hiscores.json");
-    let mut lock_path = conf_dir;
-    lock_path.push("hiscores.json.lock");
-    let scores = ScoresHandler::new(scores_path, lock_path);
+    let scores = LeaderboardHandler::new(&conf_dir, "hiscores", 10, initial_scores);
+    let dig_times: LeaderboardHandler<DigTime> =
+        LeaderboardHandler::new(&conf_dir, "dig-times", DIG_LEADERBOARD_SIZE, Vec::new);
 
     let static_path = env::var("STATIC_FILES")
         .or_else(|_| env::var("CARGO_MANIFEST_DIR").map(|s| s + "/../static"))
@@ -103,12 +136,19 @@ fn rocket(conf_dir: impl Into<PathBuf>) -> rocket::Rocket<rocket::Build> {
 
     rocket::build()
         .manage(scores)
+        .manage(dig_times)
         .mount("/", FileServer::from(static_path))
-        .mount("/", routes![get_scores, post_score])
+        .mount(
+            "/",
+            routes![get_scores, post_score, get_dig_times, post_dig_time],
+        )
 }
 
 const TETRIS_CONF: &str = ".tetris";
 
+/// Where players connect over WebSockets to play online matches, unless `ONLINE_ADDRESS` is set.
+const ONLINE_ADDRESS: &str = "0.0.0.0:8001";
+
 #[cfg(test)]
 mod test {
     use super::rocket;
@@ -117,6 +157,9 @@ mod test {
     use serde_json::{json, Value};
     use std::path::PathBuf;
     use tempdir::TempDir;
+    use tetris::DigTime;
+    use tetris::DigTimeMessage;
+    use tetris::ScoreMessage;
 
     const GAME: &str = include_str!("../../resources/games/short.json");
 
@@ -133,6 +176,15 @@ mod test {
         assert_eq!(get_scores(&client), short_score());
     }
 
+    #[test]
+    fn valid_scores_in_compact_format_are_added() {
+        let client = client_from_dir(new_config_dir().into_path());
+        let message: ScoreMessage = serde_json::from_str(GAME).expect("valid score message");
+        let body = message.to_compact_json();
+        assert_eq!(post_score(&client, &body), short_score());
+        assert_eq!(get_scores(&client), short_score());
+    }
+
     #[test]
     fn scores_are_persisted() {
         let config_dir = new_config_dir();
@@ -155,6 +207,44 @@ mod test {
         assert_eq!(get_scores(&client2), short_score());
     }
 
+    #[test]
+    fn dig_times_begin_empty() {
+        let client = client_from_dir(new_config_dir().into_path());
+        assert_eq!(get(&client, "/dig-times"), json!([]));
+    }
+
+    #[test]
+    fn valid_dig_times_are_added() {
+        let client = client_from_dir(new_config_dir().into_path());
+        let (frames, body) = dig_race("DIG");
+        let expected = json!([{"frames": frames, "name": "DIG"}]);
+        assert_eq!(post(&client, "/dig-times", &body), expected);
+        assert_eq!(get(&client, "/dig-times"), expected);
+    }
+
+    #[test]
+    fn invalid_dig_times_are_not_added() {
+        let client = client_from_dir(new_config_dir().into_path());
+        let (_, body) = dig_race("NAME TOO LONG");
+        client.post("/dig-times").body(&body).dispatch();
+        assert_eq!(get(&client, "/dig-times"), json!([]));
+    }
+
+    /// Post the recorded time for the standard dig race under the given name, returning the time
+    /// and a message posting it.
+    fn dig_race(name: &str) -> (u32, String) {
+        let body = include_str!("../../resources/games/dig.json");
+        let recorded: DigTimeMessage = serde_json::from_str(body).expect("valid dig time");
+        let history = recorded.history().clone();
+        let frames = recorded.time().expect("valid dig time").frames;
+
+        let message = DigTimeMessage::new(DigTime::new(frames, name.to_string()), history);
+        (
+            frames,
+            serde_json::to_string(&message).expect("valid dig time"),
+        )
+    }
+
     fn client_from_dir(config_dir: impl Into<PathBuf>) -> Client {
         Client::untracked(rocket(config_dir)).expect("valid rocket instance")
     }
@@ -164,14 +254,22 @@ mod test {
     }
 
     fn get_scores(client: &Client) -> Value {
-        let response = client.get("/scores").dispatch();
+        get(client, "/scores")
+    }
+
+    fn post_score(client: &Client, body: &str) -> Value {
+        post(client, "/scores", body)
+    }
+
+    fn get(client: &Client, path: &str) -> Value {
+        let response = client.get(path).dispatch();
         assert_eq!(response.status(), Status::Ok);
         let body = response.into_string().expect("body");
         serde_json::from_str::<Value>(&body).expect("json")
     }
 
-    fn post_score(client: &Client, body: &str) -> Value {
-        let response = client.post("/scores").body(body).dispatch();
+    fn post(client: &Client, path: &str, body: &str) -> Value {
+        let response = client.post(path).body(body).dispatch();
         assert_eq!(response.status(), Status::Ok);
         let body = response.into_string().expect("Expected body in response");
         serde_json::from_str::<Value>(&body).expect("Expected valid JSON response")
@@ -0,0 +1,420 @@
+use std::io;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use tetris::AttackTable;
+use tetris::ClientMessage;
+use tetris::GarbageHoles;
+use tetris::HolePlacement;
+use tetris::MatchResult;
+use tetris::OnlineRecord;
+use tetris::ServerMessage;
+use tungstenite::Message;
+use tungstenite::WebSocket;
+
+/// How long to wait for a message from a player before checking for messages to send them.
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// How long a player has to finish connecting, or to accept a message sent to them, before
+/// they're disconnected.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The most players connected at once. Anyone connecting beyond this is turned away.
+const MAX_PLAYERS: usize = 256;
+
+/// The most garbage rows passed on for a single attack, which is more than any line clear sends.
+const MAX_ATTACK: u32 = 20;
+
+/// Accept players connecting over WebSockets and pair them up into online matches, forever.
+pub fn serve(listener: TcpListener) {
+    let lobby = Arc::new(Lobby::default());
+    let players = Arc::new(AtomicUsize::new(0));
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                println!("Failed to accept player: {}", e);
+                continue;
+            }
+        };
+
+        let connected = match Connected::join(&players) {
+            Some(connected) => connected,
+            None => {
+                println!("Too many players connected, turning one away");
+                continue;
+            }
+        };
+
+        let lobby = lobby.clone();
+        thread::spawn(move || {
+            if let Err(e) = play(stream, &lobby) {
+                println!("Player disconnected: {}", e);
+            }
+            drop(connected);
+        });
+    }
+}
+
+/// Counts a player as connected until this is dropped.
+struct Connected(Arc<AtomicUsize>);
+
+impl Connected {
+    /// Count another connected player, unless there are already `MAX_PLAYERS`.
+    fn join(players: &Arc<AtomicUsize>) -> Option<Self> {
+        if players.fetch_add(1, Ordering::SeqCst) >= MAX_PLAYERS {
+            players.fetch_sub(1, Ordering::SeqCst);
+            return None;
+        }
+        Some(Connected(players.clone()))
+    }
+}
+
+impl Drop for Connected {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Pairs up players waiting for a match.
+#[derive(Default)]
+struct Lobby {
+    /// A match with only one player so far, waiting for an opponent.
+    waiting: Mutex<Option<Arc<Mutex<Match>>>>,
+}
+
+impl Lobby {
+    /// Put a player in a match, returning the match and which player they are, `0` or `1`.
+    fn join(&self, sender: Sender<ServerMessage>) -> (Arc<Mutex<Match>>, usize) {
+        let mut waiting = self.waiting.lock().unwrap();
+
+        match waiting.take() {
+            Some(game) => {
+                game.lock().unwrap().start(sender);
+                (game, 1)
+            }
+            None => {
+                let _ = sender.send(ServerMessage::Waiting);
+                let game = Arc::new(Mutex::new(Match::new(rand::random(), sender)));
+                *waiting = Some(game.clone());
+                (game, 0)
+            }
+        }
+    }
+
+    /// Take a player out of their match, so nobody is paired with them after they've gone.
+    fn leave(&self, game: &Arc<Mutex<Match>>, player: usize) {
+        let mut waiting = self.waiting.lock().unwrap();
+        if waiting
+            .as_ref()
+            .map_or(false, |waiting| Arc::ptr_eq(waiting, game))
+        {
+            *waiting = None;
+        }
+
+        game.lock().unwrap().leave(player);
+    }
+}
+
+/// A match between two players, passing each one's actions and garbage on to the other.
+struct Match {
+    seed: [u32; 4],
+
+    /// Where to send messages for each player. There's only one until an opponent joins.
+    senders: Vec<Sender<ServerMessage>>,
+
+    /// Each player's game as they've reported it, replayed as it arrives to check when the match
+    /// ends.
+    records: [OnlineRecord; 2],
+
+    /// Picks the hole in each garbage row.
+    holes: GarbageHoles,
+
+    /// Whether the match has ended, after which nothing more is passed on.
+    over: bool,
+}
+
+impl Match {
+    fn new(seed: [u32; 4], sender: Sender<ServerMessage>) -> Self {
+        // Online matches are played with the standard rules, the same as the players' games
+        let attack_table = AttackTable::default();
+
+        Match {
+            seed,
+            senders: vec![sender],
+            records: [
+                OnlineRecord::new(seed, attack_table),
+                OnlineRecord::new(seed, attack_table),
+            ],
+            holes: GarbageHoles::new(seed, HolePlacement::Clean),
+            over: false,
+        }
+    }
+
+    /// Add the second player and tell both players the match has started.
+    fn start(&mut self, sender: Sender<ServerMessage>) {
+        self.senders.push(sender);
+        for player in 0..2 {
+            self.send(player, ServerMessage::Start(self.seed));
+        }
+    }
+
+    fn send(&self, player: usize, message: ServerMessage) {
+        if let Some(sender) = self.senders.get(player) {
+            // The player may have just left, in which case the match is ending anyway
+            let _ = sender.send(message);
+        }
+    }
+
+    /// Handle a message from a player, passing their actions and garbage on to the opponent.
+    ///
+    /// A player who sends actions that can't happen forfeits the match. When a player tops out,
+    /// their opponent's game is checked before they're declared the winner, and if it isn't
+    /// valid the opponent forfeits instead.
+    fn receive(&mut self, player: usize, message: ClientMessage) {
+        let opponent = 1 - player;
+        if self.over || self.senders.len() < 2 {
+            return;
+        }
+
+        match message {
+            ClientMessage::Actions(batch) => match self.records[player].push_actions(&batch) {
+                Ok(()) => self.send(opponent, ServerMessage::Opponent(batch)),
+                Err(e) => {
+                    println!("Player {} forfeits: {}", player, e);
+                    self.end(opponent);
+                }
+            },
+            ClientMessage::Attack(rows) => {
+                let rows = rows.min(MAX_ATTACK);
+                let holes = self.holes.pick(rows);
+                self.records[player].push_attack(rows);
+                self.records[opponent].push_garbage(&holes);
+                self.send(opponent, ServerMessage::Garbage(holes));
+            }
+            ClientMessage::ToppedOut => match self.records[opponent].validate() {
+                Ok(()) => self.end(opponent),
+                Err(e) => {
+                    println!("Player {} forfeits: {}", opponent, e);
+                    self.end(player);
+                }
+            },
+        }
+    }
+
+    /// End the match if a player leaves before it's over, so their opponent wins.
+    fn leave(&mut self, player: usize) {
+        if !self.over && self.senders.len() == 2 {
+            self.end(1 - player);
+        }
+    }
+
+    fn end(&mut self, winner: usize) {
+        self.over = true;
+        self.send(winner, ServerMessage::Over(MatchResult::Won));
+        self.send(1 - winner, ServerMessage::Over(MatchResult::Lost));
+    }
+}
+
+/// Play a connected player's side of a match, until the match ends or they leave.
+fn play(stream: TcpStream, lobby: &Lobby) -> tungstenite::Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+
+    let mut socket = tungstenite::accept(stream).map_err(|e| match e {
+        tungstenite::HandshakeError::Failure(e) => e,
+        // The stream only stops blocking when the timeout runs out
+        tungstenite::HandshakeError::Interrupted(_) => tungstenite::Error::Io(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "player didn't finish connecting",
+        )),
+    })?;
+    socket.get_mut().set_read_timeout(Some(POLL_INTERVAL))?;
+
+    let (sender, receiver) = mpsc::channel();
+    let (game, player) = lobby.join(sender);
+
+    let result = relay(&mut socket, &game, player, &receiver);
+    lobby.leave(&game, player);
+    result
+}
+
+/// Pass messages between a player and their match, until the match is over.
+fn relay(
+    socket: &mut WebSocket<TcpStream>,
+    game: &Mutex<Match>,
+    player: usize,
+    receiver: &Receiver<ServerMessage>,
+) -> tungstenite::Result<()> {
+    loop {
+        for message in receiver.try_iter() {
+            let over = matches!(message, ServerMessage::Over(_));
+            let text = serde_json::to_string(&message).expect("messages can be serialized");
+            socket.send(Message::Text(text))?;
+
+            if over {
+                return socket.close(None);
+            }
+        }
+
+        match socket.read() {
+            Ok(Message::Text(text)) => match serde_json::from_str(&text) {
+                Ok(message) => game.lock().unwrap().receive(player, message),
+                Err(e) => println!("Invalid message from player: {}", e),
+            },
+            Ok(Message::Close(_)) => return Ok(()),
+            Ok(_) => {}
+            Err(e) if timed_out(&e) => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Return whether reading from a player stopped only because they had nothing to send yet.
+fn timed_out(error: &tungstenite::Error) -> bool {
+    match error {
+        tungstenite::Error::Io(e) => matches!(
+            e.kind(),
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+        ),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::serve;
+    use std::io::Read;
+    use std::net::TcpListener;
+    use std::net::TcpStream;
+    use std::thread;
+    use tetris::Lobby;
+    use tetris::MatchResult;
+    use tetris::State;
+
+    /// Start a match server on a free local port, returning its URL.
+    fn start_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("free local port");
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        thread::spawn(move || serve(listener));
+        url
+    }
+
+    /// Update both players' states until neither is in the lobby, or give up.
+    fn start_match(players: &mut [State; 2]) {
+        for _ in 0..1000 {
+            if players
+                .iter()
+                .all(|state| !matches!(state, State::Lobby(_)))
+            {
+                return;
+            }
+            update(players);
+            thread::sleep(std::time::Duration::from_millis(1));
+        }
+        panic!("the players should have been matched");
+    }
+
+    fn update(players: &mut [State; 2]) {
+        for state in players.iter_mut() {
+            *state = std::mem::take(state).update();
+        }
+    }
+
+    fn result(state: &State) -> Option<MatchResult> {
+        match state {
+            State::OnlineOver(online_over) => Some(online_over.result()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn a_player_waits_in_the_lobby_for_an_opponent() {
+        let url = start_server();
+        let mut state = State::Lobby(Lobby::connect(&url));
+
+        for _ in 0..10 {
+            state = state.update();
+        }
+
+        match state {
+            State::Lobby(lobby) => assert_eq!(lobby.error(), None),
+            _ => panic!("the player should still be waiting"),
+        }
+    }
+
+    #[test]
+    fn matched_players_follow_each_others_games_until_one_tops_out() {
+        let url = start_server();
+        let mut players = [
+            State::Lobby(Lobby::connect(&url)),
+            State::Lobby(Lobby::connect(&url)),
+        ];
+        start_match(&mut players);
+
+        for _ in 0..10_000 {
+            if players.iter().all(|state| result(state).is_some()) {
+                break;
+            }
+            // Player two drops every piece straight down, so tops out long before player one
+            if let State::Online(online) = &mut players[1] {
+                online.game_mut().start_hard_drop();
+            }
+            update(&mut players);
+            thread::sleep(std::time::Duration::from_micros(100));
+        }
+
+        match &players {
+            [State::OnlineOver(winner), State::OnlineOver(loser)] => {
+                assert_eq!(winner.result(), MatchResult::Won);
+                assert_eq!(loser.result(), MatchResult::Lost);
+                assert_eq!(winner.opponent().board(), loser.game().board());
+                assert!(winner.opponent().is_finished());
+            }
+            _ => panic!("the match should be over"),
+        }
+    }
+
+    #[test]
+    fn a_player_who_never_finishes_connecting_is_disconnected() {
+        let url = start_server();
+        let mut stream = TcpStream::connect(url.trim_start_matches("ws://")).unwrap();
+        stream.set_read_timeout(Some(super::TIMEOUT * 4)).unwrap();
+
+        let mut buffer = [0; 16];
+        assert_eq!(stream.read(&mut buffer).unwrap(), 0);
+    }
+
+    #[test]
+    fn a_player_wins_if_their_opponent_leaves() {
+        let url = start_server();
+        let mut players = [
+            State::Lobby(Lobby::connect(&url)),
+            State::Lobby(Lobby::connect(&url)),
+        ];
+        start_match(&mut players);
+
+        let [mut player, opponent] = players;
+        drop(opponent);
+
+        for _ in 0..1000 {
+            if result(&player).is_some() {
+                break;
+            }
+            player = player.update();
+            thread::sleep(std::time::Duration::from_millis(1));
+        }
+
+        assert_eq!(result(&player), Some(MatchResult::Won));
+    }
+}
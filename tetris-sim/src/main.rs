@@ -0,0 +1,275 @@
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::io::BufWriter;
+use std::io::Write;
+use std::num::NonZeroUsize;
+use std::ops::Range;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+use std::process::Command;
+use std::thread;
+
+use serde_derive::Serialize;
+
+use tetris::Ai;
+use tetris::GameConfig;
+use tetris::TbpBot;
+use tetris::Weights;
+
+use crate::simulate::Outcome;
+use crate::stats::Summary;
+
+mod simulate;
+mod stats;
+mod tune;
+
+type Error = Box<dyn std::error::Error + Send + Sync>;
+
+const USAGE: &str = "\
+Usage: tetris-sim run [options]
+
+Plays many games without rendering them, writing every game's history and a summary to disk.
+
+Options:
+    --seeds START..END  Play a game for each seed in the range [default: 0..100]
+    --games N           Play N games, the same as --seeds 0..N
+    --threads N         Play on N threads [default: the number of cores]
+    --config FILE       Play with the rules in a JSON game config [default: the standard rules]
+    --weights FILE      Play with the AI weights in a JSON file [default: the built-in weights]
+    --lookahead N       Look at N shapes from the preview when picking a placement [default: 1]
+    --max-pieces N      Stop each game after N pieces [default: 1000]
+    --out DIR           Write results to DIR [default: sim]
+    --bot COMMAND...    Play with a Tetris Bot Protocol bot instead of the AI, taking every
+                        remaining argument as the command to start it
+
+The best weights from `tetris-sim tune` can be played with `--weights tune/weights.json`.";
+
+/// What plays the simulated games.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+enum Agent {
+    /// The built-in AI.
+    Ai { weights: Weights, lookahead: usize },
+    /// An external bot, started with the given command.
+    Bot { command: Vec<String> },
+}
+
+/// Options for a simulation run, given on the command line.
+struct Options {
+    seeds: Range<u32>,
+    threads: usize,
+    config: GameConfig,
+    agent: Agent,
+    max_pieces: u32,
+    out: PathBuf,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Options, Error> {
+        let mut options = Options {
+            seeds: 0..100,
+            threads: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            config: GameConfig::default(),
+            agent: Agent::Ai {
+                weights: Weights::default(),
+                lookahead: 1,
+            },
+            max_pieces: 1000,
+            out: PathBuf::from("sim"),
+        };
+
+        let mut args = args.iter();
+
+        while let Some(flag) = args.next() {
+            if flag == "--bot" {
+                let command: Vec<String> = args.by_ref().cloned().collect();
+                if command.is_empty() {
+                    return Err("--bot needs a command".into());
+                }
+                options.agent = Agent::Bot { command };
+                break;
+            }
+
+            let value = args
+                .next()
+                .ok_or_else(|| format!("{} needs a value", flag))?;
+
+            match flag.as_str() {
+                "--seeds" => options.seeds = parse_range(value)?,
+                "--games" => options.seeds = 0..value.parse()?,
+                "--threads" => options.threads = value.parse()?,
+                "--config" => options.config = GameConfig::from_json(&fs::read_to_string(value)?)?,
+                "--weights" => {
+                    if let Agent::Ai { weights, .. } = &mut options.agent {
+                        *weights = serde_json::from_str(&fs::read_to_string(value)?)?;
+                    }
+                }
+                "--lookahead" => {
+                    if let Agent::Ai { lookahead, .. } = &mut options.agent {
+                        *lookahead = value.parse()?;
+                    }
+                }
+                "--max-pieces" => options.max_pieces = value.parse()?,
+                "--out" => options.out = PathBuf::from(value),
+                _ => return Err(format!("Unknown option {}\n\n{}", flag, USAGE).into()),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+/// Parse a range of seeds written as `START..END`.
+fn parse_range(range: &str) -> Result<Range<u32>, Error> {
+    let (start, end) = range
+        .split_once("..")
+        .ok_or_else(|| format!("Expected a range like 0..100, but was {}", range))?;
+    Ok(start.parse()?..end.parse()?)
+}
+
+/// The results of a run, written to `stats.json`.
+#[derive(Serialize)]
+struct Report {
+    games: usize,
+    topped_out: usize,
+    config: GameConfig,
+    agent: Agent,
+    score: Option<Summary>,
+    lines: Option<Summary>,
+    pieces: Option<Summary>,
+    frames: Option<Summary>,
+}
+
+/// The results of a game, written as a row of `summary.csv`.
+struct Row {
+    seed: u32,
+    score: u32,
+    lines: u32,
+    pieces: u32,
+    frames: u32,
+    topped_out: bool,
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let result = match args.split_first() {
+        Some((command, rest)) if command == "run" => Options::parse(rest).and_then(|o| run(&o)),
+        Some((command, rest)) if command == "tune" => tune::tune(rest),
+        _ => Err(format!("{}\n\n{}", USAGE, tune::USAGE).into()),
+    };
+
+    if let Err(error) = result {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
+
+/// Play every game in the run, writing the results into the output directory.
+fn run(options: &Options) -> Result<(), Error> {
+    let histories = options.out.join("histories");
+    fs::create_dir_all(&histories)?;
+
+    let total = options.seeds.len();
+    let mut rows = Vec::with_capacity(total);
+
+    let mut on_outcome = |outcome: Outcome| -> Result<(), Error> {
+        let path = histories.join(format!("{}.json", outcome.seed));
+        fs::write(path, serde_json::to_string(&outcome.history)?)?;
+
+        rows.push(Row {
+            seed: outcome.seed,
+            score: outcome.score,
+            lines: outcome.lines,
+            pieces: outcome.pieces,
+            frames: outcome.frames,
+            topped_out: outcome.topped_out,
+        });
+        eprint!("\rPlayed {}/{} games", rows.len(), total);
+        Ok(())
+    };
+
+    let (config, max_pieces) = (options.config, options.max_pieces);
+
+    match &options.agent {
+        Agent::Ai { weights, lookahead } => simulate::run_parallel(
+            options.seeds.clone(),
+            options.threads,
+            || Ok(Ai::new(*weights, *lookahead)),
+            |ai, seed| Ok(simulate::play(ai, seed, config, max_pieces)),
+            &mut on_outcome,
+        )?,
+        Agent::Bot { command } => simulate::run_parallel(
+            options.seeds.clone(),
+            options.threads,
+            || {
+                Ok(TbpBot::spawn(
+                    Command::new(&command[0]).args(&command[1..]),
+                )?)
+            },
+            |bot, seed| {
+                let outcome = simulate::play(bot, seed, config, max_pieces);
+                match bot.error() {
+                    Some(error) => Err(format!("Bot failed on seed {}: {}", seed, error).into()),
+                    None => Ok(outcome),
+                }
+            },
+            &mut on_outcome,
+        )?,
+    }
+    eprintln!();
+
+    rows.sort_by_key(|row| row.seed);
+    write_summary(&options.out.join("summary.csv"), &rows)?;
+
+    let report = Report {
+        games: rows.len(),
+        topped_out: rows.iter().filter(|row| row.topped_out).count(),
+        config: options.config,
+        agent: options.agent.clone(),
+        score: Summary::of(rows.iter().map(|row| row.score)),
+        lines: Summary::of(rows.iter().map(|row| row.lines)),
+        pieces: Summary::of(rows.iter().map(|row| row.pieces)),
+        frames: Summary::of(rows.iter().map(|row| row.frames)),
+    };
+    fs::write(
+        options.out.join("stats.json"),
+        serde_json::to_string_pretty(&report)?,
+    )?;
+
+    print_report(&report);
+    Ok(())
+}
+
+/// Write a CSV file with a row for every game.
+fn write_summary(path: &Path, rows: &[Row]) -> Result<(), Error> {
+    let mut file = BufWriter::new(File::create(path)?);
+    writeln!(file, "seed,score,lines,pieces,frames,topped_out")?;
+    for row in rows {
+        writeln!(
+            file,
+            "{},{},{},{},{},{}",
+            row.seed, row.score, row.lines, row.pieces, row.frames, row.topped_out
+        )?;
+    }
+    file.flush()?;
+    Ok(())
+}
+
+fn print_report(report: &Report) {
+    println!("games   {}  topped out {}", report.games, report.topped_out);
+
+    let summaries = [
+        ("score", &report.score),
+        ("lines", &report.lines),
+        ("pieces", &report.pieces),
+        ("frames", &report.frames),
+    ];
+    for (name, summary) in &summaries {
+        if let Some(summary) = summary {
+            println!("{:<7} {}", name, summary);
+        }
+    }
+}
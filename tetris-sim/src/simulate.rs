@@ -0,0 +1,220 @@
+use std::ops::Range;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::thread;
+
+use tetris::Controller;
+use tetris::Game;
+use tetris::GameConfig;
+use tetris::History;
+use tetris::Player;
+use tetris::StepResult;
+
+/// How a simulated game went.
+pub struct Outcome {
+    /// The number the game's seed was made from.
+    pub seed: u32,
+    pub score: u32,
+    pub lines: u32,
+    pub pieces: u32,
+    pub frames: u32,
+
+    /// Whether the game ended in a game over, rather than reaching the piece limit.
+    pub topped_out: bool,
+
+    pub history: History,
+}
+
+/// Make a game seed from a number, so runs can be given a simple range of seeds.
+///
+/// Neighbouring numbers are scrambled so their games aren't similar, and the seed is never all
+/// zeros.
+pub fn game_seed(number: u32) -> [u32; 4] {
+    let mut state = number;
+    let mut next = || {
+        // A round of splitmix32
+        state = state.wrapping_add(0x9e37_79b9);
+        let mut z = state;
+        z = (z ^ (z >> 16)).wrapping_mul(0x85eb_ca6b);
+        z = (z ^ (z >> 13)).wrapping_mul(0xc2b2_ae35);
+        z ^ (z >> 16)
+    };
+    [next(), next(), next(), next() | 1]
+}
+
+/// Play a game with the given rules to the end, or until the given number of pieces are placed.
+pub fn play<C: Controller>(
+    controller: &mut C,
+    seed: u32,
+    config: GameConfig,
+    max_pieces: u32,
+) -> Outcome {
+    let mut player = Player::new(controller);
+    let mut game = Game::with_config(game_seed(seed), config);
+
+    let topped_out = loop {
+        if game.pieces_placed() >= max_pieces {
+            break false;
+        }
+        player.play(&mut game);
+        if game.step() == StepResult::GameOver {
+            break true;
+        }
+    };
+
+    Outcome {
+        seed,
+        score: game.score(),
+        lines: game.lines_cleared(),
+        pieces: game.pieces_placed(),
+        frames: game.statistics().frames(),
+        topped_out,
+        history: game.history().clone(),
+    }
+}
+
+/// Run a task for every number in the range, such as playing the game with that seed, spread
+/// across the given number of threads.
+///
+/// Each thread makes its own worker with `new_worker`, such as a controller, then runs tasks with
+/// `run_task`. Outcomes are passed to `on_outcome` on the calling thread as they finish, in no
+/// particular order. The first error stops the run.
+pub fn run_parallel<W, T, E>(
+    tasks: Range<u32>,
+    threads: usize,
+    new_worker: impl Fn() -> Result<W, E> + Sync,
+    run_task: impl Fn(&mut W, u32) -> Result<T, E> + Sync,
+    mut on_outcome: impl FnMut(T) -> Result<(), E>,
+) -> Result<(), E>
+where
+    T: Send,
+    E: Send,
+{
+    let next_task = AtomicU64::new(u64::from(tasks.start));
+    let end = u64::from(tasks.end);
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads.max(1))
+            .map(|_| {
+                let sender = sender.clone();
+                let (next_task, new_worker, run_task) = (&next_task, &new_worker, &run_task);
+
+                scope.spawn(move || -> Result<(), E> {
+                    let result = (|| {
+                        let mut worker = new_worker()?;
+                        loop {
+                            let task = next_task.fetch_add(1, Ordering::Relaxed);
+                            if task >= end {
+                                return Ok(());
+                            }
+                            let outcome = run_task(&mut worker, task as u32)?;
+                            if sender.send(outcome).is_err() {
+                                // The run was stopped
+                                return Ok(());
+                            }
+                        }
+                    })();
+
+                    if result.is_err() {
+                        next_task.store(end, Ordering::Relaxed);
+                    }
+                    result
+                })
+            })
+            .collect();
+
+        drop(sender);
+
+        let mut result = Ok(());
+        for outcome in receiver {
+            result = on_outcome(outcome);
+            if result.is_err() {
+                next_task.store(end, Ordering::Relaxed);
+                break;
+            }
+        }
+
+        for worker in workers {
+            let worker_result = worker.join().expect("simulation thread panicked");
+            result = result.and(worker_result);
+        }
+
+        result
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use tetris::Ai;
+    use tetris::Weights;
+
+    use super::*;
+
+    fn ai() -> Ai {
+        Ai::new(Weights::default(), 0)
+    }
+
+    #[test]
+    fn seeds_are_never_all_zero() {
+        for number in 0..1000 {
+            assert_ne!(game_seed(number), [0; 4]);
+        }
+    }
+
+    #[test]
+    fn the_same_seed_plays_the_same_game() {
+        let first = play(&mut ai(), 3, GameConfig::default(), 20);
+        let second = play(&mut ai(), 3, GameConfig::default(), 20);
+
+        assert_eq!(first.pieces, 20);
+        assert_eq!(first.frames, second.frames);
+        assert_eq!(first.history.to_bytes(), second.history.to_bytes());
+    }
+
+    #[test]
+    fn a_played_game_replays_to_the_same_score() {
+        let config = GameConfig {
+            line_scores: [1, 2, 3, 4],
+            ..GameConfig::default()
+        };
+        let outcome = play(&mut ai(), 7, config, 50);
+
+        assert!(outcome.lines > 0);
+        assert_eq!(outcome.history.replay(), outcome.score);
+    }
+
+    #[test]
+    fn every_task_is_run_once_across_threads() {
+        let mut seeds = Vec::new();
+
+        run_parallel(
+            10..30,
+            4,
+            || Ok::<_, ()>(()),
+            |_, seed| Ok(seed),
+            |seed| {
+                seeds.push(seed);
+                Ok(())
+            },
+        )
+        .unwrap();
+
+        seeds.sort_unstable();
+        assert_eq!(seeds, (10..30).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn an_error_stops_the_run() {
+        let result = run_parallel(
+            0..1000,
+            2,
+            || Ok(()),
+            |_, seed| if seed == 5 { Err(seed) } else { Ok(seed) },
+            |_| Ok(()),
+        );
+
+        assert_eq!(result, Err(5));
+    }
+}
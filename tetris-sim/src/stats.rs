@@ -0,0 +1,89 @@
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use serde_derive::Serialize;
+
+/// Summary statistics of a set of values, such as the scores of every game in a run.
+#[derive(Serialize, Clone, PartialEq, Debug)]
+pub struct Summary {
+    pub mean: f64,
+    pub std_dev: f64,
+    pub min: u32,
+    pub median: f64,
+    pub max: u32,
+}
+
+impl Summary {
+    /// Summarise the values, or return `None` if there are none.
+    pub fn of(values: impl IntoIterator<Item = u32>) -> Option<Summary> {
+        let mut values: Vec<u32> = values.into_iter().collect();
+        values.sort_unstable();
+
+        let (&min, &max) = (values.first()?, values.last()?);
+        let count = values.len() as f64;
+
+        let mean = values.iter().map(|&value| f64::from(value)).sum::<f64>() / count;
+        let variance = values
+            .iter()
+            .map(|&value| (f64::from(value) - mean).powi(2))
+            .sum::<f64>()
+            / count;
+
+        let middle = values.len() / 2;
+        let median = if values.len() % 2 == 0 {
+            (f64::from(values[middle - 1]) + f64::from(values[middle])) / 2.0
+        } else {
+            f64::from(values[middle])
+        };
+
+        Some(Summary {
+            mean,
+            std_dev: variance.sqrt(),
+            min,
+            median,
+            max,
+        })
+    }
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "mean {:.1} ± {:.1}  min {}  median {:.1}  max {}",
+            self.mean, self.std_dev, self.min, self.median, self.max
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_values_have_no_summary() {
+        assert_eq!(Summary::of(vec![]), None);
+    }
+
+    #[test]
+    fn values_are_summarised() {
+        let summary = Summary::of(vec![9, 2, 4, 4, 5, 5, 4, 7]).unwrap();
+
+        assert_eq!(
+            summary,
+            Summary {
+                mean: 5.0,
+                std_dev: 2.0,
+                min: 2,
+                median: 4.5,
+                max: 9,
+            }
+        );
+    }
+
+    #[test]
+    fn the_median_of_an_odd_number_of_values_is_the_middle_value() {
+        assert_eq!(Summary::of(vec![3, 1, 2]).unwrap().median, 2.0);
+    }
+}
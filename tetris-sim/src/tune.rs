@@ -0,0 +1,374 @@
+use std::convert::TryFrom;
+use std::fs;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::thread;
+
+use rand::distributions::IndependentSample;
+use rand::distributions::Normal;
+use rand::Rng;
+use rand::SeedableRng;
+use rand::XorShiftRng;
+use serde_derive::{Deserialize, Serialize};
+
+use tetris::Ai;
+use tetris::GameConfig;
+use tetris::Weights;
+
+use crate::simulate;
+use crate::simulate::game_seed;
+use crate::Error;
+
+pub const USAGE: &str = "\
+Usage: tetris-sim tune [options]
+
+Tunes the AI's weights with the cross-entropy method: each generation, a population of weights is
+sampled from a normal distribution, every one plays the same games, and the distribution moves
+towards the weights with the highest mean score.
+
+Options:
+    --generations N     Run N generations [default: 20]
+    --population N      Try N sets of weights each generation [default: 50]
+    --elite N           Move towards the best N sets of weights [default: 10]
+    --games N           Play N games with each set of weights [default: 10]
+    --noise X           Add X to the standard deviation, shrinking to zero by the last
+                        generation, to stop the search settling too early [default: 0.1]
+    --seed N            Seed for sampling weights, games are the same for any seed [default: 0]
+    --threads N         Play on N threads [default: the number of cores]
+    --config FILE       Play with the rules in a JSON game config [default: the standard rules]
+    --lookahead N       Look at N shapes from the preview when picking a placement [default: 0]
+    --max-pieces N      Stop each game after N pieces [default: 500]
+    --resume FILE       Continue from a checkpoint written by an earlier run
+    --out DIR           Write checkpoints and the best weights to DIR [default: tune]";
+
+/// Options for tuning the AI, given on the command line.
+struct Options {
+    generations: u32,
+    population: usize,
+    elite: usize,
+    games: u32,
+    noise: f64,
+    seed: u32,
+    threads: usize,
+    config: GameConfig,
+    lookahead: usize,
+    max_pieces: u32,
+    resume: Option<PathBuf>,
+    out: PathBuf,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Options, Error> {
+        let mut options = Options {
+            generations: 20,
+            population: 50,
+            elite: 10,
+            games: 10,
+            noise: 0.1,
+            seed: 0,
+            threads: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            config: GameConfig::default(),
+            lookahead: 0,
+            max_pieces: 500,
+            resume: None,
+            out: PathBuf::from("tune"),
+        };
+
+        let mut args = args.iter();
+
+        while let Some(flag) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| format!("{} needs a value", flag))?;
+
+            match flag.as_str() {
+                "--generations" => options.generations = value.parse()?,
+                "--population" => options.population = value.parse()?,
+                "--elite" => options.elite = value.parse()?,
+                "--games" => options.games = value.parse()?,
+                "--noise" => options.noise = value.parse()?,
+                "--seed" => options.seed = value.parse()?,
+                "--threads" => options.threads = value.parse()?,
+                "--config" => options.config = GameConfig::from_json(&fs::read_to_string(value)?)?,
+                "--lookahead" => options.lookahead = value.parse()?,
+                "--max-pieces" => options.max_pieces = value.parse()?,
+                "--resume" => options.resume = Some(PathBuf::from(value)),
+                "--out" => options.out = PathBuf::from(value),
+                _ => return Err(format!("Unknown option {}\n\n{}", flag, USAGE).into()),
+            }
+        }
+
+        if options.elite == 0 || options.elite > options.population {
+            return Err("--elite must be between 1 and the population".into());
+        }
+
+        if options.games == 0 {
+            return Err("--games must be at least 1".into());
+        }
+
+        Ok(options)
+    }
+}
+
+/// A set of weights that has been tried, and how well it played.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+struct Candidate {
+    weights: Weights,
+
+    /// The mean score of the games played with the weights.
+    fitness: f64,
+}
+
+/// The state of the search after a generation, written to disk so tuning can be resumed.
+#[derive(Serialize, Deserialize, Debug)]
+struct Checkpoint {
+    generation: u32,
+
+    /// Every set of weights tried in the generation, best first.
+    population: Vec<Candidate>,
+
+    /// The best weights tried in any generation so far.
+    best: Candidate,
+
+    /// The mean of the distribution to sample the next generation from.
+    mean: Weights,
+
+    /// The standard deviation of the distribution to sample the next generation from.
+    std_dev: Weights,
+}
+
+/// Tune the AI's weights, as described in `USAGE`.
+pub fn tune(args: &[String]) -> Result<(), Error> {
+    let options = Options::parse(args)?;
+    fs::create_dir_all(&options.out)?;
+
+    let (first_generation, mut mean, mut std_dev, mut best) = match &options.resume {
+        Some(path) => {
+            let checkpoint: Checkpoint = serde_json::from_str(&fs::read_to_string(path)?)?;
+            let next_generation = checkpoint.generation + 1;
+            let (mean, std_dev) = (checkpoint.mean, checkpoint.std_dev);
+            (next_generation, mean, std_dev, Some(checkpoint.best))
+        }
+        None => (0, Weights::default(), from_array([0.5; 5]), None),
+    };
+
+    for generation in first_generation..options.generations {
+        let mut rng = XorShiftRng::from_seed(game_seed(options.seed ^ game_seed(generation)[0]));
+        let population: Vec<Weights> = (0..options.population)
+            .map(|_| sample(&mut rng, &mean, &std_dev))
+            .collect();
+
+        let mut population = evaluate(&options, generation, &population)?;
+        population.sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
+
+        if best.map_or(true, |best| population[0].fitness > best.fitness) {
+            best = Some(population[0]);
+        }
+
+        let noise =
+            options.noise * (1.0 - f64::from(generation + 1) / f64::from(options.generations));
+        let (next_mean, next_std_dev) = fit(&population[..options.elite], noise.max(0.0));
+        mean = next_mean;
+        std_dev = next_std_dev;
+
+        let checkpoint = Checkpoint {
+            generation,
+            population,
+            best: best.expect("a generation has been evaluated"),
+            mean,
+            std_dev,
+        };
+
+        let path = options
+            .out
+            .join(format!("generation-{:03}.json", generation));
+        fs::write(path, serde_json::to_string_pretty(&checkpoint)?)?;
+        fs::write(
+            options.out.join("weights.json"),
+            serde_json::to_string_pretty(&checkpoint.best.weights)?,
+        )?;
+
+        println!(
+            "generation {:<3} best {:.1}  elite mean {:.1}  best so far {:.1}",
+            generation,
+            checkpoint.population[0].fitness,
+            mean_fitness(&checkpoint.population[..options.elite]),
+            checkpoint.best.fitness,
+        );
+    }
+
+    Ok(())
+}
+
+/// Play every set of weights in the population, returning their fitness.
+///
+/// Every set of weights in a generation plays the same games, so they're compared fairly, while
+/// each generation plays new games so the weights don't overfit.
+fn evaluate(
+    options: &Options,
+    generation: u32,
+    population: &[Weights],
+) -> Result<Vec<Candidate>, Error> {
+    let games = options.games;
+    let num_tasks = u32::try_from(population.len())?
+        .checked_mul(games)
+        .ok_or("--population times --games is too large")?;
+    // Check the last seed fits too, so the seed for each task can't overflow
+    generation
+        .checked_mul(games)
+        .and_then(|first_seed| first_seed.checked_add(games))
+        .ok_or("--generations times --games is too large")?;
+    let mut total_scores = vec![0u64; population.len()];
+
+    simulate::run_parallel(
+        0..num_tasks,
+        options.threads,
+        || Ok::<_, Error>(()),
+        |_, task| {
+            let candidate = (task / games) as usize;
+            let seed = generation * games + task % games;
+            let mut ai = Ai::new(population[candidate], options.lookahead);
+            let outcome = simulate::play(&mut ai, seed, options.config, options.max_pieces);
+            Ok((candidate, outcome.score))
+        },
+        |(candidate, score)| {
+            total_scores[candidate] += u64::from(score);
+            Ok(())
+        },
+    )?;
+
+    Ok(population
+        .iter()
+        .zip(total_scores)
+        .map(|(&weights, total)| Candidate {
+            weights,
+            fitness: total as f64 / f64::from(games),
+        })
+        .collect())
+}
+
+/// Sample a set of weights from a normal distribution for each weight.
+fn sample<R: Rng>(rng: &mut R, mean: &Weights, std_dev: &Weights) -> Weights {
+    let (mean, std_dev) = (to_array(mean), to_array(std_dev));
+    let mut weights = [0.0; 5];
+    for (i, weight) in weights.iter_mut().enumerate() {
+        *weight = Normal::new(mean[i], std_dev[i]).ind_sample(rng);
+    }
+    from_array(weights)
+}
+
+/// Fit a normal distribution for each weight to the elite candidates, adding the given noise to
+/// the standard deviation.
+fn fit(elite: &[Candidate], noise: f64) -> (Weights, Weights) {
+    let count = elite.len() as f64;
+    let mut mean = [0.0; 5];
+    let mut std_dev = [0.0; 5];
+
+    for candidate in elite {
+        for (sum, weight) in mean.iter_mut().zip(&to_array(&candidate.weights)) {
+            *sum += weight / count;
+        }
+    }
+
+    for candidate in elite {
+        for (i, weight) in to_array(&candidate.weights).iter().enumerate() {
+            std_dev[i] += (weight - mean[i]).powi(2) / count;
+        }
+    }
+
+    for value in &mut std_dev {
+        *value = value.sqrt() + noise;
+    }
+
+    (from_array(mean), from_array(std_dev))
+}
+
+fn mean_fitness(candidates: &[Candidate]) -> f64 {
+    candidates.iter().map(|c| c.fitness).sum::<f64>() / candidates.len() as f64
+}
+
+fn to_array(weights: &Weights) -> [f64; 5] {
+    [
+        weights.aggregate_height,
+        weights.holes,
+        weights.bumpiness,
+        weights.wells,
+        weights.lines_cleared,
+    ]
+}
+
+fn from_array(array: [f64; 5]) -> Weights {
+    let [aggregate_height, holes, bumpiness, wells, lines_cleared] = array;
+    Weights {
+        aggregate_height,
+        holes,
+        bumpiness,
+        wells,
+        lines_cleared,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(weight: f64, fitness: f64) -> Candidate {
+        Candidate {
+            weights: from_array([weight; 5]),
+            fitness,
+        }
+    }
+
+    #[test]
+    fn the_distribution_is_fit_to_the_elite() {
+        let elite = [candidate(1.0, 10.0), candidate(3.0, 5.0)];
+
+        let (mean, std_dev) = fit(&elite, 0.5);
+
+        assert_eq!(mean, from_array([2.0; 5]));
+        assert_eq!(std_dev, from_array([1.5; 5]));
+    }
+
+    #[test]
+    fn samples_with_no_deviation_are_the_mean() {
+        let mean = Weights::default();
+        let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+
+        assert_eq!(sample(&mut rng, &mean, &from_array([0.0; 5])), mean);
+    }
+
+    #[test]
+    fn the_same_seed_samples_the_same_weights() {
+        let (mean, std_dev) = (Weights::default(), from_array([1.0; 5]));
+        let mut first = XorShiftRng::from_seed([1, 2, 3, 4]);
+        let mut second = XorShiftRng::from_seed([1, 2, 3, 4]);
+
+        assert_eq!(
+            sample(&mut first, &mean, &std_dev),
+            sample(&mut second, &mean, &std_dev)
+        );
+    }
+
+    #[test]
+    fn every_candidate_is_evaluated_on_the_same_games() {
+        let options = Options::parse(&[
+            "--games".to_string(),
+            "2".to_string(),
+            "--max-pieces".to_string(),
+            "30".to_string(),
+        ])
+        .unwrap();
+
+        let candidates = evaluate(&options, 0, &[Weights::default(), Weights::default()]).unwrap();
+
+        assert_eq!(candidates.len(), 2);
+        assert_eq!(candidates[0].fitness, candidates[1].fitness);
+    }
+
+    #[test]
+    fn too_many_games_is_an_error() {
+        let options = Options::parse(&["--games".to_string(), u32::MAX.to_string()]).unwrap();
+
+        assert!(evaluate(&options, 0, &[Weights::default(), Weights::default()]).is_err());
+    }
+}
@@ -0,0 +1,147 @@
+//! Benchmarks for the board operations the AI and simulations spend most of their time in.
+//!
+//! Run with `cargo bench --package tetris`.
+
+use std::hint::black_box;
+use std::time::Duration;
+use std::time::Instant;
+
+use tetris::Ai;
+use tetris::Board;
+use tetris::Game;
+use tetris::Piece;
+use tetris::Placement;
+use tetris::Player;
+use tetris::Pos;
+use tetris::Shape;
+use tetris::ShapeColor;
+use tetris::StepResult;
+use tetris::Weights;
+
+/// How long to run each benchmark for.
+const DURATION: Duration = Duration::from_secs(2);
+
+/// A board with a messy stack, typical of the middle of a game.
+const STACK: &str = "
+    ..........
+    ....X.....
+    X...XX...X
+    XX.XXXX.XX
+    XXXX.XXXXX
+    X.XXXXXXXX
+    XXXXXXXX.X
+    XXX.XXXXXX
+";
+
+fn main() {
+    let board: Board = STACK.parse().unwrap();
+    let shapes: Vec<Shape> = [
+        ShapeColor::I,
+        ShapeColor::O,
+        ShapeColor::T,
+        ShapeColor::S,
+        ShapeColor::Z,
+        ShapeColor::J,
+        ShapeColor::L,
+    ]
+    .iter()
+    .filter_map(|&color| Shape::from_color(color))
+    .collect();
+
+    let pieces: Vec<Piece> = shapes
+        .iter()
+        .flat_map(|&shape| {
+            (0..4).flat_map(move |rotations| {
+                (-2..Board::WIDTH as i16).flat_map(move |x| {
+                    (0..Board::HEIGHT as i16).map(move |y| {
+                        let mut piece = Piece::new(shape);
+                        for _ in 0..rotations {
+                            piece.rotate_clockwise();
+                        }
+                        piece.pos = Pos::new(x, y);
+                        piece
+                    })
+                })
+            })
+        })
+        .collect();
+
+    let grid = GridBoard(*board.grid());
+    assert!(
+        pieces
+            .iter()
+            .all(|piece| grid.overlaps(piece) == board.overlaps(piece)),
+        "the grid and the board should agree on every collision"
+    );
+
+    bench("collision, before row bitmasks", || {
+        pieces.iter().filter(|piece| grid.overlaps(piece)).count()
+    });
+
+    bench("collision", || {
+        pieces.iter().filter(|piece| board.overlaps(piece)).count()
+    });
+
+    bench("lock piece and clear lines", || {
+        let mut board = board.clone();
+        let mut i_piece = Piece::new(Shape::from_color(ShapeColor::I).unwrap());
+        i_piece.rotate_clockwise();
+        i_piece.pos = Pos::new(6, Board::HEIGHT as i16 - 6);
+        board.lock_piece(&i_piece).lines_cleared
+    });
+
+    bench("find all placements", || {
+        shapes
+            .iter()
+            .map(|&shape| Placement::find_all(&board, shape).len())
+            .sum::<usize>()
+    });
+
+    let ai = Ai::new(Weights::default(), 1);
+    bench("ai best placement with lookahead", || {
+        ai.best_placement(&board, shapes[0], &shapes[1..2])
+    });
+
+    bench("ai plays 100 pieces", || {
+        let mut player = Player::new(Ai::new(Weights::default(), 0));
+        let mut game = Game::from_seed([1, 2, 3, 4]);
+        while game.pieces_placed() < 100 && game.step() == StepResult::Continue {
+            player.play(&mut game);
+        }
+        game.score()
+    });
+}
+
+/// A board stored as a grid of cells, as it was before rows were stored as bitmasks, to compare
+/// collision checks against.
+struct GridBoard([[Option<ShapeColor>; Board::WIDTH as usize]; Board::HEIGHT as usize]);
+
+impl GridBoard {
+    /// Check whether the piece overlaps a filled cell or the edge of the board, one block at a
+    /// time.
+    fn overlaps(&self, piece: &Piece) -> bool {
+        piece.blocks().any(|pos| !self.is_pos_free(pos))
+    }
+
+    fn is_pos_free(&self, pos: Pos) -> bool {
+        let out_bounds = pos.x() < 0
+            || pos.y() < 0
+            || pos.x() >= i16::from(Board::WIDTH)
+            || pos.y() >= i16::from(Board::HEIGHT);
+
+        !out_bounds && self.0[pos.y() as usize][pos.x() as usize].is_none()
+    }
+}
+
+/// Run the function repeatedly for a while, then print how long each call took on average.
+fn bench<T>(name: &str, mut f: impl FnMut() -> T) {
+    let start = Instant::now();
+    let mut iterations = 0;
+
+    while start.elapsed() < DURATION {
+        black_box(f());
+        iterations += 1;
+    }
+
+    println!("{:<34} {:>12.2?}", name, start.elapsed() / iterations);
+}
@@ -0,0 +1,281 @@
+use serde_derive::{Deserialize, Serialize};
+
+use crate::analysis::Analysis;
+use crate::board::Board;
+use crate::board::FillResult;
+use crate::controller::Controller;
+use crate::game::Game;
+use crate::game::StepResult;
+use crate::piece::Piece;
+use crate::placement::Placement;
+use crate::shape::Shape;
+
+/// How much each feature of a board counts towards how good it is.
+///
+/// Features that make a board worse, like holes, should have negative weights.
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Debug)]
+pub struct Weights {
+    /// The sum of the height of every column.
+    pub aggregate_height: f64,
+
+    /// The number of empty cells with a filled cell somewhere above them.
+    pub holes: f64,
+
+    /// The sum of the differences in height between neighbouring columns.
+    pub bumpiness: f64,
+
+    /// The sum of the depths of every well, a column lower than both its neighbours.
+    pub wells: f64,
+
+    /// The number of lines cleared by placing the piece.
+    pub lines_cleared: f64,
+}
+
+impl Default for Weights {
+    /// Weights that play a solid, if cautious, game.
+    fn default() -> Self {
+        Weights {
+            aggregate_height: -0.510_066,
+            holes: -0.356_63,
+            bumpiness: -0.184_483,
+            wells: -0.1,
+            lines_cleared: 0.760_666,
+        }
+    }
+}
+
+impl Weights {
+    /// Score a board by its features, not counting any lines cleared.
+    fn score(&self, board: &Board) -> f64 {
+        let analysis = Analysis::new(board);
+        let wells: u32 = analysis.well_depths().iter().sum();
+
+        self.aggregate_height * f64::from(analysis.aggregate_height())
+            + self.holes * f64::from(analysis.holes())
+            + self.bumpiness * f64::from(analysis.bumpiness())
+            + self.wells * f64::from(wells)
+    }
+}
+
+/// A computer player, that picks where to place each piece by scoring the board it would leave.
+///
+/// The AI is a `Controller`, so plays a `Game` through a `Player`.
+#[derive(Clone, Debug)]
+pub struct Ai {
+    weights: Weights,
+
+    /// How many shapes from the preview to consider when picking a placement.
+    lookahead: usize,
+}
+
+impl Default for Ai {
+    /// Create an AI with the default weights, looking ahead at the next shape.
+    fn default() -> Self {
+        Ai::new(Weights::default(), 1)
+    }
+}
+
+impl Ai {
+    /// Create an AI using the given weights, that looks ahead at the given number of shapes from
+    /// the preview when picking a placement.
+    ///
+    /// Each shape looked ahead at makes the AI much slower, and there is only one shape in the
+    /// preview, so the lookahead should be zero or one.
+    pub fn new(weights: Weights, lookahead: usize) -> Self {
+        Ai { weights, lookahead }
+    }
+
+    /// Get the weights the AI uses to score boards.
+    pub fn weights(&self) -> Weights {
+        self.weights
+    }
+
+    /// Pick the best placement for a shape on the given board, considering the shapes that will
+    /// come after it.
+    ///
+    /// Only as many shapes from the preview as the AI's lookahead are considered. Returns `None`
+    /// if the shape can't be placed anywhere.
+    pub fn best_placement(
+        &self,
+        board: &Board,
+        shape: Shape,
+        preview: &[Shape],
+    ) -> Option<Placement> {
+        let preview = &preview[..preview.len().min(self.lookahead)];
+        self.best_of(board, Placement::find_all(board, shape), preview)
+            .map(|(placement, _)| placement)
+    }
+
+    /// Find the best of the given placements, and its score.
+    pub(crate) fn best_of(
+        &self,
+        board: &Board,
+        placements: Vec<Placement>,
+        preview: &[Shape],
+    ) -> Option<(Placement, f64)> {
+        let mut best: Option<(Placement, f64)> = None;
+
+        for placement in placements {
+            let score = self.evaluate(board, &placement.piece, preview);
+
+            if best
+                .as_ref()
+                .map_or(true, |&(_, best_score)| score > best_score)
+            {
+                best = Some((placement, score));
+            }
+        }
+
+        best
+    }
+
+    /// Score placing the piece on the board, assuming the best placements for the shapes in the
+    /// preview.
+    fn evaluate(&self, board: &Board, piece: &Piece, preview: &[Shape]) -> f64 {
+        let mut board = board.clone();
+        let FillResult {
+            step_result,
+            lines_cleared,
+        } = board.lock_piece(piece);
+
+        if step_result == StepResult::GameOver {
+            return f64::NEG_INFINITY;
+        }
+
+        let lines_score = self.weights.lines_cleared * f64::from(lines_cleared);
+
+        match preview.split_first() {
+            None => lines_score + self.weights.score(&board),
+            Some((&next, rest)) => {
+                let placements = Placement::find_all(&board, next);
+                let next_score = self
+                    .best_of(&board, placements, rest)
+                    .map_or(f64::NEG_INFINITY, |(_, score)| score);
+                lines_score + next_score
+            }
+        }
+    }
+}
+
+impl Controller for Ai {
+    /// Pick the best placement for the game's current piece, looking ahead at the next shape if
+    /// the AI's lookahead allows.
+    fn choose(&mut self, game: &Game) -> Option<Placement> {
+        let preview = [game.next_shape()];
+        let preview = &preview[..self.lookahead.min(preview.len())];
+        let placements = Placement::find_all_from(game.board(), game.piece().clone());
+
+        self.best_of(game.board(), placements, preview)
+            .map(|(placement, _)| placement)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::controller::Player;
+    use crate::pos::Pos;
+    use crate::puzzle::Puzzle;
+    use crate::shape::ShapeColor;
+    use crate::state::State;
+
+    use super::*;
+
+    fn board(rows: &str) -> Board {
+        rows.parse().unwrap()
+    }
+
+    fn shape(color: ShapeColor) -> Shape {
+        Shape::from_color(color).unwrap()
+    }
+
+    #[test]
+    fn an_i_is_dropped_into_a_well_to_clear_lines() {
+        let board = board(
+            "
+            XXXXXXXXX.
+            XXXXXXXXX.
+            XXXXXXXXX.
+            XXXXXXXXX.
+            ",
+        );
+
+        let placement = Ai::default()
+            .best_placement(&board, shape(ShapeColor::I), &[])
+            .unwrap();
+
+        let mut after = board.clone();
+        assert_eq!(after.lock_piece(&placement.piece).lines_cleared, 4);
+    }
+
+    #[test]
+    fn a_piece_is_not_placed_over_a_hole() {
+        let board = board(
+            "
+            XXX.XXXX..
+            ",
+        );
+
+        let placement = Ai::new(Weights::default(), 0)
+            .best_placement(&board, shape(ShapeColor::O), &[])
+            .unwrap();
+
+        let mut after = board.clone();
+        after.lock_piece(&placement.piece);
+        assert_eq!(Analysis::new(&after).holes(), 0);
+    }
+
+    #[test]
+    fn nothing_is_placed_if_the_piece_cannot_spawn() {
+        let mut board = Board::default();
+        for x in 0..Board::WIDTH as i16 {
+            board.fill_pos(Pos::new(x, 1), ShapeColor::Garbage);
+        }
+
+        let placement = Ai::default().best_placement(&board, shape(ShapeColor::T), &[]);
+
+        assert_eq!(placement, None);
+    }
+
+    #[test]
+    fn the_ai_solves_a_t_spin_puzzle() {
+        let puzzle =
+            Puzzle::from_json(include_str!("../../resources/puzzles/t-spin-double.json")).unwrap();
+
+        let mut player = Player::new(Ai::default());
+        let mut state = State::Play(Game::puzzle(puzzle));
+
+        let solved = loop {
+            state = match state {
+                State::Play(mut game) => {
+                    player.play(&mut game);
+                    game.update()
+                }
+                State::PuzzleOver(puzzle_over) => break puzzle_over.is_solved(),
+                _ => panic!("expected the puzzle to be played"),
+            };
+        };
+
+        assert!(solved);
+    }
+
+    #[test]
+    fn the_ai_survives_a_game() {
+        let mut player = Player::new(Ai::new(Weights::default(), 0));
+        let mut state = State::Play(Game::from_seed([1, 2, 3, 4]));
+
+        let game = loop {
+            state = match state {
+                State::Play(mut game) => {
+                    if game.pieces_placed() >= 200 {
+                        break game;
+                    }
+                    player.play(&mut game);
+                    game.update()
+                }
+                _ => panic!("expected the AI to keep playing"),
+            };
+        };
+
+        assert!(game.lines_cleared() >= 60);
+    }
+}
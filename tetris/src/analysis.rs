@@ -0,0 +1,348 @@
+use crate::board::Board;
+use crate::piece::Piece;
+use crate::pos::Pos;
+use crate::shape::Rotation;
+use crate::shape::Shape;
+use crate::shape::ShapeColor;
+
+/// The number of columns on the board.
+const WIDTH: usize = Board::WIDTH as usize;
+
+/// Measurements of the shape of a board's stack, such as how tall and uneven it is.
+///
+/// Heights are measured in cells from the floor, so an empty column has height zero.
+#[derive(Clone, Debug)]
+pub struct Analysis<'a> {
+    board: &'a Board,
+    heights: [u32; WIDTH],
+}
+
+/// A place on the board where a T can be spun in, which may clear lines.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TSlot {
+    /// The T, pointing down into the slot.
+    pub piece: Piece,
+
+    /// The number of lines the T would clear.
+    pub lines_cleared: u32,
+}
+
+impl<'a> Analysis<'a> {
+    /// Analyse the given board.
+    pub fn new(board: &'a Board) -> Self {
+        let mut heights = [0; WIDTH];
+
+        for (x, height) in heights.iter_mut().enumerate() {
+            *height = board
+                .grid()
+                .iter()
+                .position(|row| row[x].is_some())
+                .map_or(0, |y| u32::from(Board::HEIGHT) - y as u32);
+        }
+
+        Analysis { board, heights }
+    }
+
+    /// Get the height of each column, from the floor to its highest filled cell.
+    pub fn column_heights(&self) -> &[u32] {
+        &self.heights
+    }
+
+    /// Get the height of the tallest column.
+    pub fn max_height(&self) -> u32 {
+        self.heights.iter().copied().max().unwrap_or(0)
+    }
+
+    /// Get the sum of the height of every column.
+    pub fn aggregate_height(&self) -> u32 {
+        self.heights.iter().sum()
+    }
+
+    /// Count the empty cells that have a filled cell somewhere above them.
+    pub fn holes(&self) -> u32 {
+        (0..WIDTH)
+            .map(|x| self.column(x).filter(Option::is_none).count() as u32)
+            .sum()
+    }
+
+    /// Count the filled cells that have a hole somewhere below them, which must be cleared to
+    /// uncover the hole.
+    pub fn covered_cells(&self) -> u32 {
+        (0..WIDTH)
+            .map(|x| {
+                let column: Vec<_> = self.column(x).collect();
+                let deepest_hole = column.iter().rposition(Option::is_none);
+                deepest_hole.map_or(0, |hole| {
+                    column[..hole].iter().filter(|cell| cell.is_some()).count() as u32
+                })
+            })
+            .sum()
+    }
+
+    /// Sum the differences in height between neighbouring columns.
+    pub fn bumpiness(&self) -> u32 {
+        self.heights
+            .windows(2)
+            .map(|pair| (pair[0] as i32 - pair[1] as i32).unsigned_abs())
+            .sum()
+    }
+
+    /// Count how often neighbouring cells in a row change between filled and empty, treating the
+    /// walls as filled.
+    ///
+    /// Only rows at or below the top of the stack are counted.
+    pub fn row_transitions(&self) -> u32 {
+        let top = Board::HEIGHT as usize - self.max_height() as usize;
+
+        self.board.grid()[top..]
+            .iter()
+            .map(|row| {
+                let mut filled = true;
+                let mut transitions = 0;
+                for cell in row.iter().map(Option::is_some).chain(Some(true)) {
+                    if cell != filled {
+                        transitions += 1;
+                        filled = cell;
+                    }
+                }
+                transitions
+            })
+            .sum()
+    }
+
+    /// Count how often neighbouring cells in a column change between filled and empty, treating
+    /// the floor as filled.
+    ///
+    /// Only cells at or below the top of each column are counted, so a column with no holes has no
+    /// transitions.
+    pub fn column_transitions(&self) -> u32 {
+        (0..WIDTH)
+            .map(|x| {
+                let mut filled = true;
+                let mut transitions = 0;
+                for cell in self.column(x).map(|cell| cell.is_some()).chain(Some(true)) {
+                    if cell != filled {
+                        transitions += 1;
+                        filled = cell;
+                    }
+                }
+                transitions
+            })
+            .sum()
+    }
+
+    /// Get how far each column is below both its neighbours, treating the walls as infinitely
+    /// high.
+    ///
+    /// Columns that aren't wells have a depth of zero.
+    pub fn well_depths(&self) -> [u32; WIDTH] {
+        let mut depths = [0; WIDTH];
+
+        for (x, depth) in depths.iter_mut().enumerate() {
+            let left = if x == 0 {
+                u32::MAX
+            } else {
+                self.heights[x - 1]
+            };
+            let right = self.heights.get(x + 1).copied().unwrap_or(u32::MAX);
+            *depth = left.min(right).saturating_sub(self.heights[x]);
+        }
+
+        depths
+    }
+
+    /// Find every slot a T can be spun into, pointing down.
+    ///
+    /// A slot has both cells beside the bottom of the T filled, and at least one cell above its
+    /// arms filled so the T can't be dropped in. This doesn't check whether the T can be moved
+    /// into the slot.
+    pub fn t_slots(&self) -> Vec<TSlot> {
+        let shape = Shape::from_color(ShapeColor::T).expect("T is a shape");
+        let pointing_down = Rotation::default().clockwise().clockwise();
+        let mut slots = Vec::new();
+
+        for y in -1..Board::HEIGHT as i16 {
+            for x in -1..Board::WIDTH as i16 {
+                let piece = Piece {
+                    shape,
+                    rot: pointing_down,
+                    pos: Pos::new(x, y),
+                };
+
+                if self.is_t_slot(&piece) {
+                    let mut board = self.board.clone();
+                    let lines_cleared = board.lock_piece(&piece).lines_cleared;
+                    slots.push(TSlot {
+                        piece,
+                        lines_cleared,
+                    });
+                }
+            }
+        }
+
+        slots
+    }
+
+    /// Return whether the T pointing down fits into a slot, as described in `t_slots`.
+    fn is_t_slot(&self, piece: &Piece) -> bool {
+        let fits = !self.board.overlaps(piece);
+        let rests = self.board.drop_distance(piece) == 0;
+
+        let centre = piece.pos + Pos::new(1, 2);
+        let filled = |x, y| !self.board.is_pos_free(centre + Pos::new(x, y));
+
+        fits && rests && filled(-1, 1) && filled(1, 1) && (filled(-1, -1) || filled(1, -1))
+    }
+
+    /// Get the cells in a column from the top of the stack down to the floor.
+    fn column(&self, x: usize) -> impl Iterator<Item = Option<ShapeColor>> + 'a {
+        let top = Board::HEIGHT as usize - self.heights[x] as usize;
+        self.board.grid()[top..].iter().map(move |row| row[x])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(rows: &str) -> Board {
+        rows.parse().unwrap()
+    }
+
+    #[test]
+    fn column_heights_are_measured_from_the_floor() {
+        let board = board(
+            "
+            .X........
+            .X......X.
+            XX......XX
+            ",
+        );
+        let analysis = Analysis::new(&board);
+
+        assert_eq!(analysis.column_heights(), &[1, 3, 0, 0, 0, 0, 0, 0, 2, 1]);
+        assert_eq!(analysis.max_height(), 3);
+        assert_eq!(analysis.aggregate_height(), 7);
+    }
+
+    #[test]
+    fn holes_are_empty_cells_under_filled_cells() {
+        let board = board(
+            "
+            .X..X.....
+            ....X.....
+            .X........
+            ",
+        );
+
+        assert_eq!(Analysis::new(&board).holes(), 2);
+    }
+
+    #[test]
+    fn covered_cells_are_filled_cells_over_holes() {
+        let board = board(
+            "
+            X...X.....
+            X...X.....
+            ....X.....
+            X.........
+            ",
+        );
+
+        assert_eq!(Analysis::new(&board).covered_cells(), 2 + 3);
+    }
+
+    #[test]
+    fn bumpiness_is_the_total_height_difference_between_columns() {
+        let board = board(
+            "
+            .X........
+            .X......X.
+            XX......XX
+            ",
+        );
+
+        assert_eq!(Analysis::new(&board).bumpiness(), 2 + 3 + 2 + 1);
+    }
+
+    #[test]
+    fn row_transitions_count_the_walls_as_filled() {
+        let board = board(
+            "
+            ..........
+            XXXX..XXXX
+            XXXXXXXXX.
+            ",
+        );
+
+        assert_eq!(Analysis::new(&board).row_transitions(), 2 + 2);
+    }
+
+    #[test]
+    fn column_transitions_count_the_floor_as_filled() {
+        let board = board(
+            "
+            X.X.......
+            ..X.......
+            X.........
+            ",
+        );
+
+        assert_eq!(Analysis::new(&board).column_transitions(), 2 + 2);
+    }
+
+    #[test]
+    fn wells_are_columns_lower_than_both_neighbours() {
+        let board = board(
+            "
+            X.X.......
+            X.X...X...
+            X.XX.XX...
+            ",
+        );
+
+        assert_eq!(
+            Analysis::new(&board).well_depths(),
+            [0, 3, 0, 0, 1, 0, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn a_t_slot_is_found_under_an_overhang() {
+        let board = board(
+            "
+            XX........
+            X...XXXXXX
+            XX.XXXXXXX
+            ",
+        );
+
+        let slots = Analysis::new(&board).t_slots();
+
+        assert_eq!(slots.len(), 1);
+        assert_eq!(slots[0].lines_cleared, 2);
+        let blocks: Vec<Pos> = slots[0].piece.blocks().collect();
+        let bottom = Board::HEIGHT as i16 - 1;
+        assert_eq!(
+            blocks,
+            vec![
+                Pos::new(1, bottom - 1),
+                Pos::new(2, bottom - 1),
+                Pos::new(3, bottom - 1),
+                Pos::new(2, bottom),
+            ]
+        );
+    }
+
+    #[test]
+    fn an_open_slot_is_not_a_t_slot() {
+        let board = board(
+            "
+            X...XXXXXX
+            XX.XXXXXXX
+            ",
+        );
+
+        assert_eq!(Analysis::new(&board).t_slots(), vec![]);
+    }
+}
@@ -1,15 +1,32 @@
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::str::FromStr;
+
 use crate::game::StepResult;
 use crate::piece::Piece;
 use crate::pos::Pos;
+use crate::shape::Shape;
 use crate::shape::ShapeColor;
 
 /// The board state, describing which cells are full and what colour tetromino they were filled
 /// with.
-#[derive(Clone, Debug, Default)]
+///
+/// Which cells are full is stored as a bitmask for each row, so checking for collisions, clearing
+/// lines and dropping pieces are a few bit operations per row. The colours are kept separately.
+#[derive(Clone, PartialEq, Debug, Default)]
 pub struct Board {
+    /// A bitmask of the full cells in each row, where bit `x` is set if column `x` is full.
+    rows: [u16; Board::HEIGHT as usize],
+
+    /// The colour of each full cell.
     grid: [[Option<ShapeColor>; Board::WIDTH as usize]; Board::HEIGHT as usize],
 }
 
+/// The bitmask of a row where every cell is full.
+const FULL_ROW: u16 = (1 << Board::WIDTH) - 1;
+
 impl Board {
     /// Width of the playable board in cells.
     pub const WIDTH: u8 = 10;
@@ -36,6 +53,159 @@ impl Board {
         &self.grid[Board::HIDE_ROWS as usize..]
     }
 
+    /// Create a board from rows of cells, where `.` is an empty cell and a letter is a filled
+    /// cell, e.g. `T` for a T shape or `X` for garbage.
+    ///
+    /// Rows are listed from top to bottom, and are aligned to the bottom of the board so only the
+    /// bottom-most rows need to be given.
+    pub fn from_rows<S: AsRef<str>>(rows: &[S]) -> Result<Board, ParseBoardError> {
+        let (board, overlay) = Board::parse_rows(rows)?;
+
+        match overlay.first() {
+            Some(&(_, letter)) => Err(ParseBoardError::UnknownCell(letter)),
+            None => Ok(board),
+        }
+    }
+
+    /// Write the board as rows of cells in the format read by `Board::from_rows`, leaving out the
+    /// empty rows above the highest filled cell.
+    pub(crate) fn to_rows(&self) -> Vec<String> {
+        let rows = self.to_string();
+        rows.lines()
+            .skip_while(|row| row.chars().all(|cell| cell == '.'))
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Parse a board in the text format from `Display`, with a piece overlaid in lowercase
+    /// letters as written by `Board::to_string_with_piece`.
+    ///
+    /// Returns `None` for the piece if there are no lowercase letters.
+    pub fn parse_with_piece(s: &str) -> Result<(Board, Option<Piece>), ParseBoardError> {
+        let (board, overlay) = Board::parse_rows(&text_rows(s))?;
+
+        if overlay.is_empty() {
+            return Ok((board, None));
+        }
+
+        let piece = piece_from_cells(&overlay).ok_or(ParseBoardError::InvalidPiece)?;
+        Ok((board, Some(piece)))
+    }
+
+    /// Write the board in the text format from `Display`, with the given piece overlaid in
+    /// lowercase letters.
+    pub fn to_string_with_piece(&self, piece: &Piece) -> String {
+        let mut text = String::new();
+        self.write_rows(&mut text, Some(piece))
+            .expect("writing to a string can't fail");
+        text
+    }
+
+    /// Parse rows of cells into a board, returning any lowercase piece cells separately.
+    fn parse_rows<S: AsRef<str>>(rows: &[S]) -> Result<(Board, Vec<(Pos, char)>), ParseBoardError> {
+        if rows.len() > Board::HEIGHT as usize {
+            return Err(ParseBoardError::TooManyRows(rows.len()));
+        }
+
+        let mut board = Board::default();
+        let mut overlay = Vec::new();
+        let top = Board::HEIGHT as usize - rows.len();
+
+        for (y, row) in rows.iter().enumerate() {
+            let row = row.as_ref();
+            let y = top + y;
+
+            if row.chars().count() != Board::WIDTH as usize {
+                return Err(ParseBoardError::WrongWidth(row.to_string()));
+            }
+
+            for (x, cell) in row.chars().enumerate() {
+                if cell.is_lowercase() {
+                    overlay.push((Pos::new(x as i16, y as i16), cell));
+                } else if cell != '.' {
+                    let color =
+                        ShapeColor::from_letter(cell).ok_or(ParseBoardError::UnknownCell(cell))?;
+                    board.fill_pos(Pos::new(x as i16, y as i16), color);
+                }
+            }
+        }
+
+        Ok((board, overlay))
+    }
+
+    /// Write every row of the board, optionally with a piece overlaid in lowercase letters.
+    fn write_rows(&self, out: &mut impl fmt::Write, piece: Option<&Piece>) -> fmt::Result {
+        for (y, row) in self.grid.iter().enumerate() {
+            for (x, cell) in row.iter().enumerate() {
+                let pos = Pos::new(x as i16, y as i16);
+
+                let letter = match (piece, cell) {
+                    (Some(piece), _) if piece.blocks().any(|block| block == pos) => {
+                        piece.shape.color.letter().to_ascii_lowercase()
+                    }
+                    (_, Some(color)) => color.letter(),
+                    (_, None) => '.',
+                };
+
+                out.write_char(letter)?;
+            }
+            out.write_char('\n')?;
+        }
+
+        Ok(())
+    }
+
+    /// Return whether the board has no filled cells.
+    pub fn is_empty(&self) -> bool {
+        self.rows.iter().all(|&row| row == 0)
+    }
+
+    /// Get the number of rows containing any garbage.
+    pub fn garbage_rows(&self) -> u32 {
+        self.grid
+            .iter()
+            .filter(|row| row.contains(&Some(ShapeColor::Garbage)))
+            .count() as u32
+    }
+
+    /// Return whether the piece overlaps any full cells or is out of bounds.
+    pub fn overlaps(&self, piece: &Piece) -> bool {
+        piece_rows(piece).any(|(y, mask)| match mask {
+            Some(mask) => self.rows[y as usize] & mask != 0,
+            None => true,
+        })
+    }
+
+    /// Get how many cells the piece can drop before it lands on the stack or the floor.
+    ///
+    /// Returns zero if the piece already overlaps the board.
+    pub fn drop_distance(&self, piece: &Piece) -> i16 {
+        if self.overlaps(piece) {
+            return 0;
+        }
+
+        let mut masks = [(0, 0); Shape::HEIGHT as usize];
+        let mut num_rows = 0;
+        for (y, mask) in piece_rows(piece) {
+            masks[num_rows] = (y as usize, mask.expect("piece is in bounds"));
+            num_rows += 1;
+        }
+        let masks = &masks[..num_rows];
+
+        let mut distance = 0;
+        loop {
+            let landed = masks.iter().any(|&(y, mask)| {
+                let below = y + distance + 1;
+                below >= Board::HEIGHT as usize || self.rows[below] & mask != 0
+            });
+
+            if landed {
+                return distance as i16;
+            }
+            distance += 1;
+        }
+    }
+
     /// Lock a piece, attaching it to the board permanently and potentially clearing some rows.
     ///
     /// This can cause a game over if the piece is locked above the visible playing area, which
@@ -43,11 +213,12 @@ impl Board {
     pub fn lock_piece(&mut self, piece: &Piece) -> FillResult {
         let mut step_result = StepResult::GameOver;
 
-        for cell in piece.blocks() {
-            if cell.y() > i16::from(Board::HIDE_ROWS) {
+        for (y, mask) in piece_rows(piece) {
+            let mask = mask.expect("piece to lock is out of bounds");
+            if y > i16::from(Board::HIDE_ROWS) {
                 step_result = StepResult::Continue;
             }
-            self.fill_pos(cell, piece.shape.color);
+            self.fill_row(y as usize, mask, piece.shape.color);
         }
 
         FillResult {
@@ -56,13 +227,55 @@ impl Board {
         }
     }
 
+    /// Push every row up by the given number of rows, filling the bottom rows with garbage that
+    /// has one empty cell in the given column.
+    ///
+    /// This is a game over if any filled cells are pushed off the top of the board.
+    ///
+    /// # Panics
+    /// Panics if the hole column is outside the board.
+    pub fn add_garbage(&mut self, rows: u8, hole_column: u8) -> StepResult {
+        assert!(hole_column < Board::WIDTH, "hole column out of bounds");
+
+        let rows = rows.min(Board::HEIGHT) as usize;
+        let height = Board::HEIGHT as usize;
+
+        let step_result = if self.rows[..rows].iter().any(|&row| row != 0) {
+            StepResult::GameOver
+        } else {
+            StepResult::Continue
+        };
+
+        self.rows.copy_within(rows.., 0);
+        self.grid.copy_within(rows.., 0);
+
+        for y in height - rows..height {
+            self.rows[y] = 0;
+            self.grid[y] = [None; Board::WIDTH as usize];
+            self.fill_row(y, FULL_ROW & !(1 << hole_column), ShapeColor::Garbage);
+        }
+
+        step_result
+    }
+
     /// Fill a single position on the board.
     ///
     /// # Panics
     /// Panics if the position is out of bounds.
-    fn fill_pos(&mut self, pos: Pos, color: ShapeColor) {
+    pub(crate) fn fill_pos(&mut self, pos: Pos, color: ShapeColor) {
         assert!(!out_bounds(pos));
-        self.grid[pos.y() as usize][pos.x() as usize] = Some(color);
+        self.fill_row(pos.y() as usize, 1 << pos.x(), color);
+    }
+
+    /// Fill the cells in a row given by a bitmask.
+    fn fill_row(&mut self, y: usize, mask: u16, color: ShapeColor) {
+        self.rows[y] |= mask;
+
+        for (x, cell) in self.grid[y].iter_mut().enumerate() {
+            if mask & (1 << x) != 0 {
+                *cell = Some(color);
+            }
+        }
     }
 
     /// Clear any full rows and return the number of rows cleared.
@@ -70,16 +283,7 @@ impl Board {
         let mut lines_cleared = 0;
 
         for y in 0..Board::HEIGHT {
-            let mut clear = true;
-
-            'check_clear: for cell in &self.grid[y as usize] {
-                if cell.is_none() {
-                    clear = false;
-                    break 'check_clear;
-                }
-            }
-
-            if clear {
+            if self.rows[y as usize] == FULL_ROW {
                 self.clear_row(y);
                 lines_cleared += 1;
             }
@@ -88,20 +292,18 @@ impl Board {
         lines_cleared
     }
 
-    /// Clear the given row.
+    /// Clear the given row, moving every row above it down.
     fn clear_row(&mut self, y: u8) {
-        for yy in (1..=y as usize).rev() {
-            self.grid[yy] = self.grid[yy - 1];
-        }
-
-        for x in 0..Board::WIDTH as usize {
-            self.grid[0][x] = None;
-        }
+        let y = y as usize;
+        self.rows.copy_within(..y, 1);
+        self.grid.copy_within(..y, 1);
+        self.rows[0] = 0;
+        self.grid[0] = [None; Board::WIDTH as usize];
     }
 
     /// Returns if this position on the board is free and in-bounds
     pub fn is_pos_free(&self, pos: Pos) -> bool {
-        !out_bounds(pos) && self.grid[pos.y() as usize][pos.x() as usize].is_none()
+        !out_bounds(pos) && self.rows[pos.y() as usize] & (1 << pos.x()) == 0
     }
 }
 
@@ -112,6 +314,119 @@ pub struct FillResult {
     pub lines_cleared: u32,
 }
 
+impl Display for Board {
+    /// Write the board as text, with one line per row from top to bottom, including hidden rows.
+    ///
+    /// Empty cells are written as `.` and filled cells as the letter of their shape, e.g. `T` for
+    /// a T shape or `X` for garbage.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.write_rows(f, None)
+    }
+}
+
+impl FromStr for Board {
+    type Err = ParseBoardError;
+
+    /// Parse a board in the text format from `Display`.
+    ///
+    /// Surrounding whitespace and blank lines are ignored, and rows are aligned to the bottom of
+    /// the board so only the bottom-most rows need to be given.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Board::from_rows(&text_rows(s))
+    }
+}
+
+/// Potential errors from parsing a board from text.
+#[derive(Debug, PartialEq)]
+pub enum ParseBoardError {
+    /// There are more rows than fit on the board
+    TooManyRows(usize),
+    /// A row is not exactly as wide as the board
+    WrongWidth(String),
+    /// A cell is not `.` or the letter of a shape
+    UnknownCell(char),
+    /// The lowercase cells of a piece don't make up a single tetromino
+    InvalidPiece,
+}
+
+impl Error for ParseBoardError {}
+
+impl Display for ParseBoardError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseBoardError::TooManyRows(rows) => write!(
+                f,
+                "Board has {} rows, but must have at most {}",
+                rows,
+                Board::HEIGHT
+            ),
+            ParseBoardError::WrongWidth(row) => {
+                write!(f, "Row {:?} must be {} cells wide", row, Board::WIDTH)
+            }
+            ParseBoardError::UnknownCell(cell) => write!(f, "Unknown board cell {:?}", cell),
+            ParseBoardError::InvalidPiece => write!(f, "Piece cells don't make up a tetromino"),
+        }
+    }
+}
+
+/// Split text into rows of a board, ignoring surrounding whitespace and blank lines.
+fn text_rows(s: &str) -> Vec<&str> {
+    s.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+/// Find the piece whose blocks are exactly the given cells, all written with the piece's letter.
+fn piece_from_cells(cells: &[(Pos, char)]) -> Option<Piece> {
+    let letter = cells[0].1;
+    if cells.iter().any(|&(_, cell)| cell != letter) {
+        return None;
+    }
+
+    let color = ShapeColor::from_letter(letter.to_ascii_uppercase())?;
+    let blocks: Vec<Pos> = cells.iter().map(|&(pos, _)| pos).collect();
+
+    Piece::from_blocks(Shape::from_color(color)?, &blocks)
+}
+
+/// Get the rows the piece covers, with a bitmask of its cells in each row like `Board::rows`.
+///
+/// The bitmask is `None` if any of the piece's cells in that row are out of bounds.
+fn piece_rows(piece: &Piece) -> impl Iterator<Item = (i16, Option<u16>)> + '_ {
+    let (top, bottom) = piece.shape.bounding_box(piece.rot);
+
+    (top.y() as u8..=bottom.y() as u8).filter_map(move |dy| {
+        let cells = u32::from(piece.shape.row_mask(piece.rot, dy));
+        if cells == 0 {
+            return None;
+        }
+
+        let y = piece.pos.y() + i16::from(dy);
+        let x = piece.pos.x();
+
+        // Cells shifted out of the mask are past a wall, so are out of bounds
+        let mask = if x < 0 {
+            let shift = u32::from(x.unsigned_abs());
+            cells
+                .checked_shr(shift)
+                .filter(|&mask| mask.checked_shl(shift) == Some(cells))
+        } else {
+            let shift = x as u32;
+            cells
+                .checked_shl(shift)
+                .filter(|&mask| mask.checked_shr(shift) == Some(cells))
+        };
+
+        let in_bounds = y >= 0 && y < i16::from(Board::HEIGHT);
+        let mask = mask
+            .filter(|&mask| in_bounds && mask & !u32::from(FULL_ROW) == 0)
+            .map(|mask| mask as u16);
+
+        Some((y, mask))
+    })
+}
+
 /// Return whether the given position is out of bounds of the board (including hidden rows).
 fn out_bounds(pos: Pos) -> bool {
     pos.x() < 0
@@ -126,6 +441,8 @@ mod tests {
 
     use quickcheck::{quickcheck, Arbitrary, Gen, TestResult};
 
+    use crate::shape::Rotation;
+
     use super::*;
 
     impl Arbitrary for Board {
@@ -141,7 +458,15 @@ mod tests {
                     }
                 }
 
-                Board { grid: array }
+                let mut board = Board::default();
+                for (y, row) in array.iter().enumerate() {
+                    for (x, cell) in row.iter().enumerate() {
+                        if let Some(color) = *cell {
+                            board.fill_pos(Pos::new(x as i16, y as i16), color);
+                        }
+                    }
+                }
+                board
             }
         }
     }
@@ -163,8 +488,189 @@ mod tests {
         }
     }
 
+    #[test]
+    fn garbage_pushes_the_stack_up() {
+        let mut board = Board::from_rows(&["..T.......", ".TTT......"]).unwrap();
+
+        let step_result = board.add_garbage(2, 3);
+
+        assert_eq!(step_result, StepResult::Continue);
+        assert_eq!(
+            board,
+            Board::from_rows(&["..T.......", ".TTT......", "XXX.XXXXXX", "XXX.XXXXXX",]).unwrap()
+        );
+    }
+
+    #[test]
+    fn garbage_pushing_blocks_off_the_top_is_a_game_over() {
+        let mut board = Board::default();
+        board.fill_pos(Pos::new(0, 1), ShapeColor::O);
+
+        assert_eq!(board.add_garbage(1, 0), StepResult::Continue);
+        assert_eq!(board.add_garbage(1, 0), StepResult::GameOver);
+    }
+
+    #[test]
+    fn a_partial_board_is_aligned_to_the_bottom() {
+        let board: Board = "
+            T.........
+            TTXXXXXXXX
+        "
+        .parse()
+        .unwrap();
+
+        let bottom = Board::HEIGHT as usize - 1;
+        assert_eq!(board.grid[bottom - 1][0], Some(ShapeColor::T));
+        assert_eq!(board.grid[bottom][1], Some(ShapeColor::T));
+        assert_eq!(board.grid[bottom][2], Some(ShapeColor::Garbage));
+        assert_eq!(board.grid[bottom - 1][1], None);
+        assert!(board.grid[..bottom - 1]
+            .iter()
+            .flatten()
+            .all(Option::is_none));
+    }
+
+    #[test]
+    fn a_board_is_written_with_one_line_per_row() {
+        let board: Board = "IIII......".parse().unwrap();
+        let text = board.to_string();
+
+        assert_eq!(text.lines().count(), Board::HEIGHT as usize);
+        assert_eq!(text.lines().last(), Some("IIII......"));
+        assert_eq!(text.lines().next(), Some(".........."));
+    }
+
+    #[test]
+    fn a_board_with_a_piece_is_the_same_after_writing_and_parsing() {
+        let board: Board = "XXXX..XXXX".parse().unwrap();
+        let mut piece = Piece::new(Shape::from_color(ShapeColor::T).unwrap());
+        piece.rotate_clockwise();
+        piece.down();
+
+        let text = board.to_string_with_piece(&piece);
+        let (parsed_board, parsed_piece) = Board::parse_with_piece(&text).unwrap();
+
+        assert_eq!(parsed_board, board);
+        assert_eq!(parsed_piece, Some(piece));
+    }
+
+    #[test]
+    fn a_board_without_a_piece_is_parsed_with_no_piece() {
+        let (_, piece) = Board::parse_with_piece("XXXX..XXXX").unwrap();
+
+        assert_eq!(piece, None);
+    }
+
+    #[test]
+    fn piece_cells_that_are_not_a_tetromino_are_an_error() {
+        let result = Board::parse_with_piece("tttt......");
+
+        assert_eq!(result, Err(ParseBoardError::InvalidPiece));
+    }
+
+    #[test]
+    fn a_piece_cell_is_an_error_when_parsing_a_plain_board() {
+        let result = "ttt.......".parse::<Board>();
+
+        assert_eq!(result, Err(ParseBoardError::UnknownCell('t')));
+    }
+
+    #[test]
+    fn a_row_of_the_wrong_width_is_an_error() {
+        let result = "XXX".parse::<Board>();
+
+        assert_eq!(result, Err(ParseBoardError::WrongWidth("XXX".to_string())));
+    }
+
+    #[test]
+    fn a_piece_drops_onto_the_stack() {
+        let board: Board = "
+            ....X.....
+            XXXXXX....
+            "
+        .parse()
+        .unwrap();
+        let piece = Piece::new(Shape::from_color(ShapeColor::O).unwrap());
+
+        let distance = board.drop_distance(&piece);
+
+        let mut dropped = piece.clone();
+        dropped.pos = dropped.pos + Pos::new(0, distance);
+        let blocks: Vec<Pos> = dropped.blocks().collect();
+        let top = Board::HEIGHT as i16 - 4;
+        assert_eq!(
+            blocks,
+            vec![
+                Pos::new(4, top),
+                Pos::new(5, top),
+                Pos::new(4, top + 1),
+                Pos::new(5, top + 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn full_rows_are_cleared_when_a_piece_locks() {
+        let mut board: Board = "
+            ..........
+            .XXXXXXXXX
+            XXXXXXXX.X
+            .XXXXXXXXX
+            "
+        .parse()
+        .unwrap();
+        let mut piece = Piece::new(Shape::from_color(ShapeColor::I).unwrap());
+        piece.rotate_clockwise();
+        piece.pos = Pos::new(-2, Board::HEIGHT as i16 - 4);
+
+        assert_eq!(board.lock_piece(&piece).lines_cleared, 2);
+        assert_eq!(
+            board,
+            "
+            I.........
+            IXXXXXXX.X
+            "
+            .parse()
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn a_piece_far_past_either_wall_overlaps_the_board() {
+        let board = Board::default();
+        let mut piece = Piece::new(Shape::from_color(ShapeColor::T).unwrap());
+
+        for &x in &[-32, -40, i16::MIN, 32, 40, i16::MAX] {
+            piece.pos = Pos::new(x, 10);
+            assert!(board.overlaps(&piece), "piece at x = {} should overlap", x);
+        }
+    }
+
     quickcheck! {
 
+        fn overlapping_is_the_same_as_checking_every_block(
+            board: Board, shape: &'static Shape, rot: Rotation, pos: Pos) -> bool {
+            let piece = Piece { shape: *shape, rot, pos };
+            let blocks_overlap = piece.blocks().any(|block| !board.is_pos_free(block));
+            board.overlaps(&piece) == blocks_overlap
+        }
+
+        fn a_dropped_piece_rests_on_the_board(
+            board: Board, shape: &'static Shape, rot: Rotation, pos: Pos) -> TestResult {
+            let piece = Piece { shape: *shape, rot, pos };
+            when!(!board.overlaps(&piece));
+
+            let mut dropped = piece.clone();
+            dropped.pos = pos + Pos::new(0, board.drop_distance(&piece));
+            let mut below = dropped.clone();
+            below.down();
+            then!(!board.overlaps(&dropped) && board.overlaps(&below))
+        }
+
+        fn a_board_is_the_same_after_writing_and_parsing(board: Board) -> bool {
+            board.to_string().parse::<Board>() == Ok(board)
+        }
+
         fn a_new_board_is_empty(pos: InBoundsPos) -> bool {
             Board::default().is_pos_free(pos.0)
         }
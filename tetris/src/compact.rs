@@ -0,0 +1,522 @@
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use crate::board::Board;
+use crate::config::GameConfig;
+use crate::game::Action;
+use crate::game::History;
+use crate::game::Tick;
+use crate::shape::Shape;
+use crate::shape::ShapeColor;
+
+/// The version of the compact format, stored in the first byte so the format can change later.
+const VERSION: u8 = 1;
+
+/// Number of bits used to store an action code, packed under the tick delta.
+const ACTION_BITS: u32 = 3;
+
+/// Flag set when the game was rewound in practice mode.
+const FLAG_REWOUND: u8 = 1;
+
+/// Flag set when the game was played with non-default rules, which follow the flags.
+const FLAG_CUSTOM_CONFIG: u8 = 2;
+
+/// Flag set when the game started from its own board or pieces, which follow the config.
+const FLAG_CUSTOM_START: u8 = 4;
+
+/// Flag set when the game was played by a bot.
+const FLAG_PLAYED_BY_BOT: u8 = 8;
+
+/// Potential errors from decoding a compact history.
+#[derive(Debug)]
+pub enum DecodeError {
+    /// The data is not valid base64
+    InvalidBase64(base64::DecodeError),
+    /// The data was written with an unknown version of the format
+    UnknownVersion(u8),
+    /// The data ended before the history was complete
+    UnexpectedEnd,
+    /// The data contains an action code that doesn't exist
+    UnknownAction(u64),
+    /// The data contains a number too long to fit in 64 bits
+    VarintTooLong,
+    /// A tick is too large to fit in a game
+    TickOverflow,
+    /// A rule in the game's config is too large
+    ConfigOverflow,
+    /// The history contains garbage with its hole outside the board
+    InvalidGarbage,
+    /// The history lists an action before one that happened earlier
+    ActionsOutOfOrder,
+    /// The board or pieces the game started from can't be read
+    InvalidStart,
+}
+
+impl Error for DecodeError {}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::InvalidBase64(e) => write!(f, "History is not valid base64: {}", e),
+            DecodeError::UnknownVersion(version) => {
+                write!(f, "Unknown compact history version {}", version)
+            }
+            DecodeError::UnexpectedEnd => write!(f, "History ended unexpectedly"),
+            DecodeError::UnknownAction(code) => write!(f, "Unknown action code {}", code),
+            DecodeError::VarintTooLong => write!(f, "History contains a number that is too long"),
+            DecodeError::TickOverflow => write!(f, "History contains a tick that is too large"),
+            DecodeError::ConfigOverflow => write!(f, "History contains a rule that is too large"),
+            DecodeError::InvalidGarbage => {
+                write!(f, "History contains garbage with a hole outside the board")
+            }
+            DecodeError::ActionsOutOfOrder => write!(f, "History lists actions out of order"),
+            DecodeError::InvalidStart => {
+                write!(f, "History starts from an invalid board or pieces")
+            }
+        }
+    }
+}
+
+/// Encode a history in the compact binary format.
+///
+/// The format is:
+/// 1. A version byte.
+/// 2. The seed as four little-endian `u32`s.
+/// 3. A byte of flags, e.g. `FLAG_REWOUND`.
+/// 4. If `FLAG_CUSTOM_CONFIG` is set, each number in the game's config as a varint: the initial
+///    gravity, gravity per level, lines per level and then the four line scores.
+/// 5. If `FLAG_CUSTOM_START` is set, the number of rows of the starting board as a varint, then
+///    each row from top to bottom as a byte per cell, e.g. `b'.'` for an empty cell or `b'T'` for
+///    a T shape. Then the number of given pieces as a varint, followed by each piece's letter.
+/// 6. The number of actions as a varint.
+/// 7. Each action as a varint, containing the number of ticks since the previous action shifted
+///    left by `ACTION_BITS`, with the action code in the lowest bits. Garbage is followed by a
+///    byte for the number of rows and a byte for the hole column.
+pub(crate) fn encode(history: &History) -> Vec<u8> {
+    let mut bytes = vec![VERSION];
+
+    for part in &history.seed() {
+        bytes.extend_from_slice(&part.to_le_bytes());
+    }
+
+    let mut flags = 0;
+    if history.is_rewound() {
+        flags |= FLAG_REWOUND;
+    }
+    let config = history.config();
+    if !config.is_default() {
+        flags |= FLAG_CUSTOM_CONFIG;
+    }
+    if history.has_custom_start() {
+        flags |= FLAG_CUSTOM_START;
+    }
+    if history.is_played_by_bot() {
+        flags |= FLAG_PLAYED_BY_BOT;
+    }
+    bytes.push(flags);
+
+    if !config.is_default() {
+        for &value in &config_values(config) {
+            write_varint(&mut bytes, u64::from(value));
+        }
+    }
+
+    if history.has_custom_start() {
+        let rows = history.board().to_rows();
+        write_varint(&mut bytes, rows.len() as u64);
+        for row in &rows {
+            bytes.extend_from_slice(row.as_bytes());
+        }
+
+        let pieces = history.pieces();
+        write_varint(&mut bytes, pieces.len() as u64);
+        for shape in pieces {
+            bytes.push(shape.color.letter() as u8);
+        }
+    }
+
+    let actions = history.actions();
+    write_varint(&mut bytes, actions.len() as u64);
+
+    let mut last_tick = 0;
+
+    for &(tick, action) in actions {
+        let delta = tick
+            .value()
+            .checked_sub(last_tick)
+            .expect("history actions are in chronological order");
+        let delta = u64::from(delta);
+        write_varint(&mut bytes, delta << ACTION_BITS | action_code(action));
+        if let Action::Garbage { rows, hole } = action {
+            bytes.push(rows);
+            bytes.push(hole);
+        }
+        last_tick = tick.value();
+    }
+
+    bytes
+}
+
+/// Decode a history from the compact binary format described in `encode`.
+pub(crate) fn decode(bytes: &[u8]) -> Result<History, DecodeError> {
+    let mut reader = Reader { bytes };
+
+    let version = reader.byte()?;
+    if version != VERSION {
+        return Err(DecodeError::UnknownVersion(version));
+    }
+
+    let mut seed = [0; 4];
+    for part in &mut seed {
+        *part = reader.u32()?;
+    }
+
+    let flags = reader.byte()?;
+
+    let config = if flags & FLAG_CUSTOM_CONFIG != 0 {
+        let mut values = [0; 7];
+        for value in &mut values {
+            let varint = reader.varint_or(DecodeError::ConfigOverflow)?;
+            *value = u32::try_from(varint).map_err(|_| DecodeError::ConfigOverflow)?;
+        }
+        values_config(values)
+    } else {
+        GameConfig::default()
+    };
+
+    let (board, pieces) = if flags & FLAG_CUSTOM_START != 0 {
+        read_start(&mut reader)?
+    } else {
+        (Board::default(), Vec::new())
+    };
+
+    let mut history = History::with_start(seed, config, board, pieces);
+    if flags & FLAG_REWOUND != 0 {
+        history.set_rewound();
+    }
+    if flags & FLAG_PLAYED_BY_BOT != 0 {
+        history.set_played_by_bot();
+    }
+
+    let num_actions = reader.varint()?;
+    let mut tick: u32 = 0;
+
+    for _ in 0..num_actions {
+        let value = reader.varint_or(DecodeError::TickOverflow)?;
+        let action = read_action(value & ((1 << ACTION_BITS) - 1), &mut reader)?;
+        let delta = value >> ACTION_BITS;
+
+        tick = u32::try_from(delta)
+            .ok()
+            .and_then(|delta| tick.checked_add(delta))
+            .ok_or(DecodeError::TickOverflow)?;
+
+        history.push_action(Tick::from(tick), action);
+    }
+
+    Ok(history)
+}
+
+/// Read the board and pieces a game started from.
+fn read_start(reader: &mut Reader<'_>) -> Result<(Board, Vec<Shape>), DecodeError> {
+    let num_rows = reader.varint()?;
+    if num_rows > u64::from(Board::HEIGHT) {
+        return Err(DecodeError::InvalidStart);
+    }
+
+    let mut rows = Vec::new();
+    for _ in 0..num_rows {
+        let row = (0..Board::WIDTH)
+            .map(|_| reader.byte().map(char::from))
+            .collect::<Result<String, _>>()?;
+        rows.push(row);
+    }
+    let board = Board::from_rows(&rows).map_err(|_| DecodeError::InvalidStart)?;
+
+    let num_pieces = reader.varint()?;
+    let mut pieces = Vec::new();
+    for _ in 0..num_pieces {
+        let letter = char::from(reader.byte()?);
+        let shape = ShapeColor::from_letter(letter)
+            .and_then(Shape::from_color)
+            .ok_or(DecodeError::InvalidStart)?;
+        pieces.push(shape);
+    }
+
+    Ok((board, pieces))
+}
+
+/// Encode a history in the compact binary format, as a base64 string for transport.
+pub(crate) fn encode_base64(history: &History) -> String {
+    base64::encode(encode(history))
+}
+
+/// Decode a history from a base64 string containing the compact binary format.
+pub(crate) fn decode_base64(data: &str) -> Result<History, DecodeError> {
+    let bytes = base64::decode(data).map_err(DecodeError::InvalidBase64)?;
+    decode(&bytes)
+}
+
+/// List the numbers in a config, in the order they're encoded.
+fn config_values(config: &GameConfig) -> [u32; 7] {
+    let [single, double, triple, tetris] = config.line_scores;
+    [
+        config.initial_gravity,
+        config.gravity_per_level,
+        config.lines_per_level,
+        single,
+        double,
+        triple,
+        tetris,
+    ]
+}
+
+/// Create a config from its numbers, in the order listed by `config_values`.
+fn values_config(values: [u32; 7]) -> GameConfig {
+    let [initial_gravity, gravity_per_level, lines_per_level, single, double, triple, tetris] =
+        values;
+    GameConfig {
+        initial_gravity,
+        gravity_per_level,
+        lines_per_level,
+        line_scores: [single, double, triple, tetris],
+    }
+}
+
+fn action_code(action: Action) -> u64 {
+    match action {
+        Action::MoveLeft => 0,
+        Action::MoveRight => 1,
+        Action::Rotate => 2,
+        Action::StartSoftDrop => 3,
+        Action::StartHardDrop => 4,
+        Action::StopDrop => 5,
+        Action::Garbage { .. } => 6,
+    }
+}
+
+/// Read the action with the given code, along with any bytes following it.
+fn read_action(code: u64, reader: &mut Reader<'_>) -> Result<Action, DecodeError> {
+    match code {
+        0 => Ok(Action::MoveLeft),
+        1 => Ok(Action::MoveRight),
+        2 => Ok(Action::Rotate),
+        3 => Ok(Action::StartSoftDrop),
+        4 => Ok(Action::StartHardDrop),
+        5 => Ok(Action::StopDrop),
+        6 => {
+            let action = Action::Garbage {
+                rows: reader.byte()?,
+                hole: reader.byte()?,
+            };
+            if action.is_valid() {
+                Ok(action)
+            } else {
+                Err(DecodeError::InvalidGarbage)
+            }
+        }
+        _ => Err(DecodeError::UnknownAction(code)),
+    }
+}
+
+/// Write an unsigned LEB128 varint, using 7 bits per byte with the top bit marking continuation.
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+/// Reads values from the front of a byte slice.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn byte(&mut self) -> Result<u8, DecodeError> {
+        let (&first, rest) = self.bytes.split_first().ok_or(DecodeError::UnexpectedEnd)?;
+        self.bytes = rest;
+        Ok(first)
+    }
+
+    fn u32(&mut self) -> Result<u32, DecodeError> {
+        let mut le_bytes = [0; 4];
+        for byte in &mut le_bytes {
+            *byte = self.byte()?;
+        }
+        Ok(u32::from_le_bytes(le_bytes))
+    }
+
+    /// Read an unsigned LEB128 varint, as written by `write_varint`.
+    fn varint(&mut self) -> Result<u64, DecodeError> {
+        let mut value = 0;
+
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(DecodeError::VarintTooLong)
+    }
+
+    /// Read a varint, reporting one too long to fit in 64 bits as the given error.
+    fn varint_or(&mut self, too_long: DecodeError) -> Result<u64, DecodeError> {
+        self.varint().map_err(|e| match e {
+            DecodeError::VarintTooLong => too_long,
+            e => e,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::score::ScoreMessage;
+
+    use super::*;
+
+    fn long_game() -> History {
+        let body = include_str!("../../resources/games/long.json");
+        let message: ScoreMessage = serde_json::from_str(body).unwrap();
+        message.into_history()
+    }
+
+    #[test]
+    fn a_history_is_the_same_after_encoding_and_decoding() {
+        let history = long_game();
+        let decoded = decode(&encode(&history)).unwrap();
+
+        assert_eq!(decoded.seed(), history.seed());
+        assert_eq!(decoded.actions(), history.actions());
+    }
+
+    #[test]
+    fn a_history_is_the_same_after_base64_encoding_and_decoding() {
+        let history = long_game();
+        let decoded = decode_base64(&encode_base64(&history)).unwrap();
+
+        assert_eq!(decoded.actions(), history.actions());
+    }
+
+    #[test]
+    fn a_rewound_history_is_still_rewound_after_encoding_and_decoding() {
+        let mut history = long_game();
+        history.set_rewound();
+
+        assert!(decode(&encode(&history)).unwrap().is_rewound());
+    }
+
+    #[test]
+    fn a_history_played_by_a_bot_is_still_played_by_a_bot_after_encoding_and_decoding() {
+        let mut history = long_game();
+        history.set_played_by_bot();
+
+        assert!(decode(&encode(&history)).unwrap().is_played_by_bot());
+    }
+
+    #[test]
+    fn a_custom_config_is_the_same_after_encoding_and_decoding() {
+        let config = GameConfig {
+            initial_gravity: 50,
+            lines_per_level: 0,
+            line_scores: [1, 2, 3, 1000],
+            ..GameConfig::default()
+        };
+        let history = History::with_config([1, 2, 3, 4], config);
+
+        assert_eq!(*decode(&encode(&history)).unwrap().config(), config);
+    }
+
+    #[test]
+    fn garbage_is_the_same_after_encoding_and_decoding() {
+        let mut history = History::new([1, 2, 3, 4]);
+        history.push_action(Tick::from(10), Action::Rotate);
+        history.push_action(Tick::from(12), Action::Garbage { rows: 3, hole: 7 });
+        history.push_action(Tick::from(12), Action::MoveLeft);
+        let decoded = decode(&encode(&history)).unwrap();
+
+        assert_eq!(decoded.actions(), history.actions());
+    }
+
+    #[test]
+    fn garbage_with_a_hole_outside_the_board_is_an_error() {
+        let mut history = History::new([1, 2, 3, 4]);
+        history.push_action(Tick::from(0), Action::Garbage { rows: 1, hole: 10 });
+
+        assert!(matches!(
+            decode(&encode(&history)),
+            Err(DecodeError::InvalidGarbage)
+        ));
+    }
+
+    #[test]
+    fn a_json_history_listing_actions_out_of_order_is_an_error() {
+        let mut history = History::new([1, 2, 3, 4]);
+        history.push_action(Tick::from(10), Action::Rotate);
+        history.push_action(Tick::from(12), Action::MoveLeft);
+        let mut json = serde_json::to_value(&history).unwrap();
+        json["actions"].as_array_mut().unwrap().swap(0, 1);
+
+        assert!(serde_json::from_value::<History>(json).is_err());
+    }
+
+    #[test]
+    fn the_compact_encoding_is_much_smaller_than_json() {
+        let history = long_game();
+        let json = serde_json::to_string(&history).unwrap();
+
+        assert!(encode_base64(&history).len() * 5 < json.len());
+    }
+
+    #[test]
+    fn a_truncated_history_is_an_error() {
+        let bytes = encode(&long_game());
+
+        assert!(matches!(
+            decode(&bytes[..bytes.len() - 1]),
+            Err(DecodeError::UnexpectedEnd)
+        ));
+    }
+
+    /// The start of a history with the default config and board, before the number of actions.
+    fn header() -> Vec<u8> {
+        let mut bytes = vec![VERSION];
+        bytes.extend([0; 16]);
+        bytes.push(0);
+        bytes
+    }
+
+    #[test]
+    fn a_varint_too_long_for_64_bits_is_an_error() {
+        let mut bytes = header();
+        bytes.extend([0xff; 10]);
+
+        assert!(matches!(decode(&bytes), Err(DecodeError::VarintTooLong)));
+    }
+
+    #[test]
+    fn an_action_too_long_for_64_bits_is_a_tick_overflow() {
+        let mut bytes = header();
+        bytes.push(1);
+        bytes.extend([0xff; 10]);
+
+        assert!(matches!(decode(&bytes), Err(DecodeError::TickOverflow)));
+    }
+
+    #[test]
+    fn an_unknown_version_is_an_error() {
+        let mut bytes = encode(&long_game());
+        bytes[0] = VERSION + 1;
+
+        assert!(matches!(
+            decode(&bytes),
+            Err(DecodeError::UnknownVersion(_))
+        ));
+    }
+}
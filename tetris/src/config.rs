@@ -0,0 +1,126 @@
+use serde_derive::{Deserialize, Serialize};
+
+/// The rules of a game that can be changed, such as how fast pieces fall and how lines are scored.
+///
+/// Only games with the default rules can be submitted as high-scores. A config is loaded from
+/// JSON, where any missing fields keep their default values.
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+#[serde(default)]
+pub struct GameConfig {
+    /// How fast pieces fall at the start of the game, in hundredths of a cell per frame.
+    pub initial_gravity: u32,
+
+    /// How much faster pieces fall at each level, in hundredths of a cell per frame.
+    ///
+    /// Pieces never fall faster than a soft drop.
+    pub gravity_per_level: u32,
+
+    /// The number of lines to clear to go up a level, or zero to stay on the first level.
+    pub lines_per_level: u32,
+
+    /// The points for clearing one, two, three and four lines with a single piece.
+    pub line_scores: [u32; 4],
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            initial_gravity: 4,
+            gravity_per_level: 2,
+            lines_per_level: 10,
+            line_scores: [100, 400, 900, 1600],
+        }
+    }
+}
+
+impl GameConfig {
+    /// Parse a config from JSON.
+    pub fn from_json(json: &str) -> serde_json::Result<GameConfig> {
+        serde_json::from_str(json)
+    }
+
+    /// Return whether these are the standard rules.
+    pub fn is_default(&self) -> bool {
+        *self == GameConfig::default()
+    }
+
+    /// Get the level after clearing the given number of lines, starting from zero.
+    pub(crate) fn level(&self, lines_cleared: u32) -> u32 {
+        lines_cleared.checked_div(self.lines_per_level).unwrap_or(0)
+    }
+
+    /// Get the points for clearing the given number of lines with a single piece.
+    pub(crate) fn line_score(&self, lines_cleared: u32) -> u32 {
+        match lines_cleared {
+            0 => 0,
+            lines => self.line_scores[lines.min(4) as usize - 1],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::Board;
+    use crate::game::Action;
+    use crate::game::GameState;
+    use crate::game::History;
+    use crate::shape::Shape;
+    use crate::shape::ShapeColor;
+
+    use super::*;
+
+    #[test]
+    fn missing_fields_in_json_keep_their_defaults() {
+        let config = GameConfig::from_json(r#"{"lines_per_level": 5}"#).unwrap();
+
+        assert_eq!(config.lines_per_level, 5);
+        assert_eq!(
+            config.initial_gravity,
+            GameConfig::default().initial_gravity
+        );
+        assert!(!config.is_default());
+    }
+
+    #[test]
+    fn the_default_line_scores_are_the_square_of_the_lines_cleared() {
+        let config = GameConfig::default();
+
+        for lines in 0..=4 {
+            assert_eq!(config.line_score(lines), lines * lines * 100);
+        }
+    }
+
+    #[test]
+    fn the_level_never_increases_with_zero_lines_per_level() {
+        let config = GameConfig {
+            lines_per_level: 0,
+            ..GameConfig::default()
+        };
+
+        assert_eq!(config.level(100), 0);
+    }
+
+    #[test]
+    fn huge_rules_do_not_overflow() {
+        let config = GameConfig {
+            initial_gravity: u32::MAX,
+            gravity_per_level: u32::MAX,
+            lines_per_level: 1,
+            line_scores: [u32::MAX; 4],
+        };
+        let board = Board::from_rows(&["XXXX..XXXX"]).unwrap();
+        let o = Shape::from_color(ShapeColor::O).unwrap();
+        let history = History::with_start([1, 2, 3, 4], config, board, vec![o]);
+
+        let mut game_state = GameState::start_of(&history);
+        game_state.lines_cleared = 10;
+        game_state.score = 1;
+        game_state.apply_action(Action::StartHardDrop);
+        while game_state.pieces_placed == 0 {
+            game_state.apply_step();
+        }
+
+        assert_eq!(game_state.lines_cleared, 11);
+        assert_eq!(game_state.score, u32::MAX);
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::VecDeque;
+
+use crate::game::Game;
+use crate::piece::Piece;
+use crate::placement::apply_move;
+use crate::placement::hard_drop;
+use crate::placement::sorted_blocks;
+use crate::placement::Move;
+use crate::placement::Placement;
+
+/// Something that decides where to place each piece, such as an AI.
+pub trait Controller {
+    /// Pick where to place the game's current piece, starting from where it is now.
+    ///
+    /// Returns `None` if there's nowhere to place the piece, in which case it's hard dropped.
+    fn choose(&mut self, game: &Game) -> Option<Placement>;
+}
+
+impl<C: Controller + ?Sized> Controller for &mut C {
+    fn choose(&mut self, game: &Game) -> Option<Placement> {
+        (**self).choose(game)
+    }
+}
+
+/// Plays a `Game` for a `Controller`, pressing the same keys as a human player would to place each
+/// piece where the controller chooses.
+///
+/// The player must be told to play every frame before the game is updated.
+#[derive(Clone, Debug)]
+pub struct Player<C> {
+    controller: C,
+
+    /// How the current piece is being placed, if the controller has chosen.
+    plan: Option<Plan>,
+}
+
+/// Where a piece is being placed, and the moves left to get it there.
+#[derive(Clone, Debug)]
+struct Plan {
+    /// The number of pieces placed in the game before this piece, to tell when it has locked.
+    piece_number: u32,
+
+    /// Where the piece is being placed.
+    target: Piece,
+
+    /// Where the piece is expected to be, if nothing but the player has moved it.
+    expected: Piece,
+
+    /// The moves left to reach the target from the expected position.
+    moves: VecDeque<Move>,
+
+    /// Whether the player is holding a soft drop.
+    dropping: bool,
+}
+
+impl<C: Controller> Player<C> {
+    /// Create a player that places pieces where the given controller chooses.
+    pub fn new(controller: C) -> Self {
+        Player {
+            controller,
+            plan: None,
+        }
+    }
+
+    /// Get the controller choosing where to place pieces.
+    pub fn controller(&self) -> &C {
+        &self.controller
+    }
+
+    /// Press the keys to place the game's current piece, asking the controller where to place it
+    /// if it's new.
+    ///
+    /// The game is marked as played by a bot, so it can't be submitted as a score.
+    pub fn play(&mut self, game: &mut Game) {
+        game.set_played_by_bot();
+
+        let is_new_piece = self
+            .plan
+            .as_ref()
+            .map_or(true, |plan| plan.piece_number != game.pieces_placed());
+
+        if is_new_piece {
+            self.plan = Some(self.make_plan(game));
+        } else if self
+            .plan
+            .as_ref()
+            .map_or(false, |plan| plan.is_off_course(game))
+        {
+            // Gravity has moved the piece, so find a new way to the target from where it is now
+            self.replan(game);
+        }
+
+        if let Some(plan) = &mut self.plan {
+            plan.follow(game);
+        }
+    }
+
+    /// Ask the controller where to place the game's current piece, hard dropping it if there's
+    /// nowhere to place it.
+    fn make_plan(&mut self, game: &Game) -> Plan {
+        let placement = self.controller.choose(game).unwrap_or_else(|| Placement {
+            piece: hard_drop(game.board(), game.piece()),
+            moves: vec![Move::Down],
+        });
+
+        Plan::new(game, placement)
+    }
+
+    /// Find a new way to place the current piece, after it has moved unexpectedly.
+    ///
+    /// The player sticks to the same target if it's still reachable, otherwise asks the
+    /// controller again.
+    fn replan(&mut self, game: &Game) {
+        let (target, dropping) = match &self.plan {
+            Some(plan) => (sorted_blocks(&plan.target), plan.dropping),
+            None => return,
+        };
+
+        let placements = Placement::find_all_from(game.board(), game.piece().clone());
+
+        let mut plan = match placements
+            .into_iter()
+            .find(|placement| sorted_blocks(&placement.piece) == target)
+        {
+            Some(placement) => Plan::new(game, placement),
+            None => self.make_plan(game),
+        };
+
+        plan.dropping = dropping;
+        self.plan = Some(plan);
+    }
+}
+
+impl Plan {
+    fn new(game: &Game, placement: Placement) -> Self {
+        Plan {
+            piece_number: game.pieces_placed(),
+            target: placement.piece,
+            expected: game.piece().clone(),
+            moves: placement.moves.into(),
+            dropping: false,
+        }
+    }
+
+    /// Return whether the piece isn't where the plan expects, while there are still moves to make.
+    ///
+    /// Once the piece is hard dropped it's expected to move, so it's never off course.
+    fn is_off_course(&self, game: &Game) -> bool {
+        !self.moves.is_empty() && &self.expected != game.piece()
+    }
+
+    /// Press the keys for the next moves in the plan.
+    ///
+    /// Moves and rotations are all made at once, while drops happen one cell each frame using soft
+    /// drop. Once only drops are left, the piece is hard dropped.
+    fn follow(&mut self, game: &mut Game) {
+        while let Some(&mv) = self.moves.front() {
+            if mv == Move::Down {
+                if self.moves.iter().all(|&mv| mv == Move::Down) {
+                    self.moves.clear();
+                    self.expected = self.target.clone();
+                    game.start_hard_drop();
+                } else {
+                    if !self.dropping {
+                        game.start_soft_drop();
+                        self.dropping = true;
+                    }
+                    self.moves.pop_front();
+                    self.expected.down();
+                }
+                return;
+            }
+
+            if self.dropping {
+                game.stop_drop();
+                self.dropping = false;
+            }
+
+            match mv {
+                Move::Left => game.move_left(),
+                Move::Right => game.move_right(),
+                Move::Rotate => game.rotate(),
+                Move::Down => unreachable!(),
+            }
+
+            self.moves.pop_front();
+            if let Some(moved) = apply_move(game.board(), &self.expected, mv) {
+                self.expected = moved;
+            }
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use rand::Rng;
+use rand::SeedableRng;
+use rand::XorShiftRng;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::ai::Ai;
+use crate::ai::Weights;
+use crate::controller::Controller;
+use crate::controller::Player;
+use crate::game::Game;
+use crate::placement::Placement;
+
+/// How well a CPU opponent plays in versus mode.
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Difficulty {
+    /// Slow, and often places pieces in the wrong place
+    Easy,
+    /// A steady pace, with the occasional mistake
+    Medium,
+    /// Fast, never makes mistakes and looks ahead at the next piece
+    Hard,
+}
+
+impl Difficulty {
+    /// Every difficulty, from easiest to hardest.
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard];
+
+    /// Get the number of pieces the CPU places per second, at most.
+    pub fn pieces_per_second(self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.5,
+            Difficulty::Medium => 1.0,
+            Difficulty::Hard => 2.0,
+        }
+    }
+
+    /// Get the percentage chance of the CPU placing a piece somewhere random, instead of where
+    /// the AI thinks is best.
+    pub fn mistake_chance(self) -> u8 {
+        match self {
+            Difficulty::Easy => 25,
+            Difficulty::Medium => 5,
+            Difficulty::Hard => 0,
+        }
+    }
+
+    /// Get how many shapes from the preview the CPU considers.
+    fn lookahead(self) -> usize {
+        match self {
+            Difficulty::Easy | Difficulty::Medium => 0,
+            Difficulty::Hard => 1,
+        }
+    }
+
+    /// Get how many frames the CPU waits after each piece appears before moving it.
+    fn frames_per_piece(self) -> u32 {
+        (60.0 / self.pieces_per_second()) as u32
+    }
+}
+
+impl Display for Difficulty {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Difficulty::Easy => write!(f, "easy"),
+            Difficulty::Medium => write!(f, "medium"),
+            Difficulty::Hard => write!(f, "hard"),
+        }
+    }
+}
+
+/// Chooses where the CPU places each piece, using the AI but sometimes making a mistake.
+#[derive(Clone, Debug)]
+struct Mistaken {
+    ai: Ai,
+
+    /// The percentage chance of placing a piece somewhere random.
+    mistake_chance: u8,
+
+    rng: XorShiftRng,
+}
+
+impl Controller for Mistaken {
+    fn choose(&mut self, game: &Game) -> Option<Placement> {
+        if self.rng.gen_range(0, 100) >= self.mistake_chance {
+            return self.ai.choose(game);
+        }
+
+        let mut placements = Placement::find_all_from(game.board(), game.piece().clone());
+        if placements.is_empty() {
+            None
+        } else {
+            let index = self.rng.gen_range(0, placements.len());
+            Some(placements.swap_remove(index))
+        }
+    }
+}
+
+/// A CPU opponent in versus mode, playing its own game at a speed set by its difficulty.
+///
+/// The CPU is played one frame at a time alongside the game, so it runs in the same loop as the
+/// human player.
+#[derive(Clone, Debug)]
+pub struct Cpu {
+    difficulty: Difficulty,
+    player: Player<Mistaken>,
+
+    /// The number of pieces placed when the CPU started waiting, to tell when a new piece appears.
+    piece_number: u32,
+
+    /// How many frames the CPU has waited on the current piece.
+    waited: u32,
+}
+
+impl Cpu {
+    /// Create a CPU opponent of the given difficulty, whose mistakes are determined by the seed.
+    pub fn new(difficulty: Difficulty, seed: [u32; 4]) -> Self {
+        // Scramble the seed so mistakes aren't made in step with the pieces or garbage holes
+        let mistake_seed = seed.map(|part| part.rotate_left(8));
+
+        let controller = Mistaken {
+            ai: Ai::new(Weights::default(), difficulty.lookahead()),
+            mistake_chance: difficulty.mistake_chance(),
+            rng: XorShiftRng::from_seed(mistake_seed),
+        };
+
+        Cpu {
+            difficulty,
+            player: Player::new(controller),
+            piece_number: 0,
+            waited: 0,
+        }
+    }
+
+    /// Get how well the CPU plays.
+    pub fn difficulty(&self) -> Difficulty {
+        self.difficulty
+    }
+
+    /// Press the keys for the CPU on this frame, before the game is updated.
+    ///
+    /// The CPU waits a while on each new piece, so it places pieces no faster than its difficulty
+    /// allows.
+    pub fn play(&mut self, game: &mut Game) {
+        if game.pieces_placed() != self.piece_number {
+            self.piece_number = game.pieces_placed();
+            self.waited = 0;
+        }
+
+        if self.waited < self.difficulty.frames_per_piece() {
+            self.waited += 1;
+        } else {
+            self.player.play(game);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::StepResult;
+
+    use super::*;
+
+    const SEED: [u32; 4] = [1, 2, 3, 4];
+
+    /// Let a CPU play a game for the given number of frames, returning the pieces placed.
+    fn pieces_placed(difficulty: Difficulty, frames: u32) -> u32 {
+        let mut game = Game::from_seed(SEED);
+        let mut cpu = Cpu::new(difficulty, SEED);
+
+        for _ in 0..frames {
+            cpu.play(&mut game);
+            if game.step() == StepResult::GameOver {
+                break;
+            }
+        }
+
+        game.pieces_placed()
+    }
+
+    #[test]
+    fn harder_cpus_place_pieces_faster() {
+        let easy = pieces_placed(Difficulty::Easy, 1200);
+        let hard = pieces_placed(Difficulty::Hard, 1200);
+
+        assert!(easy < hard, "easy placed {}, hard placed {}", easy, hard);
+    }
+
+    #[test]
+    fn a_cpu_places_no_more_pieces_per_second_than_its_difficulty() {
+        for &difficulty in &Difficulty::ALL {
+            let pieces = pieces_placed(difficulty, 600);
+            let max_pieces = (difficulty.pieces_per_second() * 10.0) as u32;
+
+            assert!(
+                pieces <= max_pieces,
+                "{} placed {} pieces",
+                difficulty,
+                pieces
+            );
+        }
+    }
+
+    #[test]
+    fn a_cpu_that_always_makes_mistakes_still_places_pieces() {
+        let mut controller = Mistaken {
+            ai: Ai::default(),
+            mistake_chance: 100,
+            rng: XorShiftRng::from_seed(SEED),
+        };
+
+        assert!(controller.choose(&Game::from_seed(SEED)).is_some());
+    }
+}
@@ -0,0 +1,604 @@
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::time::Duration;
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::board::Board;
+use crate::game::Action;
+use crate::game::History;
+use crate::game::StepResult;
+use crate::game::Tick;
+use crate::garbage;
+use crate::garbage::GarbageHoles;
+use crate::garbage::HolePlacement;
+use crate::puzzle::Progress;
+use crate::replay::ReplayPlayer;
+use crate::rest;
+use crate::score::check_name;
+use crate::score::ScoreValidationError;
+use crate::state::State;
+use crate::statistics::Statistics;
+
+/// The end-point for the Tetris server that serves the dig race leaderboard.
+pub const DIG_TIME_ENDPOINT: &str = "/dig-times";
+
+/// The number of times kept on the dig race leaderboard.
+pub const DIG_LEADERBOARD_SIZE: usize = 10;
+
+/// The rate the game is played at, used to convert frames to time.
+const FRAMES_PER_SECOND: u64 = 60;
+
+/// A dig race, where the player starts with rows of garbage and races to clear all of them.
+///
+/// The holes in the garbage are picked from the game's seed, so every race with the same seed
+/// starts from the same board. Only races with the default setup go on the leaderboard.
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+#[serde(default)]
+pub struct DigRace {
+    /// The number of garbage rows to clear.
+    pub rows: u8,
+
+    /// The percentage chance of each garbage row having its hole in a different column from the
+    /// row below it.
+    pub messiness: u8,
+}
+
+impl Default for DigRace {
+    fn default() -> Self {
+        DigRace {
+            rows: 10,
+            messiness: 100,
+        }
+    }
+}
+
+impl DigRace {
+    /// Return whether this is the standard race, which can go on the leaderboard.
+    pub fn is_default(&self) -> bool {
+        *self == DigRace::default()
+    }
+
+    /// Get the garbage a race with the given seed starts with, as `(rows, hole)` pairs in the order
+    /// they're added.
+    pub(crate) fn garbage(&self, seed: [u32; 4]) -> Vec<(u8, u8)> {
+        let mut holes = GarbageHoles::new(seed, HolePlacement::Messy(self.messiness));
+        garbage::runs(&holes.pick(u32::from(self.rows)))
+    }
+
+    /// Check the progress of the race, after the given step.
+    pub(crate) fn progress(&self, board: &Board, step_result: StepResult) -> Progress {
+        if board.garbage_rows() == 0 {
+            Progress::Solved
+        } else if step_result == StepResult::GameOver {
+            Progress::Failed
+        } else {
+            Progress::Unsolved
+        }
+    }
+
+    /// Replay a game of this race, returning the frame all the garbage was cleared on.
+    ///
+    /// Returns `None` if the game topped out first.
+    fn finishing_frame(&self, history: &History) -> Option<u32> {
+        let mut player = ReplayPlayer::new(history.clone());
+
+        loop {
+            let step_result = player.step();
+            match self.progress(player.board(), step_result) {
+                Progress::Solved => return Some(player.tick()),
+                Progress::Failed => return None,
+                Progress::Unsolved => {}
+            }
+        }
+    }
+}
+
+/// A time on the dig race leaderboard.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+pub struct DigTime {
+    /// The number of frames taken to clear all the garbage.
+    pub frames: u32,
+    /// The name of the player who achieved this time.
+    pub name: String,
+}
+
+impl DigTime {
+    /// Create a new `DigTime` from the given number of `frames` and `name`.
+    pub fn new(frames: u32, name: String) -> Self {
+        DigTime { frames, name }
+    }
+
+    /// Get the time taken to clear all the garbage.
+    pub fn duration(&self) -> Duration {
+        Duration::from_millis(u64::from(self.frames) * 1000 / FRAMES_PER_SECOND)
+    }
+}
+
+impl Ord for DigTime {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.frames.cmp(&other.frames)
+    }
+}
+
+impl PartialOrd for DigTime {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// A message for posting a dig race time. Includes the `History` for validation purposes.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DigTimeMessage {
+    time: DigTime,
+    history: History,
+}
+
+/// Potential errors from dig race time validation.
+#[derive(Debug)]
+pub enum DigValidationError {
+    /// Name is not valid
+    InvalidName(ScoreValidationError),
+    /// Game was rewound in practice mode or branched from a replay
+    Rewound,
+    /// Game was played by a bot
+    PlayedByBot,
+    /// Game was played with non-default rules
+    CustomRules,
+    /// Game started from its own board or pieces
+    CustomStart,
+    /// Game didn't start with the standard race's garbage, or had garbage added later
+    WrongGarbage,
+    /// Game topped out before all the garbage was cleared
+    Unfinished,
+    /// Time does not match game history
+    UnexpectedTime {
+        /// The time that was submitted
+        time: DigTime,
+        /// The number of frames the history took to clear all the garbage
+        expected_frames: u32,
+    },
+}
+
+impl Error for DigValidationError {}
+
+impl Display for DigValidationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            DigValidationError::InvalidName(e) => write!(f, "{}", e),
+            DigValidationError::Rewound => {
+                write!(
+                    f,
+                    "Game was rewound or branched from a replay, so can't be submitted"
+                )
+            }
+            DigValidationError::PlayedByBot => {
+                write!(f, "Game was played by a bot, so can't be submitted")
+            }
+            DigValidationError::CustomRules => {
+                write!(
+                    f,
+                    "Game was played with custom rules, so can't be submitted"
+                )
+            }
+            DigValidationError::CustomStart => {
+                write!(
+                    f,
+                    "Game didn't start from an empty board, so can't be submitted"
+                )
+            }
+            DigValidationError::WrongGarbage => {
+                write!(f, "Game didn't have the standard dig race garbage")
+            }
+            DigValidationError::Unfinished => {
+                write!(f, "Game topped out before clearing all the garbage")
+            }
+            DigValidationError::UnexpectedTime {
+                time,
+                expected_frames,
+            } => write!(
+                f,
+                "Time does not match game history:\n History suggests {} frames but was {}",
+                expected_frames, time.frames
+            ),
+        }
+    }
+}
+
+impl DigTimeMessage {
+    /// Create a new `DigTimeMessage` from the given `DigTime` and `History`.
+    pub fn new(time: DigTime, history: History) -> Self {
+        DigTimeMessage { time, history }
+    }
+
+    /// Get the history of the race that achieved the time.
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// Extract the `DigTime`, but only if it is valid.
+    ///
+    /// A valid time will have a 3-letter long alphanumeric name, and will match the given history
+    /// of the standard race.
+    pub fn time(self) -> Result<DigTime, DigValidationError> {
+        check_name(&self.time.name).map_err(DigValidationError::InvalidName)?;
+
+        if self.history.is_rewound() {
+            return Err(DigValidationError::Rewound);
+        }
+
+        if self.history.is_played_by_bot() {
+            return Err(DigValidationError::PlayedByBot);
+        }
+
+        if !self.history.config().is_default() {
+            return Err(DigValidationError::CustomRules);
+        }
+
+        if self.history.has_custom_start() {
+            return Err(DigValidationError::CustomStart);
+        }
+
+        let race = DigRace::default();
+        let garbage: Vec<(Tick, Action)> = race
+            .garbage(self.history.seed())
+            .into_iter()
+            .map(|(rows, hole)| (Tick::from(0), Action::Garbage { rows, hole }))
+            .collect();
+
+        let actions = self.history.actions();
+        let starts_with_garbage = actions.get(..garbage.len()) == Some(&garbage[..]);
+        let garbage_added_later = actions[garbage.len().min(actions.len())..]
+            .iter()
+            .any(|(_, action)| matches!(action, Action::Garbage { .. }));
+
+        if !starts_with_garbage || garbage_added_later {
+            return Err(DigValidationError::WrongGarbage);
+        }
+
+        let expected_frames = race
+            .finishing_frame(&self.history)
+            .ok_or(DigValidationError::Unfinished)?;
+
+        if expected_frames == self.time.frames {
+            Ok(self.time)
+        } else {
+            Err(DigValidationError::UnexpectedTime {
+                time: self.time,
+                expected_frames,
+            })
+        }
+    }
+}
+
+/// Dig race over state, where the user can see their time and post it to the leaderboard.
+pub struct DigOver {
+    /// The race that was played.
+    pub race: DigRace,
+
+    /// Whether all the garbage was cleared, rather than the player topping out.
+    pub finished: bool,
+
+    /// The time the race took, and the name the user has entered for the leaderboard.
+    pub time: DigTime,
+
+    /// The fastest times on the leaderboard.
+    ///
+    /// Optional because these are retrieved from the internet, so might not be available. They're
+    /// also only retrieved when the standard race was finished.
+    pub leaderboard: Option<Vec<DigTime>>,
+
+    /// Statistics about how the user played the race.
+    pub statistics: Statistics,
+
+    /// The history of the race.
+    pub history: History,
+}
+
+impl DigOver {
+    /// Create a new dig race over state, for a race that was either finished or topped out on
+    /// the given frame.
+    pub fn new(
+        race: DigRace,
+        finished: bool,
+        frames: u32,
+        statistics: Statistics,
+        history: History,
+    ) -> Self {
+        let leaderboard = if finished && race.is_default() {
+            match rest::get_dig_times() {
+                Ok(times) => Some(times),
+                Err(e) => {
+                    println!("Failed to retrieve dig times: {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        DigOver {
+            race,
+            finished,
+            time: DigTime::new(frames, "".to_string()),
+            leaderboard,
+            statistics,
+            history,
+        }
+    }
+
+    /// Return whether the user can post their time to the leaderboard.
+    ///
+    /// This is true only if the standard race was finished, the leaderboard was retrieved and the
+    /// time is fast enough to go on it, and the game wasn't rewound, played by a bot, played with
+    /// custom rules or started from its own board.
+    pub fn posting_time(&self) -> bool {
+        self.finished
+            && self.race.is_default()
+            && !self.history.is_rewound()
+            && !self.history.is_played_by_bot()
+            && self.history.config().is_default()
+            && !self.history.has_custom_start()
+            && self.leaderboard.as_ref().map_or(false, |times| {
+                times.len() < DIG_LEADERBOARD_SIZE
+                    || times.last().map_or(true, |slowest| self.time < *slowest)
+            })
+    }
+
+    /// Delete a character from the entered name.
+    pub fn backspace(&mut self) {
+        self.time.name.pop();
+    }
+
+    /// Push some characters to the entered name.
+    pub fn push_name(&mut self, str: &str) {
+        if str.chars().all(char::is_alphanumeric) {
+            self.time.name.push_str(str);
+            self.time.name.truncate(3);
+        }
+    }
+
+    /// Potentially submit the time if allowed, then start another race with the same setup.
+    pub fn submit(self) -> State {
+        if !self.posting_time() || !self.time.name.is_empty() {
+            if self.posting_time() {
+                let message = DigTimeMessage::new(self.time.clone(), self.history);
+                rest::post_dig_time(&message);
+            }
+            State::dig(self.race)
+        } else {
+            State::DigOver(self)
+        }
+    }
+
+    /// Start another race with the same setup, without submitting the time.
+    pub fn retry(self) -> State {
+        State::dig(self.race)
+    }
+
+    /// Return to the title screen.
+    pub fn exit(self) -> State {
+        State::title()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ai::Ai;
+    use crate::ai::Weights;
+    use crate::controller::Player;
+    use crate::game::Game;
+    use crate::placement::apply_move;
+    use crate::placement::hard_drop;
+    use crate::placement::sorted_blocks;
+    use crate::placement::Move;
+    use crate::placement::Placement;
+
+    use super::*;
+
+    const SEED: [u32; 4] = [1, 2, 3, 4];
+
+    /// Play a race by pressing keys until all the garbage is cleared, returning the frame it
+    /// finished.
+    ///
+    /// The keys are pressed on the game directly rather than through a `Player`, so the race counts
+    /// as played by a person.
+    fn play_race(game: &mut Game) -> u32 {
+        let race = *game.current_dig().unwrap();
+        let ai = Ai::new(Weights::default(), 0);
+        let mut pieces_placed = None;
+
+        loop {
+            if pieces_placed != Some(game.pieces_placed()) {
+                pieces_placed = Some(game.pieces_placed());
+                hard_drop_where_the_ai_would(game, &ai);
+            }
+
+            let step_result = game.step();
+            match race.progress(game.board(), step_result) {
+                Progress::Solved => return game.statistics().frames(),
+                Progress::Failed => panic!("the race topped out"),
+                Progress::Unsolved => {}
+            }
+        }
+    }
+
+    /// Move and rotate the current piece to the best place the AI can find for it without tucks or
+    /// spins, then hard drop it.
+    fn hard_drop_where_the_ai_would(game: &mut Game, ai: &Ai) {
+        let placements = hard_drop_placements(game);
+
+        if let Some((placement, _)) = ai.best_of(game.board(), placements, &[]) {
+            for mv in placement.moves {
+                match mv {
+                    Move::Left => game.move_left(),
+                    Move::Right => game.move_right(),
+                    Move::Rotate => game.rotate(),
+                    Move::Down => unreachable!(),
+                }
+            }
+        }
+
+        game.start_hard_drop();
+    }
+
+    /// Find everywhere the current piece can be placed by rotating it, moving it sideways, then
+    /// hard dropping it.
+    fn hard_drop_placements(game: &Game) -> Vec<Placement> {
+        let width = usize::from(Board::WIDTH);
+        let shifts = (0..width)
+            .map(|distance| (Move::Left, distance))
+            .chain((1..width).map(|distance| (Move::Right, distance)));
+
+        let mut placements: Vec<Placement> = vec![];
+
+        for rotations in 0..4 {
+            for (direction, distance) in shifts.clone() {
+                let mut moves = vec![Move::Rotate; rotations];
+                moves.extend(std::iter::repeat(direction).take(distance));
+
+                let moved = moves.iter().try_fold(game.piece().clone(), |piece, &mv| {
+                    apply_move(game.board(), &piece, mv)
+                });
+
+                if let Some(moved) = moved {
+                    let piece = hard_drop(game.board(), &moved);
+                    let blocks = sorted_blocks(&piece);
+                    if placements
+                        .iter()
+                        .all(|placement| sorted_blocks(&placement.piece) != blocks)
+                    {
+                        placements.push(Placement { piece, moves });
+                    }
+                }
+            }
+        }
+
+        placements
+    }
+
+    #[test]
+    fn a_race_starts_with_the_given_number_of_garbage_rows() {
+        let race = DigRace {
+            rows: 8,
+            messiness: 50,
+        };
+        let game = Game::dig_from_seed(SEED, race);
+
+        assert_eq!(game.board().garbage_rows(), 8);
+    }
+
+    #[test]
+    fn a_race_with_the_same_seed_starts_from_the_same_board() {
+        let first = Game::dig_from_seed(SEED, DigRace::default());
+        let second = Game::dig_from_seed(SEED, DigRace::default());
+
+        assert_eq!(first.board(), second.board());
+    }
+
+    #[test]
+    fn a_race_is_solved_once_every_garbage_row_is_cleared() {
+        let race = DigRace::default();
+        let board = Board::from_rows(&["..TTT.....", "...T......"]).unwrap();
+
+        assert_eq!(
+            race.progress(&board, StepResult::Continue),
+            Progress::Solved
+        );
+    }
+
+    #[test]
+    fn a_valid_time_is_accepted() {
+        let mut game = Game::dig_from_seed(SEED, DigRace::default());
+        let frames = play_race(&mut game);
+
+        let message = DigTimeMessage::new(
+            DigTime::new(frames, "DIG".to_string()),
+            game.history().clone(),
+        );
+
+        assert_eq!(
+            message.time().unwrap(),
+            DigTime::new(frames, "DIG".to_string())
+        );
+    }
+
+    #[test]
+    fn a_recorded_race_is_accepted() {
+        let body = include_str!("../../resources/games/dig.json");
+        let message: DigTimeMessage = serde_json::from_str(body).unwrap();
+
+        assert_eq!(
+            message.time().unwrap(),
+            DigTime::new(412, "DIG".to_string())
+        );
+    }
+
+    #[test]
+    fn a_race_played_by_a_bot_is_rejected() {
+        let mut game = Game::dig_from_seed(SEED, DigRace::default());
+        let mut player = Player::new(Ai::new(Weights::default(), 0));
+        player.play(&mut game);
+        game.step();
+
+        let message = DigTimeMessage::new(
+            DigTime::new(game.statistics().frames(), "DIG".to_string()),
+            game.history().clone(),
+        );
+
+        assert!(matches!(
+            message.time(),
+            Err(DigValidationError::PlayedByBot)
+        ));
+    }
+
+    #[test]
+    fn a_time_that_does_not_match_the_history_is_rejected() {
+        let mut game = Game::dig_from_seed(SEED, DigRace::default());
+        let frames = play_race(&mut game);
+
+        let message = DigTimeMessage::new(
+            DigTime::new(frames - 1, "DIG".to_string()),
+            game.history().clone(),
+        );
+
+        assert!(matches!(
+            message.time(),
+            Err(DigValidationError::UnexpectedTime { .. })
+        ));
+    }
+
+    #[test]
+    fn a_race_with_a_different_setup_is_rejected() {
+        let race = DigRace {
+            rows: 2,
+            messiness: 0,
+        };
+        let mut game = Game::dig_from_seed(SEED, race);
+        let frames = play_race(&mut game);
+
+        let message = DigTimeMessage::new(
+            DigTime::new(frames, "DIG".to_string()),
+            game.history().clone(),
+        );
+
+        assert!(matches!(
+            message.time(),
+            Err(DigValidationError::WrongGarbage)
+        ));
+    }
+
+    #[test]
+    fn faster_times_are_ordered_first() {
+        let mut times = vec![
+            DigTime::new(900, "SLO".to_string()),
+            DigTime::new(300, "FST".to_string()),
+        ];
+        times.sort();
+
+        assert_eq!(times[0].name, "FST");
+    }
+}
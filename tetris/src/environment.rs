@@ -0,0 +1,534 @@
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use crate::board::Board;
+use crate::config::GameConfig;
+use crate::controller::Controller;
+use crate::controller::Player;
+use crate::game::Game;
+use crate::game::History;
+use crate::game::StepResult;
+use crate::placement::Placement;
+use crate::shape::Shape;
+use crate::shape::ShapeColor;
+
+/// The number of cells on the board, including the hidden rows at the top.
+const NUM_CELLS: usize = Board::WIDTH as usize * Board::HEIGHT as usize;
+
+/// The number of shapes a piece can have, for one-hot encoding shapes.
+const NUM_SHAPES: usize = 7;
+
+/// The number of rotations a piece can have.
+const NUM_ROTATIONS: usize = 4;
+
+/// A reinforcement learning environment, in the style of OpenAI Gym, that plays the game with the
+/// same engine as a human player.
+///
+/// Each episode is one game, started with `reset`. Agents then call `step` with an action, and get
+/// back what they can see of the game, a reward, whether the game is over and extra information.
+/// The reward for a step is the score gained during it.
+///
+/// ```
+/// use tetris::ActionSpace;
+/// use tetris::Environment;
+/// use tetris::GameConfig;
+///
+/// let mut env = Environment::new(GameConfig::default(), ActionSpace::Placement);
+/// let (mut observation, mut info) = env.reset([1, 2, 3, 4]);
+///
+/// loop {
+///     // Pick the first legal action, a real agent would look at the observation
+///     let action = info.action_mask.iter().position(|&legal| legal).unwrap();
+///     let (next_observation, reward, done, next_info) = env.step(action).unwrap();
+///     observation = next_observation;
+///     info = next_info;
+///     if done {
+///         break;
+///     }
+/// }
+///
+/// assert_eq!(observation.to_vec().len(), tetris::Observation::SIZE);
+/// ```
+pub struct Environment {
+    config: GameConfig,
+    action_space: ActionSpace,
+
+    /// The game in the current episode, or `None` before the first reset.
+    game: Option<Game>,
+
+    /// Whether the current episode's game is over.
+    done: bool,
+}
+
+/// The actions an agent chooses between on each step, identified by their index.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ActionSpace {
+    /// Each action places the current piece and plays until it locks.
+    ///
+    /// Action `rotation * Board::WIDTH + column` places the piece rotated clockwise `rotation`
+    /// times, with its leftmost block in `column`. If the piece can rest in more than one place
+    /// like that, such as under an overhang, it's placed in the highest, which is where a hard drop
+    /// would land. Some actions can't be played, which `Info::action_mask` shows.
+    Placement,
+
+    /// Each action presses one key, or none, then advances the game one frame.
+    ///
+    /// Action `i` is `FrameAction::ALL[i]`.
+    Frame,
+}
+
+/// A key press in the `ActionSpace::Frame` action space.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum FrameAction {
+    /// Don't press anything
+    Nothing,
+    /// Move the piece to the left
+    MoveLeft,
+    /// Move the piece to the right
+    MoveRight,
+    /// Rotate the piece clockwise
+    Rotate,
+    /// Start a fast soft drop
+    StartSoftDrop,
+    /// Immediately drop and lock the piece
+    StartHardDrop,
+    /// Stop a soft or hard drop
+    StopDrop,
+}
+
+/// What an agent can see of the game, encoded as numbers that can be fed to a neural network.
+///
+/// Cells are listed row by row from the top of the board, including the hidden rows pieces spawn
+/// in. Shapes are one-hot encoded in the order O, I, J, L, S, T, Z.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Observation {
+    /// For every cell, `1.0` if it's filled and `0.0` if it's empty.
+    pub board: Vec<f32>,
+
+    /// For every cell, `1.0` if the falling piece covers it and `0.0` otherwise.
+    pub piece: Vec<f32>,
+
+    /// The shape of the falling piece.
+    pub shape: [f32; NUM_SHAPES],
+
+    /// The shape of the next piece, from the preview.
+    pub next_shape: [f32; NUM_SHAPES],
+}
+
+/// Extra information about the game after a reset or step.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Info {
+    /// The number of lines cleared during the step.
+    pub lines_cleared: u32,
+
+    /// The score of the game so far.
+    pub score: u32,
+
+    /// The number of pieces placed in the game so far.
+    pub pieces_placed: u32,
+
+    /// The number of frames played in the game so far.
+    pub frames: u32,
+
+    /// Whether each action in the action space can be played next.
+    pub action_mask: Vec<bool>,
+}
+
+/// An error from stepping an `Environment`.
+#[derive(Debug, Eq, PartialEq)]
+pub enum EnvironmentError {
+    /// The environment must be reset before the first step
+    NotReset,
+    /// The game is over, so the environment must be reset before stepping again
+    GameOver,
+    /// The action isn't in the action space, or can't be played now
+    InvalidAction(usize),
+}
+
+impl Error for EnvironmentError {}
+
+impl Display for EnvironmentError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvironmentError::NotReset => write!(f, "The environment hasn't been reset"),
+            EnvironmentError::GameOver => write!(f, "The game is over"),
+            EnvironmentError::InvalidAction(action) => {
+                write!(f, "Action {} can't be played", action)
+            }
+        }
+    }
+}
+
+impl Environment {
+    /// Create an environment that plays games with the given rules and action space.
+    ///
+    /// The environment must be `reset` before the first step.
+    pub fn new(config: GameConfig, action_space: ActionSpace) -> Self {
+        Environment {
+            config,
+            action_space,
+            game: None,
+            done: false,
+        }
+    }
+
+    /// Get the environment's action space.
+    pub fn action_space(&self) -> ActionSpace {
+        self.action_space
+    }
+
+    /// Start a new game from the given seed, returning what the agent can see of it.
+    pub fn reset(&mut self, seed: [u32; 4]) -> (Observation, Info) {
+        let game = Game::with_config(seed, self.config);
+        self.done = false;
+        let result = (Observation::of(&game), self.info(&game, 0));
+        self.game = Some(game);
+        result
+    }
+
+    /// Play the given action, returning what the agent can see of the game, the reward, whether
+    /// the game is over and extra information.
+    pub fn step(
+        &mut self,
+        action: usize,
+    ) -> Result<(Observation, f64, bool, Info), EnvironmentError> {
+        let action_space = self.action_space;
+        let game = self.game.as_mut().ok_or(EnvironmentError::NotReset)?;
+
+        if self.done {
+            return Err(EnvironmentError::GameOver);
+        }
+
+        let (score, lines) = (game.score(), game.lines_cleared());
+
+        let step_result = match action_space {
+            ActionSpace::Placement => {
+                let placement = placement_actions(game)
+                    .into_iter()
+                    .nth(action)
+                    .flatten()
+                    .ok_or(EnvironmentError::InvalidAction(action))?;
+                place(game, placement)
+            }
+            ActionSpace::Frame => {
+                let frame_action = FrameAction::ALL
+                    .get(action)
+                    .ok_or(EnvironmentError::InvalidAction(action))?;
+                frame_action.press(game);
+                game.step()
+            }
+        };
+
+        self.done = step_result == StepResult::GameOver;
+
+        let game = self.game.as_ref().expect("the environment was reset");
+        let reward = f64::from(game.score() - score);
+        let info = self.info(game, game.lines_cleared() - lines);
+
+        Ok((Observation::of(game), reward, self.done, info))
+    }
+
+    /// Get the history of the current episode's game, so it can be replayed.
+    pub fn history(&self) -> Option<&History> {
+        self.game.as_ref().map(Game::history)
+    }
+
+    fn info(&self, game: &Game, lines_cleared: u32) -> Info {
+        let action_mask = match self.action_space {
+            ActionSpace::Placement => placement_actions(game)
+                .iter()
+                .map(|placement| !self.done && placement.is_some())
+                .collect(),
+            ActionSpace::Frame => vec![!self.done; self.action_space.num_actions()],
+        };
+
+        Info {
+            lines_cleared,
+            score: game.score(),
+            pieces_placed: game.pieces_placed(),
+            frames: game.statistics().frames(),
+            action_mask,
+        }
+    }
+}
+
+impl ActionSpace {
+    /// Get the number of actions in the action space.
+    pub fn num_actions(self) -> usize {
+        match self {
+            ActionSpace::Placement => NUM_ROTATIONS * Board::WIDTH as usize,
+            ActionSpace::Frame => FrameAction::ALL.len(),
+        }
+    }
+}
+
+impl FrameAction {
+    /// Every frame action, in the order of their index in the action space.
+    pub const ALL: [FrameAction; 7] = [
+        FrameAction::Nothing,
+        FrameAction::MoveLeft,
+        FrameAction::MoveRight,
+        FrameAction::Rotate,
+        FrameAction::StartSoftDrop,
+        FrameAction::StartHardDrop,
+        FrameAction::StopDrop,
+    ];
+
+    fn press(self, game: &mut Game) {
+        match self {
+            FrameAction::Nothing => {}
+            FrameAction::MoveLeft => game.move_left(),
+            FrameAction::MoveRight => game.move_right(),
+            FrameAction::Rotate => game.rotate(),
+            FrameAction::StartSoftDrop => game.start_soft_drop(),
+            FrameAction::StartHardDrop => game.start_hard_drop(),
+            FrameAction::StopDrop => game.stop_drop(),
+        }
+    }
+}
+
+impl Observation {
+    /// The number of values in an observation.
+    pub const SIZE: usize = NUM_CELLS * 2 + NUM_SHAPES * 2;
+
+    /// Get what an agent can see of the game.
+    fn of(game: &Game) -> Self {
+        let board = game
+            .board()
+            .grid()
+            .iter()
+            .flat_map(|row| row.iter())
+            .map(|cell| if cell.is_some() { 1.0 } else { 0.0 })
+            .collect();
+
+        let mut piece = vec![0.0; NUM_CELLS];
+        for pos in game.piece().blocks() {
+            let in_bounds = (0..i16::from(Board::WIDTH)).contains(&pos.x())
+                && (0..i16::from(Board::HEIGHT)).contains(&pos.y());
+            if in_bounds {
+                piece[pos.y() as usize * Board::WIDTH as usize + pos.x() as usize] = 1.0;
+            }
+        }
+
+        Observation {
+            board,
+            piece,
+            shape: one_hot(game.piece().shape),
+            next_shape: one_hot(game.next_shape()),
+        }
+    }
+
+    /// Flatten the observation into one list of `Observation::SIZE` values: the board, the
+    /// piece, the shape and then the next shape.
+    pub fn to_vec(&self) -> Vec<f32> {
+        let mut values = Vec::with_capacity(Observation::SIZE);
+        values.extend_from_slice(&self.board);
+        values.extend_from_slice(&self.piece);
+        values.extend_from_slice(&self.shape);
+        values.extend_from_slice(&self.next_shape);
+        values
+    }
+}
+
+/// One-hot encode a shape.
+fn one_hot(shape: Shape) -> [f32; NUM_SHAPES] {
+    let index = match shape.color {
+        ShapeColor::O => 0,
+        ShapeColor::I => 1,
+        ShapeColor::J => 2,
+        ShapeColor::L => 3,
+        ShapeColor::S => 4,
+        ShapeColor::T => 5,
+        ShapeColor::Z | ShapeColor::Garbage => 6,
+    };
+
+    let mut encoded = [0.0; NUM_SHAPES];
+    encoded[index] = 1.0;
+    encoded
+}
+
+/// Find the placement for every action in the `ActionSpace::Placement` action space, or `None`
+/// for actions that can't be played.
+fn placement_actions(game: &Game) -> Vec<Option<Placement>> {
+    let width = Board::WIDTH as usize;
+    let mut actions: Vec<Option<Placement>> = vec![None; NUM_ROTATIONS * width];
+
+    for placement in Placement::find_all_from(game.board(), game.piece().clone()) {
+        let mut rot = game.piece().rot;
+        let mut rotations = 0;
+        while rot != placement.piece.rot {
+            rot = rot.clockwise();
+            rotations += 1;
+        }
+
+        // A resting piece is inside the board, so its leftmost block is in a column
+        let (top_left, _) = placement.piece.bounding_box();
+        let action = &mut actions[rotations * width + top_left.x() as usize];
+
+        let is_higher = action.as_ref().map_or(true, |other| {
+            top_left.y() < other.piece.bounding_box().0.y()
+        });
+        if is_higher {
+            *action = Some(placement);
+        }
+    }
+
+    actions
+}
+
+/// Play the placement, pressing keys each frame until the piece locks.
+fn place(game: &mut Game, placement: Placement) -> StepResult {
+    let pieces_placed = game.pieces_placed();
+    let mut player = Player::new(Chosen(Some(placement)));
+
+    loop {
+        player.play(game);
+        if game.step() == StepResult::GameOver {
+            return StepResult::GameOver;
+        }
+        if game.pieces_placed() > pieces_placed {
+            return StepResult::Continue;
+        }
+    }
+}
+
+/// A controller for a placement that has already been chosen.
+struct Chosen(Option<Placement>);
+
+impl Controller for Chosen {
+    fn choose(&mut self, _: &Game) -> Option<Placement> {
+        self.0.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED: [u32; 4] = [1, 2, 3, 4];
+
+    fn first_legal(info: &Info) -> usize {
+        info.action_mask.iter().position(|&legal| legal).unwrap()
+    }
+
+    #[test]
+    fn a_reset_game_has_an_empty_board() {
+        let mut env = Environment::new(GameConfig::default(), ActionSpace::Placement);
+
+        let (observation, info) = env.reset(SEED);
+
+        assert_eq!(observation.to_vec().len(), Observation::SIZE);
+        assert!(observation.board.iter().all(|&cell| cell == 0.0));
+        assert_eq!(observation.piece.iter().sum::<f32>(), 4.0);
+        assert_eq!(observation.shape.iter().sum::<f32>(), 1.0);
+        assert_eq!(info.score, 0);
+        assert_eq!(info.action_mask.len(), ActionSpace::Placement.num_actions());
+    }
+
+    #[test]
+    fn stepping_before_a_reset_is_an_error() {
+        let mut env = Environment::new(GameConfig::default(), ActionSpace::Frame);
+
+        assert_eq!(env.step(0), Err(EnvironmentError::NotReset));
+    }
+
+    #[test]
+    fn a_placement_action_locks_one_piece() {
+        let mut env = Environment::new(GameConfig::default(), ActionSpace::Placement);
+        let (_, info) = env.reset(SEED);
+
+        let (observation, _, done, info) = env.step(first_legal(&info)).unwrap();
+
+        assert!(!done);
+        assert_eq!(info.pieces_placed, 1);
+        assert_eq!(observation.board.iter().sum::<f32>(), 4.0);
+    }
+
+    #[test]
+    fn an_illegal_placement_is_an_error() {
+        let mut env = Environment::new(GameConfig::default(), ActionSpace::Placement);
+        let (_, info) = env.reset(SEED);
+        let illegal = info.action_mask.iter().position(|&legal| !legal).unwrap();
+
+        assert_eq!(
+            env.step(illegal),
+            Err(EnvironmentError::InvalidAction(illegal))
+        );
+        assert_eq!(
+            env.step(ActionSpace::Placement.num_actions()),
+            Err(EnvironmentError::InvalidAction(
+                ActionSpace::Placement.num_actions()
+            ))
+        );
+    }
+
+    #[test]
+    fn a_frame_action_advances_one_frame() {
+        let mut env = Environment::new(GameConfig::default(), ActionSpace::Frame);
+        let (before, _) = env.reset(SEED);
+
+        let left = FrameAction::ALL
+            .iter()
+            .position(|&action| action == FrameAction::MoveLeft)
+            .unwrap();
+        let (after, reward, done, info) = env.step(left).unwrap();
+
+        assert_eq!(info.frames, 1);
+        assert_eq!(reward, 0.0);
+        assert!(!done);
+        assert_ne!(before.piece, after.piece);
+    }
+
+    #[test]
+    fn the_reward_is_the_score_gained() {
+        let mut env = Environment::new(GameConfig::default(), ActionSpace::Placement);
+        let (_, mut info) = env.reset(SEED);
+        let mut total_reward = 0.0;
+
+        for _ in 0..20 {
+            let (_, reward, done, next_info) = env.step(first_legal(&info)).unwrap();
+            total_reward += reward;
+            info = next_info;
+            if done {
+                break;
+            }
+        }
+
+        assert_eq!(total_reward, f64::from(info.score));
+    }
+
+    #[test]
+    fn stepping_after_a_game_over_is_an_error() {
+        let mut env = Environment::new(GameConfig::default(), ActionSpace::Placement);
+        let (_, mut info) = env.reset(SEED);
+
+        // Stacking every piece on the left soon tops out
+        loop {
+            let (_, _, done, next_info) = env.step(first_legal(&info)).unwrap();
+            info = next_info;
+            if done {
+                break;
+            }
+        }
+
+        assert!(info.action_mask.iter().all(|&legal| !legal));
+        assert_eq!(env.step(0), Err(EnvironmentError::GameOver));
+        assert_eq!(env.history().unwrap().replay(), info.score);
+
+        let (_, info) = env.reset(SEED);
+        assert!(env.step(first_legal(&info)).is_ok());
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_episode() {
+        let mut first = Environment::new(GameConfig::default(), ActionSpace::Placement);
+        let mut second = Environment::new(GameConfig::default(), ActionSpace::Placement);
+        let (observation, info) = first.reset(SEED);
+        assert_eq!(second.reset(SEED), (observation, info.clone()));
+
+        let action = first_legal(&info);
+        assert_eq!(first.step(action), second.step(action));
+    }
+}
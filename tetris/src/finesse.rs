@@ -0,0 +1,279 @@
+use std::collections::HashSet;
+use std::collections::VecDeque;
+
+use crate::board::Board;
+use crate::piece::Piece;
+use crate::placement::apply_move;
+use crate::placement::hard_drop;
+use crate::placement::sorted_blocks;
+use crate::placement::Move;
+use crate::pos::Pos;
+use crate::shape::Rotation;
+
+/// Tracks the player's finesse: whether each piece was placed using as few inputs as possible.
+///
+/// Only moves and rotations count as inputs. Pieces that can't be placed by moving and rotating
+/// at the top of the board then hard dropping, such as tucks and spins, are not analysed.
+///
+/// Finding the fewest inputs for a piece means searching every way to place it, so pieces are
+/// only analysed in games that show the finesse to the player. Replaying or verifying a game, or
+/// letting a bot play it, never pays for the search.
+#[derive(Clone, Default, Debug)]
+pub struct Finesse {
+    /// Whether pieces are analysed as they lock.
+    enabled: bool,
+
+    /// The number of inputs used on the current piece so far.
+    inputs: u32,
+
+    /// The number of pieces that have been analysed.
+    pieces_analysed: u32,
+
+    /// The number of pieces placed with more inputs than needed.
+    faults: u32,
+
+    /// The total number of inputs used beyond the minimum.
+    extra_inputs: u32,
+}
+
+impl Finesse {
+    /// Get the number of pieces placed with more inputs than needed.
+    pub fn faults(&self) -> u32 {
+        self.faults
+    }
+
+    /// Get the total number of inputs used beyond the minimum needed.
+    pub fn extra_inputs(&self) -> u32 {
+        self.extra_inputs
+    }
+
+    /// Get the number of pieces that have been analysed.
+    pub fn pieces_analysed(&self) -> u32 {
+        self.pieces_analysed
+    }
+
+    /// Start analysing the pieces that lock from now on.
+    pub(crate) fn enable(&mut self) {
+        self.enabled = true;
+    }
+
+    /// Record that the player moved or rotated the current piece.
+    pub(crate) fn record_input(&mut self) {
+        self.inputs += 1;
+    }
+
+    /// Record that the current piece locked, comparing the inputs used with the minimum needed.
+    ///
+    /// The board should be the board before the piece locked.
+    pub(crate) fn record_lock(&mut self, board: &Board, piece: &Piece) {
+        let inputs = self.inputs;
+        self.inputs = 0;
+
+        if !self.enabled {
+            return;
+        }
+
+        if let Some(minimum) = minimum_inputs(board, piece) {
+            self.pieces_analysed += 1;
+
+            if inputs > minimum {
+                self.faults += 1;
+                self.extra_inputs += inputs - minimum;
+            }
+        }
+    }
+}
+
+/// Find the fewest moves and rotations to get a new piece to the given piece's position, by moving
+/// the new piece at the top of the board then hard dropping it.
+///
+/// Returns `None` if the position can't be reached with a hard drop, e.g. for a tuck or spin.
+fn minimum_inputs(board: &Board, target: &Piece) -> Option<u32> {
+    let target_blocks = sorted_blocks(target);
+
+    let spawn = Piece::new(target.shape);
+    if board.overlaps(&spawn) {
+        return None;
+    }
+
+    let mut visited: HashSet<(Pos, Rotation)> = HashSet::new();
+    visited.insert((spawn.pos, spawn.rot));
+    let mut queue = VecDeque::new();
+    queue.push_back((spawn, 0));
+
+    while let Some((piece, inputs)) = queue.pop_front() {
+        if sorted_blocks(&hard_drop(board, &piece)) == target_blocks {
+            return Some(inputs);
+        }
+
+        let moves = [
+            apply_move(board, &piece, Move::Left),
+            apply_move(board, &piece, Move::Right),
+            apply_move(board, &piece, Move::Rotate),
+        ];
+
+        for next in moves.iter().flatten() {
+            if visited.insert((next.pos, next.rot)) {
+                queue.push_back((next.clone(), inputs + 1));
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::Game;
+    use crate::shape::Shape;
+    use crate::shape::ShapeColor;
+    use crate::state::State;
+
+    use super::*;
+
+    fn analysed() -> Finesse {
+        let mut finesse = Finesse::default();
+        finesse.enable();
+        finesse
+    }
+
+    fn piece_on(board: &str) -> (Board, Piece) {
+        let (board, piece) = Board::parse_with_piece(board).unwrap();
+        (board, piece.unwrap())
+    }
+
+    #[test]
+    fn dropping_a_piece_where_it_spawns_needs_no_inputs() {
+        let board = Board::default();
+        let piece = hard_drop(
+            &board,
+            &Piece::new(Shape::from_color(ShapeColor::T).unwrap()),
+        );
+
+        assert_eq!(minimum_inputs(&board, &piece), Some(0));
+    }
+
+    #[test]
+    fn moving_a_piece_to_the_wall_needs_one_input_per_column() {
+        let (board, piece) = piece_on(
+            "
+            oo........
+            oo........
+            ",
+        );
+
+        // The O spawns in columns 4 and 5
+        assert_eq!(minimum_inputs(&board, &piece), Some(4));
+    }
+
+    #[test]
+    fn a_tuck_is_not_analysed() {
+        let (board, piece) = piece_on(
+            "
+            XXXX......
+            ...oo.....
+            ...oo.....
+            ",
+        );
+
+        assert_eq!(minimum_inputs(&board, &piece), None);
+    }
+
+    #[test]
+    fn placing_a_piece_with_extra_inputs_is_a_fault() {
+        let (board, piece) = piece_on(
+            "
+            oo........
+            oo........
+            ",
+        );
+
+        let mut finesse = analysed();
+        for _ in 0..6 {
+            finesse.record_input();
+        }
+        finesse.record_lock(&board, &piece);
+
+        assert_eq!(finesse.pieces_analysed(), 1);
+        assert_eq!(finesse.faults(), 1);
+        assert_eq!(finesse.extra_inputs(), 2);
+    }
+
+    #[test]
+    fn placing_a_piece_with_the_fewest_inputs_is_not_a_fault() {
+        let (board, piece) = piece_on(
+            "
+            oo........
+            oo........
+            ",
+        );
+
+        let mut finesse = analysed();
+        for _ in 0..4 {
+            finesse.record_input();
+        }
+        finesse.record_lock(&board, &piece);
+
+        assert_eq!(finesse.faults(), 0);
+    }
+
+    #[test]
+    fn moving_a_piece_back_and_forth_in_a_game_is_a_fault() {
+        let mut game = Game::default();
+        game.analyse_finesse();
+        game.move_left();
+        game.move_right();
+        game.start_hard_drop();
+
+        let mut state = State::Play(game);
+        let game = loop {
+            state = state.update();
+            match state {
+                State::Play(ref game) if game.pieces_placed() > 0 => break game,
+                State::Play(_) => {}
+                _ => panic!("expected game to continue"),
+            }
+        };
+
+        assert_eq!(game.statistics().finesse().faults(), 1);
+        assert_eq!(game.statistics().finesse().extra_inputs(), 2);
+    }
+
+    #[test]
+    fn pieces_are_not_analysed_unless_finesse_is_enabled() {
+        let (board, piece) = piece_on(
+            "
+            oo........
+            oo........
+            ",
+        );
+
+        let mut finesse = Finesse::default();
+        finesse.record_lock(&board, &piece);
+
+        assert_eq!(finesse.pieces_analysed(), 0);
+    }
+
+    #[test]
+    fn inputs_are_counted_separately_for_each_piece() {
+        let (board, piece) = piece_on(
+            "
+            oo........
+            oo........
+            ",
+        );
+
+        let mut finesse = analysed();
+        for _ in 0..4 {
+            finesse.record_input();
+        }
+        finesse.record_lock(&board, &piece);
+        for _ in 0..4 {
+            finesse.record_input();
+        }
+        finesse.record_lock(&board, &piece);
+
+        assert_eq!(finesse.pieces_analysed(), 2);
+        assert_eq!(finesse.faults(), 0);
+    }
+}
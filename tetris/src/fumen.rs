@@ -0,0 +1,1007 @@
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::str::FromStr;
+
+use crate::board::Board;
+use crate::game::History;
+use crate::game::StepResult;
+use crate::piece::Piece;
+use crate::pos::Pos;
+use crate::replay::ReplayPlayer;
+use crate::shape::Shape;
+use crate::shape::ShapeColor;
+
+/// The version of fumen we read and write, written before the data.
+const VERSION_PREFIX: &str = "v115@";
+
+/// The characters used to encode values, where each character is a digit in base 64.
+const ENCODE_TABLE: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// The number of values a single character can encode.
+const TABLE_LENGTH: u32 = 64;
+
+/// Width of a fumen field in cells.
+const FIELD_WIDTH: i16 = 10;
+
+/// Number of rows in a fumen field, not counting the garbage row underneath.
+const FIELD_TOP: i16 = 23;
+
+/// Number of cells in a fumen field, including the garbage row underneath.
+const FIELD_BLOCKS: usize = ((FIELD_TOP + 1) * FIELD_WIDTH) as usize;
+
+/// The value added to the difference between two cells, so it's never negative.
+const DIFF_OFFSET: u32 = 8;
+
+/// Number of characters used to encode each part of a page.
+const FIELD_RUN_CHARS: usize = 2;
+const ACTION_CHARS: usize = 3;
+const COMMENT_LENGTH_CHARS: usize = 2;
+const COMMENT_BLOCK_CHARS: usize = 5;
+
+/// After this many characters, and every `SPLIT_LENGTH` after that, a `?` is inserted so the data
+/// can be broken over lines.
+const FIRST_SPLIT_LENGTH: usize = 42;
+const SPLIT_LENGTH: usize = 47;
+
+/// The cell value for garbage blocks. Values 1 to 7 are tetromino colours.
+const GARBAGE: u8 = 8;
+
+/// A sequence of boards and pieces in the fumen format, used to share Tetris positions.
+///
+/// A fumen can be written as a string like `v115@vhAAgH` with `Display`, and read from a string
+/// with `FromStr`, which also accepts a full fumen URL.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Fumen {
+    pages: Vec<FumenPage>,
+}
+
+/// A single page of a fumen, showing a board and optionally the piece being placed.
+#[derive(Clone, PartialEq, Debug)]
+pub struct FumenPage {
+    /// The board, made up of blocks from old pieces.
+    pub board: Board,
+
+    /// The piece being placed on this page.
+    pub piece: Option<Piece>,
+}
+
+/// Potential errors from reading a fumen.
+#[derive(Debug, PartialEq)]
+pub enum FumenError {
+    /// The data doesn't contain a supported fumen version, such as `v115@`
+    UnknownVersion,
+    /// The data contains a character that isn't used in fumens
+    InvalidCharacter(char),
+    /// The data ended before a page was complete
+    UnexpectedEnd,
+    /// A cell in a field has a value that isn't a block
+    InvalidField,
+    /// A piece has an unknown type or doesn't fit on the board
+    InvalidPiece,
+}
+
+impl Error for FumenError {}
+
+impl Display for FumenError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            FumenError::UnknownVersion => write!(f, "Fumen must start with {}", VERSION_PREFIX),
+            FumenError::InvalidCharacter(c) => write!(f, "Invalid fumen character {:?}", c),
+            FumenError::UnexpectedEnd => write!(f, "Fumen ended unexpectedly"),
+            FumenError::InvalidField => write!(f, "Fumen contains an invalid block"),
+            FumenError::InvalidPiece => write!(f, "Fumen contains an invalid piece"),
+        }
+    }
+}
+
+impl Fumen {
+    /// Create a fumen with a single page.
+    pub fn new(board: Board, piece: Option<Piece>) -> Self {
+        Fumen::from_pages(vec![FumenPage { board, piece }])
+    }
+
+    /// Create a fumen from a sequence of pages.
+    pub fn from_pages(pages: Vec<FumenPage>) -> Self {
+        Fumen { pages }
+    }
+
+    /// Create a fumen from a recorded game, with a page for every piece placed.
+    ///
+    /// Each page shows the board before the piece locked, with the piece where it locked.
+    pub fn from_history(history: &History) -> Self {
+        let mut player = ReplayPlayer::new(history.clone());
+        let mut pages = Vec::new();
+
+        loop {
+            let board = player.board().clone();
+            let step_result = player.step();
+
+            if let Some(lock) = player.last_lock() {
+                pages.push(FumenPage {
+                    board,
+                    piece: Some(lock.piece.clone()),
+                });
+            }
+
+            if step_result == StepResult::GameOver {
+                break;
+            }
+        }
+
+        Fumen::from_pages(pages)
+    }
+
+    /// Get the pages of the fumen, in order.
+    pub fn pages(&self) -> &[FumenPage] {
+        &self.pages
+    }
+}
+
+impl Display for Fumen {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut values = Vec::new();
+        let mut prev_field = Field::default();
+
+        // Index in `values` of the count of repeated unchanged fields, if the last field was
+        // unchanged
+        let mut repeat_index: Option<usize> = None;
+
+        for (index, page) in self.pages.iter().enumerate() {
+            let field = Field::from_board(&page.board);
+            let field_values = field.encode_diff(&prev_field);
+
+            if field_values.is_some() {
+                repeat_index = None;
+            }
+
+            match (field_values, repeat_index) {
+                (Some(field_values), _) => values.extend(field_values),
+                (None, Some(i)) if values[i] < TABLE_LENGTH - 1 => values[i] += 1,
+                (None, _) => {
+                    values.extend(field.encode_diff_unchanged());
+                    values.push(0);
+                    repeat_index = Some(values.len() - 1);
+                }
+            }
+
+            let action = Action {
+                piece: page.piece.as_ref().and_then(FumenPiece::from_piece),
+                rise: false,
+                mirror: false,
+                colorize: index == 0,
+                comment: false,
+                lock: true,
+            };
+            push_value(&mut values, action.encode(), ACTION_CHARS);
+
+            prev_field = field;
+            prev_field.apply(&action);
+        }
+
+        let data: String = values
+            .iter()
+            .map(|&value| ENCODE_TABLE[value as usize] as char)
+            .collect();
+
+        write!(f, "{}", VERSION_PREFIX)?;
+
+        if data.len() <= FIRST_SPLIT_LENGTH {
+            return write!(f, "{}", data);
+        }
+
+        let (head, tail) = data.split_at(FIRST_SPLIT_LENGTH);
+        write!(f, "{}", head)?;
+
+        for chunk in tail.as_bytes().chunks(SPLIT_LENGTH) {
+            write!(f, "?{}", String::from_utf8_lossy(chunk))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Fumen {
+    type Err = FumenError;
+
+    /// Read a fumen from a string such as `v115@vhAAgH`, or a URL containing one.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let version = &VERSION_PREFIX[1..];
+        let start = s.find(version).ok_or(FumenError::UnknownVersion)? + version.len();
+
+        let mut values = Values::new(&s[start..])?;
+        let mut pages = Vec::new();
+        let mut prev_field = Field::default();
+        let mut repeat_count = 0;
+
+        while !values.is_empty() {
+            let field = if repeat_count > 0 {
+                repeat_count -= 1;
+                prev_field.clone()
+            } else {
+                let (field, changed) = prev_field.decode_diff(&mut values)?;
+                if !changed {
+                    repeat_count = values.poll(1)?;
+                }
+                field
+            };
+
+            let action = Action::decode(values.poll(ACTION_CHARS)?)?;
+
+            if action.comment {
+                let length = values.poll(COMMENT_LENGTH_CHARS)? as usize;
+                values.skip((length + 3) / 4 * COMMENT_BLOCK_CHARS)?;
+            }
+
+            let piece = match action.piece {
+                Some(fumen_piece) => Some(fumen_piece.to_piece()?),
+                None => None,
+            };
+
+            pages.push(FumenPage {
+                board: field.to_board()?,
+                piece,
+            });
+
+            prev_field = field;
+            prev_field.apply(&action);
+        }
+
+        if pages.is_empty() {
+            return Err(FumenError::UnexpectedEnd);
+        }
+
+        Ok(Fumen { pages })
+    }
+}
+
+/// Append a value as the given number of base 64 digits, least significant first.
+fn push_value(values: &mut Vec<u32>, mut value: u32, chars: usize) {
+    for _ in 0..chars {
+        values.push(value % TABLE_LENGTH);
+        value /= TABLE_LENGTH;
+    }
+}
+
+/// Reads values from fumen data.
+struct Values {
+    digits: Vec<u32>,
+    index: usize,
+}
+
+impl Values {
+    /// Read the digits of fumen data, ignoring `?` separators and whitespace.
+    fn new(data: &str) -> Result<Self, FumenError> {
+        let digits = data
+            .chars()
+            .filter(|&c| c != '?' && !c.is_whitespace())
+            .map(|c| {
+                ENCODE_TABLE
+                    .iter()
+                    .position(|&e| e as char == c)
+                    .map(|position| position as u32)
+                    .ok_or(FumenError::InvalidCharacter(c))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Values { digits, index: 0 })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.index >= self.digits.len()
+    }
+
+    /// Read a value from the given number of base 64 digits, least significant first.
+    fn poll(&mut self, chars: usize) -> Result<u32, FumenError> {
+        let digits = self
+            .digits
+            .get(self.index..self.index + chars)
+            .ok_or(FumenError::UnexpectedEnd)?;
+
+        self.index += chars;
+
+        Ok(digits
+            .iter()
+            .rev()
+            .fold(0, |value, &d| value * TABLE_LENGTH + d))
+    }
+
+    fn skip(&mut self, chars: usize) -> Result<(), FumenError> {
+        if self.index + chars > self.digits.len() {
+            return Err(FumenError::UnexpectedEnd);
+        }
+        self.index += chars;
+        Ok(())
+    }
+}
+
+/// A fumen field, with a cell for every block including the garbage row under the field.
+///
+/// Cells are stored from the top row down, and rows are numbered from the bottom, with the garbage
+/// row at `y = -1`.
+#[derive(Clone)]
+struct Field {
+    cells: [u8; FIELD_BLOCKS],
+}
+
+impl Default for Field {
+    fn default() -> Self {
+        Field {
+            cells: [0; FIELD_BLOCKS],
+        }
+    }
+}
+
+impl Field {
+    /// Convert a board into a field.
+    ///
+    /// The fumen field is one row shorter than the board, so the top hidden row is left out.
+    fn from_board(board: &Board) -> Self {
+        let mut field = Field::default();
+
+        for (y, row) in board.grid().iter().enumerate().skip(1) {
+            for (x, cell) in row.iter().enumerate() {
+                if let Some(color) = *cell {
+                    field.set(
+                        Pos::new(x as i16, board_to_field_y(y as i16)),
+                        color_value(color),
+                    );
+                }
+            }
+        }
+
+        field
+    }
+
+    /// Convert the field to a board, ignoring the garbage row.
+    fn to_board(&self) -> Result<Board, FumenError> {
+        let mut board = Board::default();
+
+        for y in 0..FIELD_TOP {
+            for x in 0..FIELD_WIDTH {
+                let pos = Pos::new(x, y);
+                if let Some(color) = value_color(self.get(pos))? {
+                    board.fill_pos(Pos::new(x, field_to_board_y(y)), color);
+                }
+            }
+        }
+
+        Ok(board)
+    }
+
+    fn index(pos: Pos) -> usize {
+        ((FIELD_TOP - 1 - pos.y()) * FIELD_WIDTH + pos.x()) as usize
+    }
+
+    fn get(&self, pos: Pos) -> u8 {
+        self.cells[Field::index(pos)]
+    }
+
+    fn set(&mut self, pos: Pos, value: u8) {
+        self.cells[Field::index(pos)] = value;
+    }
+
+    /// Encode the difference from the previous field as runs of equal differences.
+    ///
+    /// Returns `None` if the fields are the same.
+    fn encode_diff(&self, prev: &Field) -> Option<Vec<u32>> {
+        let diffs: Vec<u32> = self
+            .cells
+            .iter()
+            .zip(prev.cells.iter())
+            .map(|(&cell, &prev)| u32::from(cell) + DIFF_OFFSET - u32::from(prev))
+            .collect();
+
+        if diffs.iter().all(|&diff| diff == DIFF_OFFSET) {
+            return None;
+        }
+
+        let mut values = Vec::new();
+        let mut run_start = 0;
+
+        for index in 1..=diffs.len() {
+            if index == diffs.len() || diffs[index] != diffs[run_start] {
+                let run = (index - run_start - 1) as u32;
+                push_value(
+                    &mut values,
+                    diffs[run_start] * FIELD_BLOCKS as u32 + run,
+                    FIELD_RUN_CHARS,
+                );
+                run_start = index;
+            }
+        }
+
+        Some(values)
+    }
+
+    /// Encode a field that is the same as the previous field.
+    fn encode_diff_unchanged(&self) -> Vec<u32> {
+        let mut values = Vec::new();
+        let unchanged = DIFF_OFFSET * FIELD_BLOCKS as u32 + FIELD_BLOCKS as u32 - 1;
+        push_value(&mut values, unchanged, FIELD_RUN_CHARS);
+        values
+    }
+
+    /// Decode a field from its difference with this field, returning the new field and whether it
+    /// changed.
+    fn decode_diff(&self, values: &mut Values) -> Result<(Field, bool), FumenError> {
+        let mut field = self.clone();
+        let mut changed = true;
+        let mut index = 0;
+
+        while index < FIELD_BLOCKS {
+            let value = values.poll(FIELD_RUN_CHARS)?;
+            let diff = value / FIELD_BLOCKS as u32;
+            let run = (value % FIELD_BLOCKS as u32) as usize + 1;
+
+            if diff == DIFF_OFFSET && run == FIELD_BLOCKS {
+                changed = false;
+            }
+
+            for cell in field.cells.iter_mut().skip(index).take(run) {
+                let new_value = (u32::from(*cell) + diff)
+                    .checked_sub(DIFF_OFFSET)
+                    .filter(|&value| value <= u32::from(GARBAGE))
+                    .ok_or(FumenError::InvalidField)?;
+                *cell = new_value as u8;
+            }
+
+            index += run;
+        }
+
+        if index > FIELD_BLOCKS {
+            return Err(FumenError::InvalidField);
+        }
+
+        Ok((field, changed))
+    }
+
+    /// Update the field after a page, locking the piece and applying any garbage rise or mirror.
+    fn apply(&mut self, action: &Action) {
+        if !action.lock {
+            return;
+        }
+
+        if let Some(piece) = &action.piece {
+            for pos in piece.blocks() {
+                self.set(pos, piece.piece_type);
+            }
+        }
+
+        self.clear_lines();
+
+        if action.rise {
+            self.rise_garbage();
+        }
+
+        if action.mirror {
+            self.mirror();
+        }
+    }
+
+    /// Clear any full rows above the garbage row, moving the rows above them down.
+    fn clear_lines(&mut self) {
+        let width = FIELD_WIDTH as usize;
+        let garbage_start = FIELD_BLOCKS - width;
+
+        let mut rows: Vec<&[u8]> = self.cells[..garbage_start]
+            .chunks(width)
+            .filter(|row| row.contains(&0))
+            .collect();
+
+        let empty_row = [0; FIELD_WIDTH as usize];
+        let cleared = FIELD_TOP as usize - rows.len();
+        let mut new_rows = vec![&empty_row[..]; cleared];
+        new_rows.append(&mut rows);
+
+        let mut cells = [0; FIELD_BLOCKS];
+        for (index, row) in new_rows.iter().enumerate() {
+            cells[index * width..(index + 1) * width].copy_from_slice(row);
+        }
+        cells[garbage_start..].copy_from_slice(&self.cells[garbage_start..]);
+
+        self.cells = cells;
+    }
+
+    /// Push the field up one row, moving the garbage row into the bottom of the field.
+    fn rise_garbage(&mut self) {
+        let width = FIELD_WIDTH as usize;
+        self.cells.copy_within(width.., 0);
+
+        for cell in &mut self.cells[FIELD_BLOCKS - width..] {
+            *cell = 0;
+        }
+    }
+
+    /// Flip the field horizontally, not including the garbage row.
+    fn mirror(&mut self) {
+        let width = FIELD_WIDTH as usize;
+
+        for row in self.cells[..FIELD_BLOCKS - width].chunks_mut(width) {
+            row.reverse();
+        }
+    }
+}
+
+/// Convert a row of the board to a row of a fumen field, which is numbered from the bottom.
+fn board_to_field_y(y: i16) -> i16 {
+    i16::from(Board::HEIGHT) - 1 - y
+}
+
+/// Convert a row of a fumen field to a row of the board.
+fn field_to_board_y(y: i16) -> i16 {
+    i16::from(Board::HEIGHT) - 1 - y
+}
+
+/// Get the fumen value of a block colour.
+fn color_value(color: ShapeColor) -> u8 {
+    match color {
+        ShapeColor::I => 1,
+        ShapeColor::L => 2,
+        ShapeColor::O => 3,
+        ShapeColor::Z => 4,
+        ShapeColor::T => 5,
+        ShapeColor::J => 6,
+        ShapeColor::S => 7,
+        ShapeColor::Garbage => GARBAGE,
+    }
+}
+
+/// Get the block colour of a fumen value, or `None` for an empty cell.
+fn value_color(value: u8) -> Result<Option<ShapeColor>, FumenError> {
+    Ok(Some(match value {
+        0 => return Ok(None),
+        1 => ShapeColor::I,
+        2 => ShapeColor::L,
+        3 => ShapeColor::O,
+        4 => ShapeColor::Z,
+        5 => ShapeColor::T,
+        6 => ShapeColor::J,
+        7 => ShapeColor::S,
+        GARBAGE => ShapeColor::Garbage,
+        _ => return Err(FumenError::InvalidField),
+    }))
+}
+
+/// The rotations of a piece in a fumen, named relative to the spawn orientation.
+const REVERSE: u32 = 0;
+const RIGHT: u32 = 1;
+const SPAWN: u32 = 2;
+const LEFT: u32 = 3;
+
+/// A piece as stored in a fumen, positioned by its centre of rotation in the guideline rotation
+/// system.
+#[derive(Clone, Copy, Debug)]
+struct FumenPiece {
+    piece_type: u8,
+    rotation: u32,
+    centre: Pos,
+}
+
+impl FumenPiece {
+    /// Find the fumen piece with the same blocks as the given piece.
+    ///
+    /// Our rotation system is different, so the piece is matched by the cells it covers rather
+    /// than by its rotation. Returns `None` if the piece isn't a standard tetromino, or if it's
+    /// partly in the top hidden row of the board, which the field doesn't have.
+    fn from_piece(piece: &Piece) -> Option<FumenPiece> {
+        let piece_type = color_value(piece.shape.color);
+        if piece_type == GARBAGE {
+            return None;
+        }
+
+        let mut blocks: Vec<Pos> = piece
+            .blocks()
+            .map(|pos| Pos::new(pos.x(), board_to_field_y(pos.y())))
+            .collect();
+
+        if blocks.iter().any(|pos| pos.y() >= FIELD_TOP) {
+            return None;
+        }
+        blocks.sort_by_key(|pos| (pos.y(), pos.x()));
+
+        for &rotation in &[SPAWN, RIGHT, REVERSE, LEFT] {
+            let mut offsets = piece_offsets(piece_type, rotation);
+            offsets.sort_by_key(|pos| (pos.y(), pos.x()));
+
+            if offsets.len() != blocks.len() {
+                continue;
+            }
+
+            let centre = Pos::new(
+                blocks[0].x() - offsets[0].x(),
+                blocks[0].y() - offsets[0].y(),
+            );
+
+            if offsets
+                .iter()
+                .zip(&blocks)
+                .all(|(&offset, &block)| offset + centre == block)
+            {
+                return Some(FumenPiece {
+                    piece_type,
+                    rotation,
+                    centre,
+                });
+            }
+        }
+
+        None
+    }
+
+    /// Convert to a piece on our board, with the same blocks.
+    fn to_piece(self) -> Result<Piece, FumenError> {
+        let shape = value_color(self.piece_type)?
+            .and_then(Shape::from_color)
+            .ok_or(FumenError::InvalidPiece)?;
+
+        let blocks: Vec<Pos> = self
+            .blocks()
+            .iter()
+            .map(|pos| Pos::new(pos.x(), field_to_board_y(pos.y())))
+            .collect();
+
+        let in_bounds = blocks.iter().all(|pos| {
+            (0..i16::from(Board::WIDTH)).contains(&pos.x())
+                && (0..i16::from(Board::HEIGHT)).contains(&pos.y())
+        });
+
+        if !in_bounds {
+            return Err(FumenError::InvalidPiece);
+        }
+
+        Piece::from_blocks(shape, &blocks).ok_or(FumenError::InvalidPiece)
+    }
+
+    /// Get the blocks of the piece in the fumen field.
+    fn blocks(self) -> Vec<Pos> {
+        piece_offsets(self.piece_type, self.rotation)
+            .into_iter()
+            .map(|offset| offset + self.centre)
+            .collect()
+    }
+
+    /// Get the position value of the piece.
+    ///
+    /// Older versions of fumen used a different centre for some pieces, which is still used for
+    /// the stored position.
+    fn position(self) -> u32 {
+        let (dx, dy) = self.centre_adjustment();
+        let x = self.centre.x() - dx;
+        let y = self.centre.y() - dy;
+        ((FIELD_TOP - 1 - y) * FIELD_WIDTH + x) as u32
+    }
+
+    /// Create a piece from its type, rotation and position value.
+    fn from_position(piece_type: u8, rotation: u32, position: u32) -> FumenPiece {
+        let position = position as i16;
+        let mut piece = FumenPiece {
+            piece_type,
+            rotation,
+            centre: Pos::new(
+                position % FIELD_WIDTH,
+                FIELD_TOP - 1 - position / FIELD_WIDTH,
+            ),
+        };
+
+        let (dx, dy) = piece.centre_adjustment();
+        piece.centre = piece.centre + Pos::new(dx, dy);
+        piece
+    }
+
+    /// The difference between the centre of the piece and the centre used for the position value.
+    fn centre_adjustment(self) -> (i16, i16) {
+        match (value_color(self.piece_type), self.rotation) {
+            (Ok(Some(ShapeColor::O)), LEFT) => (1, -1),
+            (Ok(Some(ShapeColor::O)), REVERSE) => (1, 0),
+            (Ok(Some(ShapeColor::O)), SPAWN) => (0, -1),
+            (Ok(Some(ShapeColor::I)), REVERSE) => (1, 0),
+            (Ok(Some(ShapeColor::I)), LEFT) => (0, -1),
+            (Ok(Some(ShapeColor::S)), SPAWN) => (0, -1),
+            (Ok(Some(ShapeColor::S)), RIGHT) => (-1, 0),
+            (Ok(Some(ShapeColor::Z)), SPAWN) => (0, -1),
+            (Ok(Some(ShapeColor::Z)), LEFT) => (1, 0),
+            _ => (0, 0),
+        }
+    }
+}
+
+/// Get the blocks of a fumen piece relative to its centre, with y pointing up.
+fn piece_offsets(piece_type: u8, rotation: u32) -> Vec<Pos> {
+    let spawn: [(i16, i16); 4] = match piece_type {
+        1 => [(0, 0), (-1, 0), (1, 0), (2, 0)],
+        2 => [(0, 0), (-1, 0), (1, 0), (1, 1)],
+        3 => [(0, 0), (1, 0), (0, 1), (1, 1)],
+        4 => [(0, 0), (1, 0), (0, 1), (-1, 1)],
+        5 => [(0, 0), (-1, 0), (1, 0), (0, 1)],
+        6 => [(0, 0), (-1, 0), (1, 0), (-1, 1)],
+        7 => [(0, 0), (-1, 0), (0, 1), (1, 1)],
+        _ => return Vec::new(),
+    };
+
+    spawn
+        .iter()
+        .map(|&(x, y)| match rotation {
+            SPAWN => Pos::new(x, y),
+            RIGHT => Pos::new(y, -x),
+            REVERSE => Pos::new(-x, -y),
+            _ => Pos::new(-y, x),
+        })
+        .collect()
+}
+
+/// The piece and flags for a page of a fumen.
+struct Action {
+    piece: Option<FumenPiece>,
+
+    /// Whether the garbage row rises into the field after this page.
+    rise: bool,
+
+    /// Whether the field is flipped horizontally after this page.
+    mirror: bool,
+
+    /// Whether blocks are shown in guideline colours, only used on the first page.
+    colorize: bool,
+
+    /// Whether the page has a comment.
+    comment: bool,
+
+    /// Whether the piece locks and lines are cleared after this page.
+    lock: bool,
+}
+
+impl Action {
+    fn encode(&self) -> u32 {
+        let (piece_type, rotation, position) = match self.piece {
+            Some(piece) => (
+                u32::from(piece.piece_type),
+                piece.rotation,
+                piece.position(),
+            ),
+            None => (0, REVERSE, 0),
+        };
+
+        let flags = [
+            !self.lock,
+            self.comment,
+            self.colorize,
+            self.mirror,
+            self.rise,
+        ]
+        .iter()
+        .fold(0, |value, &flag| value * 2 + u32::from(flag));
+
+        ((flags * FIELD_BLOCKS as u32 + position) * 4 + rotation) * 8 + piece_type
+    }
+
+    fn decode(mut value: u32) -> Result<Action, FumenError> {
+        let mut take = |base: u32| {
+            let part = value % base;
+            value /= base;
+            part
+        };
+
+        let piece_type = take(8) as u8;
+        let rotation = take(4);
+        let position = take(FIELD_BLOCKS as u32);
+        let rise = take(2) == 1;
+        let mirror = take(2) == 1;
+        let colorize = take(2) == 1;
+        let comment = take(2) == 1;
+        let lock = take(2) == 0;
+
+        let piece = match piece_type {
+            0 => None,
+            GARBAGE => return Err(FumenError::InvalidPiece),
+            _ => Some(FumenPiece::from_position(piece_type, rotation, position)),
+        };
+
+        if let Some(piece) = piece {
+            let in_field = piece.blocks().iter().all(|pos| {
+                (0..FIELD_WIDTH).contains(&pos.x()) && (-1..FIELD_TOP).contains(&pos.y())
+            });
+            if !in_field {
+                return Err(FumenError::InvalidPiece);
+            }
+        }
+
+        Ok(Action {
+            piece,
+            rise,
+            mirror,
+            colorize,
+            comment,
+            lock,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use crate::game::Game;
+
+    use super::*;
+
+    fn short_game() -> History {
+        let body = include_str!("../../resources/games/short.json");
+        let message: Value = serde_json::from_str(body).unwrap();
+        serde_json::from_value(message["history"].clone()).unwrap()
+    }
+
+    /// Assert two fumens show the same boards and pieces.
+    ///
+    /// Pieces are compared by their blocks, because some shapes look the same in two rotations.
+    fn assert_same_pages(left: &Fumen, right: &Fumen) {
+        let describe = |fumen: &Fumen| -> Vec<String> {
+            fumen
+                .pages()
+                .iter()
+                .map(|page| match &page.piece {
+                    Some(piece) => page.board.to_string_with_piece(piece),
+                    None => page.board.to_string(),
+                })
+                .collect()
+        };
+
+        assert_eq!(describe(left), describe(right));
+    }
+
+    #[test]
+    fn an_empty_board_is_the_standard_empty_fumen() {
+        let fumen = Fumen::new(Board::default(), None);
+
+        assert_eq!(fumen.to_string(), "v115@vhAAgH");
+    }
+
+    #[test]
+    fn the_standard_empty_fumen_is_an_empty_board() {
+        let fumen: Fumen = "v115@vhAAgH".parse().unwrap();
+
+        assert_eq!(
+            fumen.pages(),
+            &[FumenPage {
+                board: Board::default(),
+                piece: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn a_fumen_can_be_read_from_a_url() {
+        let fumen: Fumen = "https://harddrop.com/fumen/?v115@vhAAgH".parse().unwrap();
+
+        assert_eq!(fumen.pages().len(), 1);
+    }
+
+    #[test]
+    fn a_board_and_piece_are_the_same_after_writing_and_reading() {
+        let board: Board = "
+            ..........
+            ZZ....IIII
+            XXXX.XXXXX
+        "
+        .parse()
+        .unwrap();
+
+        for &color in &[
+            ShapeColor::O,
+            ShapeColor::I,
+            ShapeColor::J,
+            ShapeColor::L,
+            ShapeColor::S,
+            ShapeColor::T,
+            ShapeColor::Z,
+        ] {
+            let mut piece = Piece::new(Shape::from_color(color).unwrap());
+
+            for _ in 0..4 {
+                piece.rotate_clockwise();
+                piece.down();
+
+                let fumen = Fumen::new(board.clone(), Some(piece.clone()));
+                let read: Fumen = fumen.to_string().parse().unwrap();
+
+                assert_same_pages(&read, &fumen);
+            }
+        }
+    }
+
+    #[test]
+    fn a_piece_is_positioned_by_its_centre_from_the_bottom_left_of_the_field() {
+        // A T piece pointing up, with its centre one cell from the left on the bottom row
+        let piece = FumenPiece::from_position(5, SPAWN, 221);
+
+        let (_, expected) = Board::parse_with_piece(
+            "
+            .t........
+            ttt.......
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(piece.to_piece().ok(), expected);
+    }
+
+    #[test]
+    fn a_long_fumen_is_split_with_question_marks() {
+        let fumen = Fumen::from_history(&short_game());
+        let text = fumen.to_string();
+
+        assert_eq!(
+            text.find('?'),
+            Some(VERSION_PREFIX.len() + FIRST_SPLIT_LENGTH)
+        );
+        assert_same_pages(&text.parse().unwrap(), &fumen);
+    }
+
+    #[test]
+    fn a_history_has_a_page_for_every_piece_placed() {
+        let fumen = Fumen::from_history(&short_game());
+
+        let mut player = ReplayPlayer::new(short_game());
+        player.play_to_end();
+
+        assert_eq!(fumen.pages().len() as u32, player.pieces_placed());
+    }
+
+    #[test]
+    fn pages_of_a_history_only_store_the_changes_in_the_field() {
+        let fumen = Fumen::from_history(&short_game());
+        let text = fumen.to_string();
+
+        // Every page after the first has an unchanged field, so is only a few characters
+        assert!(text.len() < fumen.pages().len() * 8);
+    }
+
+    #[test]
+    fn invalid_characters_are_an_error() {
+        assert_eq!(
+            "v115@vh!AgH".parse::<Fumen>(),
+            Err(FumenError::InvalidCharacter('!'))
+        );
+    }
+
+    #[test]
+    fn an_unknown_version_is_an_error() {
+        assert_eq!(
+            "v110@vhAAgH".parse::<Fumen>(),
+            Err(FumenError::UnknownVersion)
+        );
+    }
+
+    #[test]
+    fn a_truncated_fumen_is_an_error() {
+        assert_eq!(
+            "v115@vhAAg".parse::<Fumen>(),
+            Err(FumenError::UnexpectedEnd)
+        );
+    }
+
+    #[test]
+    fn a_piece_in_the_top_row_of_the_board_is_left_off_the_page() {
+        let mut piece = Piece::new(Shape::from_color(ShapeColor::I).unwrap());
+        piece.rotate_clockwise();
+        assert!(piece.blocks().any(|pos| pos.y() == 0));
+
+        let fumen = Fumen::new(Board::default(), Some(piece));
+        let read: Fumen = fumen.to_string().parse().unwrap();
+
+        assert_eq!(read.pages()[0].piece, None);
+    }
+
+    #[test]
+    fn a_history_that_tops_out_can_be_written() {
+        let mut game = Game::from_seed([1, 2, 3, 4]);
+        while game.step() == StepResult::Continue {
+            game.rotate();
+            game.start_hard_drop();
+        }
+
+        let fumen = Fumen::from_history(game.history());
+
+        assert!(fumen.to_string().starts_with(VERSION_PREFIX));
+    }
+}
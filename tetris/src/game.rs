@@ -1,18 +1,39 @@
 use std::cmp;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
 use std::ops::Add;
 use std::ops::Mul;
 
 use rand::SeedableRng;
 use rand::XorShiftRng;
+use serde::Serializer;
 use serde_derive::{Deserialize, Serialize};
 
 use crate::board::Board;
 use crate::board::FillResult;
+use crate::compact;
+use crate::compact::DecodeError;
+use crate::config::GameConfig;
+use crate::dig::DigOver;
+use crate::dig::DigRace;
+use crate::fumen::Fumen;
 use crate::game_over::GameOver;
 use crate::piece::Piece;
+use crate::pos::Pos;
+use crate::practice::Practice;
+use crate::practice::Snapshot;
+use crate::puzzle::Progress;
+use crate::puzzle::Puzzle;
+use crate::puzzle::PuzzleOver;
+use crate::score::ScoreMessage;
 use crate::shape::Bag;
+use crate::shape::ShapeColor;
 use crate::state::Paused;
 use crate::state::State;
+use crate::statistics::Statistics;
 use crate::Shape;
 
 /// The rate at which pieces fall, measured in hundredths of cells per frame.
@@ -27,24 +48,18 @@ impl Gravity {
     /// How many gravity units we subdivide a cell into - impacts the rate pieces fall.
     const UNITS_PER_CELL: u32 = 100;
 
-    /// The initial gravity at the start of the game.
-    const INITIAL: Gravity = Gravity(4);
-
     /// The gravity when doing a faster soft drop - defined as _1G_.
     const SOFT_DROP: Gravity = Gravity(Gravity::UNITS_PER_CELL);
 
     /// The gravity when doing a hard drop - is meant to appear instantaneous, so defined as _20G_.
     const HARD_DROP: Gravity = Gravity(Gravity::UNITS_PER_CELL * 20);
-
-    /// The rate gravity increases every level.
-    const INCREASE_PER_LEVEL: Gravity = Gravity(2);
 }
 
 impl Add for Gravity {
     type Output = Self;
 
     fn add(self, rhs: Self) -> Self {
-        Gravity(self.0 + rhs.0)
+        Gravity(self.0.saturating_add(rhs.0))
     }
 }
 
@@ -52,14 +67,12 @@ impl Mul<u32> for Gravity {
     type Output = Self;
 
     fn mul(self, rhs: u32) -> Self {
-        Gravity(self.0 * rhs)
+        Gravity(self.0.saturating_mul(rhs))
     }
 }
 
-/// The number of lines to clear to go to the next level - resulting in gravity increasing.
-const NUM_LINES_CLEARED_PER_LEVEL: u32 = 10;
-
 /// The different ways a piece can drop, depending on user input.
+#[derive(Clone)]
 enum Drop {
     /// The normal drop rate, where gravity is based on the current level.
     Normal,
@@ -70,8 +83,8 @@ enum Drop {
 }
 
 /// Actions that a user can take in the game.
-#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
-enum Action {
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub(crate) enum Action {
     /// Move the piece to the left.
     MoveLeft,
     /// Move the piece to the right.
@@ -84,11 +97,48 @@ enum Action {
     StartHardDrop,
     /// Stop a soft or hard drop and return to the normal drop speed.
     StopDrop,
+    /// Push garbage up from the bottom of the board, e.g. when attacked in versus mode.
+    Garbage {
+        /// The number of garbage rows.
+        rows: u8,
+        /// The column left empty in every row.
+        hole: u8,
+    },
+}
+
+/// Potential errors from adding garbage to a game.
+#[derive(Debug, PartialEq)]
+pub enum GarbageError {
+    /// The hole is in a column outside the board
+    HoleOutsideBoard(u8),
+}
+
+impl Error for GarbageError {}
+
+impl Display for GarbageError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            GarbageError::HoleOutsideBoard(hole) => {
+                write!(f, "Garbage hole column {} is outside the board", hole)
+            }
+        }
+    }
+}
+
+impl Action {
+    /// Return whether the action could happen in a game, which is only false for garbage with its
+    /// hole outside the board.
+    pub(crate) fn is_valid(self) -> bool {
+        match self {
+            Action::Garbage { hole, .. } => hole < Board::WIDTH,
+            _ => true,
+        }
+    }
 }
 
 /// Describes how much time has passed in frames.
 #[derive(Serialize, Deserialize, Clone, Copy, PartialOrd, PartialEq, Debug)]
-struct Tick(u32);
+pub(crate) struct Tick(u32);
 
 impl Tick {
     /// Create a new `Tick` at 0.
@@ -100,6 +150,17 @@ impl Tick {
     fn incr(&mut self) {
         self.0 += 1;
     }
+
+    /// Get the number of frames as an integer.
+    pub(crate) fn value(self) -> u32 {
+        self.0
+    }
+}
+
+impl From<u32> for Tick {
+    fn from(tick: u32) -> Self {
+        Tick(tick)
+    }
 }
 
 /// A game, bundled with a record of every action performed in that game.
@@ -109,20 +170,172 @@ impl Tick {
 pub struct Game {
     game_state: Box<GameState>,
     history: History,
+
+    /// Snapshots for undoing and rewinding, only present in practice mode.
+    practice: Option<Practice>,
+
+    /// The puzzle being solved, only present in puzzle mode.
+    puzzle: Option<Box<Puzzle>>,
+
+    /// The dig race being played, only present in dig mode.
+    dig: Option<DigRace>,
 }
 
 impl Default for Game {
     /// Create a new game with a random seed and empty history.
     fn default() -> Self {
-        let seed = rand::random();
-        Game {
-            game_state: Box::new(GameState::new(seed)),
-            history: History::new(seed),
-        }
+        Game::from_seed(rand::random())
     }
 }
 
 impl Game {
+    /// Create a new game from the given seed, which determines the order pieces appear.
+    ///
+    /// Games with the same seed always get the same pieces, so play out the same way given the
+    /// same actions.
+    ///
+    /// # Panics
+    /// Panics if the seed is all zeros.
+    pub fn from_seed(seed: [u32; 4]) -> Self {
+        Game::with_config(seed, GameConfig::default())
+    }
+
+    /// Create a new game from the given seed, played with the given rules.
+    ///
+    /// Games with non-default rules can't be submitted as high-scores.
+    ///
+    /// # Panics
+    /// Panics if the seed is all zeros.
+    pub fn with_config(seed: [u32; 4], config: GameConfig) -> Self {
+        Game::resume(
+            GameState::new(seed, config),
+            History::with_config(seed, config),
+        )
+    }
+
+    /// Continue a game from the given state, recording further actions on top of the given history.
+    pub(crate) fn resume(game_state: GameState, history: History) -> Self {
+        Game {
+            game_state: Box::new(game_state),
+            history,
+            practice: None,
+            puzzle: None,
+            dig: None,
+        }
+    }
+
+    /// Create a new practice game with a random seed, where the player can undo and rewind.
+    ///
+    /// Practice games that are undone or rewound can't be submitted as high-scores.
+    pub fn practice() -> Self {
+        let mut game = Game::default();
+        game.start_practice();
+        game
+    }
+
+    /// Create a new practice game with a random seed, starting from the given board.
+    ///
+    /// The board is recorded in the history, so the game can be replayed, but games that start
+    /// from a board can't be submitted as high-scores.
+    pub fn practice_with_board(board: Board) -> Self {
+        let history = History::with_start(rand::random(), GameConfig::default(), board, Vec::new());
+        let game_state = GameState::start_of(&history);
+
+        let mut game = Game::resume(game_state, history);
+        game.start_practice();
+        game
+    }
+
+    /// Start recording snapshots, so the game can be undone and rewound.
+    fn start_practice(&mut self) {
+        let mut practice = Practice::default();
+        practice.record(&self.game_state, self.history.actions.len());
+        self.practice = Some(practice);
+    }
+
+    /// Create a new puzzle game, starting from the puzzle's board with the puzzle's pieces.
+    ///
+    /// The puzzle's board and pieces are recorded in the history, so the game can be replayed.
+    pub fn puzzle(puzzle: Puzzle) -> Self {
+        let history = History::with_start(
+            rand::random(),
+            GameConfig::default(),
+            puzzle.board().clone(),
+            puzzle.pieces(),
+        );
+        let game_state = GameState::start_of(&history);
+        let mut game = Game::resume(game_state, history);
+        game.puzzle = Some(Box::new(puzzle));
+        game
+    }
+
+    /// Get the puzzle being solved, if this is a puzzle game.
+    pub fn current_puzzle(&self) -> Option<&Puzzle> {
+        self.puzzle.as_deref()
+    }
+
+    /// Create a new dig race with a random seed, starting with the race's garbage.
+    pub fn dig(race: DigRace) -> Self {
+        Game::dig_from_seed(rand::random(), race)
+    }
+
+    /// Create a new dig race from the given seed, which determines the holes in the garbage as
+    /// well as the order pieces appear.
+    ///
+    /// The garbage is recorded in the history, so the race can be replayed.
+    ///
+    /// # Panics
+    /// Panics if the seed is all zeros.
+    pub fn dig_from_seed(seed: [u32; 4], race: DigRace) -> Self {
+        let mut game = Game::from_seed(seed);
+        for (rows, hole) in race.garbage(seed) {
+            game.add_garbage(rows, hole)
+                .expect("a dig race's garbage has its holes inside the board");
+        }
+        game.dig = Some(race);
+        game
+    }
+
+    /// Get the dig race being played, if this is a dig game.
+    pub fn current_dig(&self) -> Option<&DigRace> {
+        self.dig.as_ref()
+    }
+
+    /// Get the number of pieces that have been locked onto the board.
+    pub fn pieces_placed(&self) -> u32 {
+        self.game_state.pieces_placed
+    }
+
+    /// Get statistics about how the game has been played so far.
+    pub fn statistics(&self) -> &Statistics {
+        &self.game_state.statistics
+    }
+
+    /// Get the current position as a fumen, to share it.
+    pub fn to_fumen(&self) -> Fumen {
+        Fumen::new(self.board().clone(), Some(self.piece().clone()))
+    }
+
+    /// Return whether this is a practice game.
+    pub fn is_practice(&self) -> bool {
+        self.practice.is_some()
+    }
+
+    /// Analyse the finesse of every piece placed from now on, to show it to the player.
+    pub(crate) fn analyse_finesse(&mut self) {
+        self.game_state.statistics.finesse.enable();
+    }
+
+    /// Get the history of every action performed in the game so far.
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// Mark the game as played by a bot, so it can't be submitted as a score.
+    pub(crate) fn set_played_by_bot(&mut self) {
+        self.history.set_played_by_bot();
+    }
+
     /// Get the current piece that the user is placing.
     pub fn piece(&self) -> &Piece {
         &self.game_state.piece
@@ -148,20 +361,102 @@ impl Game {
         self.game_state.score
     }
 
+    /// Get the rules the game is played with.
+    pub fn config(&self) -> &GameConfig {
+        &self.game_state.config
+    }
+
+    /// Advance the game one frame, without changing to another state on a game over.
+    ///
+    /// This is for running games without a frontend, such as in simulations. Once this returns
+    /// `StepResult::GameOver` the game shouldn't be stepped again.
+    pub fn step(&mut self) -> StepResult {
+        self.game_state.apply_step()
+    }
+
     /// Advance the game one frame.
     ///
     /// Consumes the game and returns the new state. In the event of a game over, the returned state
     /// will be a "game over" state, otherwise it will be the game itself.
+    ///
+    /// In puzzle mode, once the puzzle is solved or failed the returned state is a "puzzle over"
+    /// state instead.
     pub fn update(mut self) -> State {
-        match self.game_state.apply_step() {
+        let step_result = self.game_state.apply_step();
+
+        if let Some(race) = self.dig {
+            let finished = match race.progress(&self.game_state.board, step_result) {
+                Progress::Solved => true,
+                Progress::Failed => false,
+                Progress::Unsolved => return State::Play(self),
+            };
+            let game_state = *self.game_state;
+            let dig_over = DigOver::new(
+                race,
+                finished,
+                game_state.tick.value(),
+                game_state.statistics,
+                self.history,
+            );
+            return State::DigOver(dig_over);
+        }
+
+        if let Some(puzzle) = self.puzzle.take() {
+            return match puzzle.progress(&self.game_state, step_result) {
+                Progress::Solved => State::PuzzleOver(PuzzleOver::new(*puzzle, true)),
+                Progress::Failed => State::PuzzleOver(PuzzleOver::new(*puzzle, false)),
+                Progress::Unsolved => {
+                    self.puzzle = Some(puzzle);
+                    State::Play(self)
+                }
+            };
+        }
+
+        match step_result {
             StepResult::GameOver => {
-                let game_over = GameOver::new(self.game_state.score, self.history);
+                let game_state = *self.game_state;
+                let game_over =
+                    GameOver::new(game_state.score, game_state.statistics, self.history);
                 State::GameOver(game_over)
             }
-            StepResult::Continue => State::Play(self),
+            StepResult::Continue => {
+                if let Some(practice) = &mut self.practice {
+                    practice.record(&self.game_state, self.history.actions.len());
+                }
+                State::Play(self)
+            }
         }
     }
 
+    /// Undo the last piece placement, returning to when that piece appeared.
+    ///
+    /// Only possible in practice mode.
+    pub fn undo(&mut self) {
+        if let Some(practice) = &mut self.practice {
+            if let Some(snapshot) = practice.undo(&self.game_state) {
+                self.restore(snapshot);
+            }
+        }
+    }
+
+    /// Rewind the game by the given number of frames.
+    ///
+    /// Only possible in practice mode.
+    pub fn rewind(&mut self, frames: u32) {
+        if let Some(practice) = &mut self.practice {
+            let tick = self.game_state.tick.value().saturating_sub(frames);
+            if let Some(snapshot) = practice.rewind(tick) {
+                self.restore(snapshot);
+            }
+        }
+    }
+
+    /// Return the game to a snapshot, forgetting any actions performed after it.
+    fn restore(&mut self, snapshot: Snapshot) {
+        *self.game_state = snapshot.game_state;
+        self.history.rewind(snapshot.num_actions);
+    }
+
     /// Move the piece to the left.
     pub fn move_left(&mut self) {
         self.apply_action(Action::MoveLeft);
@@ -192,6 +487,42 @@ impl Game {
         self.apply_action(Action::StopDrop);
     }
 
+    /// Get what happened when a piece locked on the last frame, if one did.
+    pub(crate) fn last_lock(&self) -> Option<&Lock> {
+        self.game_state.last_lock.as_ref()
+    }
+
+    /// Get the current frame of the game, counted from the start.
+    pub(crate) fn tick(&self) -> Tick {
+        self.game_state.tick
+    }
+
+    /// Push garbage up from the bottom of the board, with a hole in the given column.
+    ///
+    /// The garbage is recorded in the history, so it's added again when the game is replayed.
+    /// This is a game over if blocks are pushed off the top of the board, or the piece is left
+    /// overlapping the board, and the game ends on the next frame.
+    ///
+    /// Garbage with its hole column outside the board is an error, and isn't added to the game or
+    /// its history.
+    pub fn add_garbage(&mut self, rows: u8, hole_column: u8) -> Result<StepResult, GarbageError> {
+        let action = Action::Garbage {
+            rows,
+            hole: hole_column,
+        };
+        if !action.is_valid() {
+            return Err(GarbageError::HoleOutsideBoard(hole_column));
+        }
+
+        self.apply_action(action);
+
+        Ok(if self.game_state.topped_out {
+            StepResult::GameOver
+        } else {
+            StepResult::Continue
+        })
+    }
+
     /// Pause the game, consuming the current state and returning a "paused" state.
     pub fn pause(self) -> State {
         State::Paused(Paused(self))
@@ -205,15 +536,16 @@ impl Game {
 }
 
 /// A game of Tetris in-progress.
-struct GameState {
+#[derive(Clone)]
+pub(crate) struct GameState {
     /// The current piece that the user is placing.
-    piece: Piece,
+    pub(crate) piece: Piece,
 
     /// The board, made up of blocks from old pieces.
-    board: Board,
+    pub(crate) board: Board,
 
     /// A bag to pull new pieces from.
-    bag: Bag,
+    pub(crate) bag: Bag,
 
     /// How far the piece has dropped through the current cell - once it reaches 100 the piece
     /// drops one cell, or locks.
@@ -228,22 +560,58 @@ struct GameState {
     drop: Drop,
 
     /// The number of lines that have been cleared.
-    lines_cleared: u32,
+    pub(crate) lines_cleared: u32,
 
     /// The player's score.
-    score: u32,
+    pub(crate) score: u32,
 
     /// Number of frames since the game has started.
-    tick: Tick,
+    pub(crate) tick: Tick,
+
+    /// Number of pieces that have been locked onto the board.
+    pub(crate) pieces_placed: u32,
+
+    /// Whether the last successful move of the piece was a rotation, used to detect T-spins.
+    rotated_last: bool,
+
+    /// What happened when a piece locked on the last frame, if one did.
+    pub(crate) last_lock: Option<Lock>,
+
+    /// Whether garbage topped the player out, so the game ends on the next frame.
+    topped_out: bool,
+
+    /// Statistics about how the game has been played.
+    pub(crate) statistics: Statistics,
+
+    /// The rules the game is played with.
+    pub(crate) config: GameConfig,
 }
 
 impl GameState {
-    /// Create a new game from the given seed, which determines the order pieces appear.
-    fn new(seed: [u32; 4]) -> GameState {
-        let mut bag = Bag::new(XorShiftRng::from_seed(seed));
+    /// Create a new game from the given seed, which determines the order pieces appear, played
+    /// with the given rules.
+    pub(crate) fn new(seed: [u32; 4], config: GameConfig) -> GameState {
+        GameState {
+            config,
+            ..GameState::with_board(Board::default(), Bag::new(XorShiftRng::from_seed(seed)))
+        }
+    }
+
+    /// Create a game at the start of the given history, before any of its actions.
+    pub(crate) fn start_of(history: &History) -> GameState {
+        let rng = XorShiftRng::from_seed(history.seed());
+        let bag = Bag::with_preset(rng, history.pieces().to_vec());
+        GameState {
+            config: *history.config(),
+            ..GameState::with_board(history.board().clone(), bag)
+        }
+    }
+
+    /// Create a new game starting from the given board, pulling pieces from the given bag.
+    pub(crate) fn with_board(board: Board, mut bag: Bag) -> GameState {
         GameState {
             piece: Piece::new(bag.pop()),
-            board: Board::default(),
+            board,
             bag,
             drop_tick: 0,
             lock_delay: false,
@@ -251,30 +619,55 @@ impl GameState {
             lines_cleared: 0,
             score: 0,
             tick: Tick::new(),
+            pieces_placed: 0,
+            rotated_last: false,
+            last_lock: None,
+            topped_out: false,
+            statistics: Statistics::default(),
+            config: GameConfig::default(),
         }
     }
 
     /// Apply the given action to the game.
-    fn apply_action(&mut self, action: Action) {
+    pub(crate) fn apply_action(&mut self, action: Action) {
+        self.statistics.record_action(action);
+
         match action {
             Action::MoveLeft => {
-                self.try_move_left();
+                if self.try_move_left() {
+                    self.rotated_last = false;
+                }
             }
             Action::MoveRight => {
-                self.try_move_right();
+                if self.try_move_right() {
+                    self.rotated_last = false;
+                }
             }
             Action::Rotate => {
-                self.try_rotate();
+                if self.try_rotate() {
+                    self.rotated_last = true;
+                }
             }
             Action::StartSoftDrop => self.drop = Drop::Soft,
             Action::StartHardDrop => self.drop = Drop::Hard,
             Action::StopDrop => self.drop = Drop::Normal,
+            Action::Garbage { rows, hole } => {
+                if self.add_garbage(rows, hole) == StepResult::GameOver {
+                    self.topped_out = true;
+                }
+            }
         }
     }
 
     /// Advance the game one frame. Returns whether this is a game over.
-    fn apply_step(&mut self) -> StepResult {
+    pub(crate) fn apply_step(&mut self) -> StepResult {
+        if self.topped_out {
+            return StepResult::GameOver;
+        }
+
         self.tick.incr();
+        self.statistics.record_frame();
+        self.last_lock = None;
 
         while self.drop_tick >= Gravity::UNITS_PER_CELL {
             self.drop_tick -= Gravity::UNITS_PER_CELL;
@@ -295,9 +688,10 @@ impl GameState {
 
     /// Get the normal gravity rate, based on the current level.
     fn normal_gravity(&self) -> Gravity {
-        let level = self.lines_cleared / NUM_LINES_CLEARED_PER_LEVEL;
+        let level = self.config.level(self.lines_cleared);
 
-        let g = Gravity::INITIAL + Gravity::INCREASE_PER_LEVEL * level;
+        let g =
+            Gravity(self.config.initial_gravity) + Gravity(self.config.gravity_per_level) * level;
 
         // Normal gravity should never be faster than a soft drop.
         cmp::min(g, Gravity::SOFT_DROP)
@@ -369,8 +763,11 @@ impl GameState {
             } else {
                 self.lock_delay = true;
             }
-        } else if self.lock_delay {
-            self.lock_delay = false;
+        } else {
+            self.rotated_last = false;
+            if self.lock_delay {
+                self.lock_delay = false;
+            }
         }
 
         StepResult::Continue
@@ -380,18 +777,37 @@ impl GameState {
     ///
     /// Returns whether this results in a game over.
     fn lock_piece(&mut self) -> StepResult {
+        let t_spin = self.is_t_spin();
+        self.statistics
+            .finesse
+            .record_lock(&self.board, &self.piece);
+
         let FillResult {
             step_result,
             lines_cleared,
         } = self.board.lock_piece(&self.piece);
 
+        let lock = Lock {
+            piece: self.piece.clone(),
+            lines_cleared,
+            t_spin,
+            perfect_clear: lines_cleared > 0 && self.board.is_empty(),
+        };
+        self.statistics.record_lock(&lock);
+        self.last_lock = Some(lock);
+
         self.piece = Piece::new(self.bag.pop());
+        self.pieces_placed += 1;
+        self.rotated_last = false;
 
         self.drop = Drop::Normal;
         self.drop_tick = 0;
         self.lock_delay = false;
         self.lines_cleared += lines_cleared;
-        self.score += lines_cleared * lines_cleared * 100;
+        // Custom rules can score more than fits, so the score stops at the maximum
+        self.score = self
+            .score
+            .saturating_add(self.config.line_score(lines_cleared));
 
         if self.piece_overlaps_board() {
             StepResult::GameOver
@@ -400,17 +816,41 @@ impl GameState {
         }
     }
 
-    /// Return whether the piece is overlapping the board.
-    fn piece_overlaps_board(&self) -> bool {
-        let mut overlaps = false;
+    /// Push garbage up from the bottom of the board, with a hole in the given column.
+    ///
+    /// This is a game over if blocks are pushed off the top of the board, or the piece is left
+    /// overlapping the board.
+    fn add_garbage(&mut self, rows: u8, hole: u8) -> StepResult {
+        if self.board.add_garbage(rows, hole) == StepResult::GameOver || self.piece_overlaps_board()
+        {
+            StepResult::GameOver
+        } else {
+            StepResult::Continue
+        }
+    }
 
-        for block in self.piece.blocks() {
-            if !self.board.is_pos_free(block) {
-                overlaps = true;
-            }
+    /// Return whether locking the piece now would be a T-spin.
+    ///
+    /// This uses the "3-corner" rule: the piece is a T that was rotated into place, and at least
+    /// three of the four cells diagonal to its centre are filled or out of bounds.
+    fn is_t_spin(&self) -> bool {
+        if self.piece.shape.color != ShapeColor::T || !self.rotated_last {
+            return false;
         }
 
-        overlaps
+        let centre = self.piece.pos + Pos::new(1, 2);
+
+        let filled_corners = [(-1, -1), (1, -1), (-1, 1), (1, 1)]
+            .iter()
+            .filter(|&&(x, y)| !self.board.is_pos_free(centre + Pos::new(x, y)))
+            .count();
+
+        filled_corners >= 3
+    }
+
+    /// Return whether the piece is overlapping the board.
+    fn piece_overlaps_board(&self) -> bool {
+        self.board.overlaps(&self.piece)
     }
 
     /// Perform a naive wall-kick (not SRS):
@@ -421,47 +861,268 @@ impl GameState {
     }
 }
 
+/// What happened when a piece locked onto the board.
+#[derive(Clone, Debug)]
+pub(crate) struct Lock {
+    /// The piece that locked, at the position it locked in.
+    pub(crate) piece: Piece,
+
+    /// The number of lines cleared by the piece.
+    pub(crate) lines_cleared: u32,
+
+    /// Whether the piece was a T rotated into a tight spot.
+    pub(crate) t_spin: bool,
+
+    /// Whether the piece cleared every block from the board.
+    pub(crate) perfect_clear: bool,
+}
+
 /// Result from applying a move or step in a game. The game may continue or it is a game over.
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum StepResult {
+    /// The game continues.
     Continue,
+    /// The game is over.
     GameOver,
 }
 
 /// A history of a game, that can be replayed. This is useful for verifying high scores.
+///
+/// A history is serialized as a JSON object listing every action, but can also be deserialized
+/// from a base64 string in the compact format from `History::to_base64`.
 #[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(try_from = "SerializedHistory")]
 pub struct History {
     /// The seed used to initialise the game, so the game can be reliably replayed.
     seed: [u32; 4],
 
     /// A list of actions and when they occurred.
     actions: Vec<(Tick, Action)>,
+
+    /// Whether the game was rewound in practice mode, meaning it can't be submitted as a score.
+    #[serde(default, skip_serializing_if = "is_false")]
+    rewound: bool,
+
+    /// Whether the game was played by a bot, meaning it can't be submitted as a score.
+    #[serde(default, skip_serializing_if = "is_false")]
+    played_by_bot: bool,
+
+    /// The rules the game was played with, if they aren't the defaults.
+    #[serde(default, skip_serializing_if = "GameConfig::is_default")]
+    config: GameConfig,
+
+    /// The board the game started from, if it wasn't empty, e.g. in a puzzle.
+    #[serde(
+        skip_serializing_if = "Board::is_empty",
+        serialize_with = "serialize_board"
+    )]
+    board: Board,
+
+    /// Pieces that were given before any from the seed, e.g. in a puzzle.
+    #[serde(
+        skip_serializing_if = "Vec::is_empty",
+        serialize_with = "serialize_pieces"
+    )]
+    pieces: Vec<Shape>,
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+/// Serialize a board as rows of cells, like the board of a puzzle.
+fn serialize_board<S: Serializer>(board: &Board, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(board.to_rows())
+}
+
+/// Serialize pieces as a string of their letters, like the pieces of a puzzle.
+fn serialize_pieces<S: Serializer>(pieces: &[Shape], serializer: S) -> Result<S::Ok, S::Error> {
+    let letters: String = pieces.iter().map(|shape| shape.color.letter()).collect();
+    serializer.serialize_str(&letters)
 }
 
 impl History {
     /// Create a new empty history with the given seed.
-    fn new(seed: [u32; 4]) -> Self {
+    pub(crate) fn new(seed: [u32; 4]) -> Self {
+        History::with_config(seed, GameConfig::default())
+    }
+
+    /// Create a new empty history with the given seed, for a game played with the given rules.
+    pub(crate) fn with_config(seed: [u32; 4], config: GameConfig) -> Self {
+        History::with_start(seed, config, Board::default(), Vec::new())
+    }
+
+    /// Create a new empty history with the given seed, for a game played with the given rules
+    /// that started from the given board and was given the given pieces before any from the seed.
+    pub(crate) fn with_start(
+        seed: [u32; 4],
+        config: GameConfig,
+        board: Board,
+        pieces: Vec<Shape>,
+    ) -> Self {
         History {
             seed,
             actions: Vec::new(),
+            rewound: false,
+            played_by_bot: false,
+            config,
+            board,
+            pieces,
         }
     }
 
     /// Push an action onto the history, with the time it happened.
     ///
-    /// Actions are assumed to be pushed chronologically.
-    fn push_action(&mut self, tick: Tick, action: Action) {
+    /// Actions must be pushed chronologically, so the history can be encoded compactly.
+    pub(crate) fn push_action(&mut self, tick: Tick, action: Action) {
+        if let Some(&(last_tick, _)) = self.actions.last() {
+            assert!(
+                last_tick <= tick,
+                "actions are pushed in chronological order"
+            );
+        }
         self.actions.push((tick, action));
     }
 
+    /// Parse a history from JSON, either on its own or inside a `ScoreMessage`.
+    pub fn from_json(json: &str) -> serde_json::Result<History> {
+        /// The formats a game can be saved in.
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum SavedGame {
+            ScoreMessage(ScoreMessage),
+            History(History),
+        }
+
+        Ok(match serde_json::from_str(json)? {
+            SavedGame::ScoreMessage(message) => message.into_history(),
+            SavedGame::History(history) => history,
+        })
+    }
+
+    /// Encode the history in a compact binary format, which is much smaller than JSON.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        compact::encode(self)
+    }
+
+    /// Decode a history from the compact binary format produced by `History::to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<History, DecodeError> {
+        compact::decode(bytes)
+    }
+
+    /// Encode the history in the compact binary format as a base64 string, for sending to the
+    /// server.
+    pub fn to_base64(&self) -> String {
+        compact::encode_base64(self)
+    }
+
+    /// Decode a history from a base64 string produced by `History::to_base64`.
+    pub fn from_base64(data: &str) -> Result<History, DecodeError> {
+        compact::decode_base64(data)
+    }
+
+    /// Get the seed used to initialise the game.
+    pub(crate) fn seed(&self) -> [u32; 4] {
+        self.seed
+    }
+
+    /// Get the rules the game was played with.
+    pub fn config(&self) -> &GameConfig {
+        &self.config
+    }
+
+    /// Get the board the game started from.
+    pub(crate) fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// Get the pieces that were given before any from the seed.
+    pub(crate) fn pieces(&self) -> &[Shape] {
+        &self.pieces
+    }
+
+    /// Return whether the game started from a board or pieces of its own, e.g. in a puzzle.
+    ///
+    /// Games with a custom start can still be replayed, but can't be submitted as a score.
+    pub fn has_custom_start(&self) -> bool {
+        !self.board.is_empty() || !self.pieces.is_empty()
+    }
+
+    /// Get the list of actions and when they occurred.
+    pub(crate) fn actions(&self) -> &[(Tick, Action)] {
+        &self.actions
+    }
+
+    /// Create a copy of this history containing only the first `num_actions` actions.
+    pub(crate) fn prefix(&self, num_actions: usize) -> History {
+        History {
+            seed: self.seed,
+            actions: self.actions[..num_actions].to_vec(),
+            rewound: self.rewound,
+            played_by_bot: self.played_by_bot,
+            config: self.config,
+            board: self.board.clone(),
+            pieces: self.pieces.clone(),
+        }
+    }
+
+    /// Forget every action after the first `num_actions`, marking the history as rewound.
+    pub(crate) fn rewind(&mut self, num_actions: usize) {
+        self.actions.truncate(num_actions);
+        self.rewound = true;
+    }
+
+    /// Mark the history as rewound.
+    pub(crate) fn set_rewound(&mut self) {
+        self.rewound = true;
+    }
+
+    /// Mark the history as played by a bot.
+    pub(crate) fn set_played_by_bot(&mut self) {
+        self.played_by_bot = true;
+    }
+
+    /// Return whether garbage was added to the game, e.g. in versus mode.
+    ///
+    /// Games with garbage can still be replayed, but can't be submitted as a score.
+    pub fn has_garbage(&self) -> bool {
+        self.actions
+            .iter()
+            .any(|(_, action)| matches!(action, Action::Garbage { .. }))
+    }
+
+    /// Return whether the game was rewound in practice mode, or branched from a replay.
+    ///
+    /// Rewound games can still be replayed, but can't be submitted as a score.
+    pub fn is_rewound(&self) -> bool {
+        self.rewound
+    }
+
+    /// Return whether the game was played by a bot rather than a person.
+    ///
+    /// Games played by bots can still be replayed, but can't be submitted as a score.
+    pub fn is_played_by_bot(&self) -> bool {
+        self.played_by_bot
+    }
+
+    /// Replay a game and return statistics about how it was played.
+    pub fn statistics(&self) -> Statistics {
+        self.play_to_end().statistics
+    }
+
     /// Replay a game and return the resulting score.
     pub fn replay(&self) -> u32 {
-        let mut game = GameState::new(self.seed);
+        self.play_to_end().score
+    }
+
+    /// Replay a game until the game over and return the final state.
+    fn play_to_end(&self) -> GameState {
+        let mut game = GameState::start_of(self);
 
         for &(action_tick, action) in &self.actions {
             while game.tick < action_tick {
                 if game.apply_step() == StepResult::GameOver {
-                    return game.score;
+                    return game;
                 }
             }
 
@@ -469,9 +1130,74 @@ impl History {
         }
 
         // after actions stopped, the game will have continued until a game over
-        loop {
-            if game.apply_step() == StepResult::GameOver {
-                return game.score;
+        while game.apply_step() == StepResult::Continue {}
+
+        game
+    }
+}
+
+/// The formats a `History` can be deserialized from.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SerializedHistory {
+    /// The compact format as a base64 string.
+    Compact(String),
+    /// Every action listed in full.
+    Full {
+        seed: [u32; 4],
+        actions: Vec<(Tick, Action)>,
+        #[serde(default)]
+        rewound: bool,
+        #[serde(default)]
+        played_by_bot: bool,
+        #[serde(default)]
+        config: GameConfig,
+        #[serde(default)]
+        board: Vec<String>,
+        #[serde(default)]
+        pieces: String,
+    },
+}
+
+impl TryFrom<SerializedHistory> for History {
+    type Error = DecodeError;
+
+    fn try_from(history: SerializedHistory) -> Result<Self, Self::Error> {
+        match history {
+            SerializedHistory::Compact(data) => History::from_base64(&data),
+            SerializedHistory::Full {
+                seed,
+                actions,
+                rewound,
+                played_by_bot,
+                config,
+                board,
+                pieces,
+            } => {
+                if actions.iter().any(|(_, action)| !action.is_valid()) {
+                    return Err(DecodeError::InvalidGarbage);
+                }
+                if actions.windows(2).any(|pair| pair[0].0 > pair[1].0) {
+                    return Err(DecodeError::ActionsOutOfOrder);
+                }
+                let board = Board::from_rows(&board).map_err(|_| DecodeError::InvalidStart)?;
+                let pieces = pieces
+                    .chars()
+                    .map(|letter| {
+                        ShapeColor::from_letter(letter)
+                            .and_then(Shape::from_color)
+                            .ok_or(DecodeError::InvalidStart)
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(History {
+                    seed,
+                    actions,
+                    rewound,
+                    played_by_bot,
+                    config,
+                    board,
+                    pieces,
+                })
             }
         }
     }
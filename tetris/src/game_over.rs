@@ -5,6 +5,7 @@ use crate::rest;
 use crate::score::Score;
 use crate::score::ScoreMessage;
 use crate::state::State;
+use crate::statistics::Statistics;
 
 /// Game over state, where a user can see high-scores and post their high-score.
 pub struct GameOver {
@@ -16,6 +17,9 @@ pub struct GameOver {
     /// The user's score.
     pub score: Score,
 
+    /// Statistics about how the user played the game.
+    pub statistics: Statistics,
+
     /// The history of the game.
     pub history: History,
 }
@@ -59,8 +63,8 @@ impl HighScores {
 }
 
 impl GameOver {
-    /// Create a new game over state from a user's score and a game history.
-    pub fn new(score: u32, history: History) -> Self {
+    /// Create a new game over state from a user's score, their statistics and a game history.
+    pub fn new(score: u32, statistics: Statistics, history: History) -> Self {
         let hiscores = rest::get_hiscores();
 
         if let Err(ref e) = hiscores {
@@ -74,17 +78,26 @@ impl GameOver {
         GameOver {
             hiscores,
             score,
+            statistics,
             history,
         }
     }
 
     /// Return whether the user can post their high-score.
     ///
-    /// This is true only if the list of high-scores was retrieved and the user has a high-score.
+    /// This is true only if the list of high-scores was retrieved and the user has a high-score,
+    /// and the game wasn't rewound in practice mode or branched from a replay, played by a bot,
+    /// played with custom rules or started from its own board.
     pub fn posting_hiscore(&self) -> bool {
-        self.hiscores
-            .as_ref()
-            .map_or(false, HighScores::has_hiscore)
+        !self.history.is_rewound()
+            && !self.history.is_played_by_bot()
+            && self.history.config().is_default()
+            && !self.history.has_garbage()
+            && !self.history.has_custom_start()
+            && self
+                .hiscores
+                .as_ref()
+                .map_or(false, HighScores::has_hiscore)
     }
 
     /// Delete a character from the entered name.
@@ -121,6 +134,7 @@ impl GameOver {
 }
 
 #[cfg(test)]
+#[allow(clippy::useless_vec)]
 mod tests {
     use super::*;
 
@@ -0,0 +1,133 @@
+use rand::Rng;
+use rand::SeedableRng;
+use rand::XorShiftRng;
+
+use crate::board::Board;
+
+/// How the holes are placed in garbage rows.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum HolePlacement {
+    /// Every row added at once has its hole in the same column, so they can be cleared together.
+    #[default]
+    Clean,
+    /// Like `Clean`, but each row has the given percentage chance of moving its hole to a different
+    /// column from the row before.
+    Messy(u8),
+    /// Every row has its hole in a random column.
+    Random,
+}
+
+/// Picks the holes in garbage rows.
+///
+/// The holes are picked deterministically from a seed, so garbage is the same every time a game
+/// with that seed is played.
+#[derive(Clone, Debug)]
+pub struct GarbageHoles {
+    placement: HolePlacement,
+    rng: XorShiftRng,
+}
+
+impl GarbageHoles {
+    /// Create a new picker that places holes in the given way, determined by a game's seed.
+    pub fn new(seed: [u32; 4], placement: HolePlacement) -> Self {
+        // Scramble the seed so the holes aren't picked by the same sequence as the pieces
+        let hole_seed = seed.map(|part| part.rotate_left(16));
+
+        GarbageHoles {
+            placement,
+            rng: XorShiftRng::from_seed(hole_seed),
+        }
+    }
+
+    /// Pick the holes for a number of garbage rows added at once.
+    ///
+    /// The holes are listed in the order the rows should be added, so the first row ends up at the
+    /// top of the garbage.
+    pub fn pick(&mut self, rows: u32) -> Vec<u8> {
+        let mut holes = Vec::with_capacity(rows as usize);
+        let mut hole = self.random_column();
+
+        for row in 0..rows {
+            if row > 0 && self.moves_hole() {
+                hole = self.other_column(hole);
+            }
+            holes.push(hole);
+        }
+
+        holes
+    }
+
+    /// Return whether the next row should have its hole in a different column from the last.
+    fn moves_hole(&mut self) -> bool {
+        match self.placement {
+            HolePlacement::Clean => false,
+            HolePlacement::Messy(percent) => self.rng.gen_range(0, 100) < percent,
+            HolePlacement::Random => true,
+        }
+    }
+
+    fn other_column(&mut self, hole: u8) -> u8 {
+        match self.placement {
+            HolePlacement::Random => self.random_column(),
+            _ => (hole + self.rng.gen_range(1, Board::WIDTH)) % Board::WIDTH,
+        }
+    }
+
+    fn random_column(&mut self) -> u8 {
+        self.rng.gen_range(0, Board::WIDTH)
+    }
+}
+
+/// Group consecutive garbage rows with their hole in the same column, as `(rows, hole)` pairs.
+pub(crate) fn runs(holes: &[u8]) -> Vec<(u8, u8)> {
+    let mut runs: Vec<(u8, u8)> = Vec::new();
+
+    for &hole in holes {
+        match runs.last_mut() {
+            Some((rows, last_hole)) if *last_hole == hole && *rows < u8::MAX => *rows += 1,
+            _ => runs.push((1, hole)),
+        }
+    }
+
+    runs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED: [u32; 4] = [1, 2, 3, 4];
+
+    #[test]
+    fn clean_garbage_has_one_hole_for_all_rows_added_at_once() {
+        let mut holes = GarbageHoles::new(SEED, HolePlacement::Clean);
+
+        let batch = holes.pick(8);
+
+        assert_eq!(batch.len(), 8);
+        assert!(batch.iter().all(|&hole| hole == batch[0]));
+    }
+
+    #[test]
+    fn fully_messy_garbage_moves_the_hole_on_every_row() {
+        let mut holes = GarbageHoles::new(SEED, HolePlacement::Messy(100));
+
+        let batch = holes.pick(20);
+
+        assert!(batch.windows(2).all(|pair| pair[0] != pair[1]));
+        assert!(batch.iter().all(|&hole| hole < Board::WIDTH));
+    }
+
+    #[test]
+    fn holes_are_the_same_for_the_same_seed() {
+        let mut first = GarbageHoles::new(SEED, HolePlacement::Random);
+        let mut second = GarbageHoles::new(SEED, HolePlacement::Random);
+
+        assert_eq!(first.pick(20), second.pick(20));
+    }
+
+    #[test]
+    fn runs_group_rows_with_the_same_hole() {
+        assert_eq!(runs(&[3, 3, 5, 3]), vec![(2, 3), (1, 5), (1, 3)]);
+    }
+}
@@ -27,6 +27,35 @@
 //!             game_over.push_name("BOB");
 //!             game_over.submit()
 //!         }
+//!         Replay(replay) => {
+//!             replay.exit()
+//!         }
+//!         PuzzleOver(puzzle_over) => {
+//!             puzzle_over.retry()
+//!         }
+//!         Versus(ref mut versus) => {
+//!             // Both players press keys in their own game
+//!             versus.game_mut(0).move_right();
+//!             versus.game_mut(1).rotate();
+//!             state
+//!         }
+//!         VersusOver(versus_over) => {
+//!             versus_over.rematch()
+//!         }
+//!         DigOver(dig_over) => {
+//!             dig_over.retry()
+//!         }
+//!         Lobby(lobby) => {
+//!             // Stop waiting for an opponent
+//!             lobby.exit()
+//!         }
+//!         Online(ref mut online) => {
+//!             online.game_mut().rotate();
+//!             state
+//!         }
+//!         OnlineOver(online_over) => {
+//!             online_over.exit()
+//!         }
 //!     };
 //!
 //!     // Update the state of the game by one tick
@@ -40,25 +69,71 @@
 
 #![deny(missing_docs)]
 
-pub use self::board::Board;
-pub use self::game::Game;
+pub use self::ai::{Ai, Weights};
+pub use self::analysis::{Analysis, TSlot};
+pub use self::board::{Board, ParseBoardError};
+pub use self::compact::DecodeError;
+pub use self::config::GameConfig;
+pub use self::controller::{Controller, Player};
+pub use self::cpu::{Cpu, Difficulty};
+pub use self::dig::{
+    DigOver, DigRace, DigTime, DigTimeMessage, DigValidationError, DIG_LEADERBOARD_SIZE,
+    DIG_TIME_ENDPOINT,
+};
+pub use self::environment::{
+    ActionSpace, Environment, EnvironmentError, FrameAction, Info, Observation,
+};
+pub use self::finesse::Finesse;
+pub use self::fumen::{Fumen, FumenError, FumenPage};
+pub use self::game::{Game, GarbageError, History, StepResult};
 pub use self::game_over::{GameOver, HighScores};
+pub use self::garbage::{GarbageHoles, HolePlacement};
+pub use self::online::{
+    ActionBatch, ClientMessage, Lobby, MatchResult, Online, OnlineOver, OnlineRecord,
+    OnlineValidationError, ServerMessage,
+};
 pub use self::piece::Piece;
+pub use self::placement::{Move, Placement};
 pub use self::pos::Pos;
+pub use self::puzzle::{Objective, Puzzle, PuzzleOver};
+pub use self::replay::{Replay, ReplayPlayer};
 pub use self::score::{Score, ScoreMessage, ScoreValidationError, SCORE_ENDPOINT};
 pub use self::shape::{Rotation, Shape, ShapeColor};
 pub use self::state::{Paused, State, Title};
+pub use self::statistics::Statistics;
+pub use self::tbp::{BotInfo, BotProcess, TbpBot, TbpError};
+pub use self::versus::{AttackTable, Versus, VersusOver};
 
 #[macro_use]
 mod macros;
 
+mod ai;
+mod analysis;
 mod args;
 mod board;
+mod compact;
+mod config;
+mod controller;
+mod cpu;
+mod dig;
+mod environment;
+mod finesse;
+mod fumen;
 mod game;
 mod game_over;
+mod garbage;
+mod online;
 mod piece;
+mod placement;
 mod pos;
+mod practice;
+mod puzzle;
+mod replay;
 mod rest;
 mod score;
 mod shape;
+mod socket;
 mod state;
+mod statistics;
+mod tbp;
+mod versus;
@@ -0,0 +1,768 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::board::Board;
+use crate::game::Action;
+use crate::game::Game;
+use crate::game::History;
+use crate::game::StepResult;
+use crate::game::Tick;
+use crate::replay::ReplayPlayer;
+use crate::socket::Connecting;
+use crate::socket::Socket;
+use crate::state::State;
+use crate::versus;
+use crate::versus::AttackTable;
+
+/// The address of the online match server.
+pub(crate) const SERVER_URL: &str = "ws://tetris.ael.red:8001";
+
+/// The most frames between batches of actions sent to the server.
+const BATCH_INTERVAL: u32 = 10;
+
+/// Actions a player took in their game, sent so their opponent can follow the game as it's played.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ActionBatch {
+    /// The frame the player's game had reached when the batch was sent.
+    tick: Tick,
+
+    /// The actions taken since the last batch, and when they occurred.
+    actions: Vec<(Tick, Action)>,
+
+    /// The garbage rows the player had received from the server when the batch was sent.
+    garbage_received: u32,
+}
+
+impl ActionBatch {
+    /// Create a batch of every action in the game after the first `actions_sent`.
+    fn since(game: &Game, actions_sent: usize, garbage_received: u32) -> Self {
+        ActionBatch {
+            tick: game.tick(),
+            actions: game.history().actions()[actions_sent..].to_vec(),
+            garbage_received,
+        }
+    }
+
+    /// Check the batch carries on from the given frame: every action can happen in a game, and
+    /// they're in order, from no earlier than that frame up to the frame the batch was sent.
+    fn check(&self, since: Tick) -> Result<(), OnlineValidationError> {
+        let mut last_tick = since;
+
+        for &(action_tick, action) in &self.actions {
+            if action_tick < last_tick || action_tick > self.tick {
+                return Err(OnlineValidationError::OutOfOrder);
+            }
+            if !action.is_valid() {
+                return Err(OnlineValidationError::InvalidAction);
+            }
+            last_tick = action_tick;
+        }
+
+        if self.tick < since {
+            return Err(OnlineValidationError::OutOfOrder);
+        }
+
+        Ok(())
+    }
+}
+
+/// A message sent from a player to the server during an online match.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum ClientMessage {
+    /// The actions the player took since their last message
+    Actions(ActionBatch),
+    /// Garbage rows sent to the opponent for clearing lines, after cancelling incoming garbage
+    Attack(u32),
+    /// The player's game topped out
+    ToppedOut,
+}
+
+/// A message sent from the server to a player in an online match.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum ServerMessage {
+    /// The player is waiting in the lobby for an opponent
+    Waiting,
+    /// An opponent was found, and the match starts with both games using the given seed
+    Start([u32; 4]),
+    /// The actions the opponent took, to follow their game
+    Opponent(ActionBatch),
+    /// Garbage sent by the opponent, as the hole in each row
+    Garbage(Vec<u8>),
+    /// The match is over
+    Over(MatchResult),
+}
+
+/// How an online match ended for a player.
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum MatchResult {
+    /// The opponent topped out, left the match or was caught cheating
+    Won,
+    /// The player topped out or was caught cheating
+    Lost,
+    /// The connection to the server was lost before the match was over
+    Disconnected,
+}
+
+impl Display for MatchResult {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            MatchResult::Won => write!(f, "You Win"),
+            MatchResult::Lost => write!(f, "You Lose"),
+            MatchResult::Disconnected => write!(f, "Disconnected"),
+        }
+    }
+}
+
+/// One player's side of an online match, kept by the server to check they're playing fairly.
+///
+/// The player's game is replayed from the actions they send as each batch arrives, so the garbage
+/// and attacks they report can be checked against it at any time.
+pub struct OnlineRecord {
+    /// The player's game, replayed as far as they've sent it.
+    player: ReplayPlayer,
+
+    attack_table: AttackTable,
+
+    /// The hole of every garbage row sent to the player, in the order it was sent.
+    garbage: Vec<u8>,
+
+    /// The garbage rows the player had received, as of their last batch of actions.
+    received: u32,
+
+    /// The garbage rows the player had certainly received when they last locked a piece without
+    /// clearing lines, which adds every incoming row to the board.
+    received_at_lock: u32,
+
+    /// How many of the rows sent have been matched against garbage added to the game.
+    garbage_matched: usize,
+
+    /// The garbage rows added to the player's game.
+    rows_added: u32,
+
+    /// The garbage rows the player's line clears were worth, before cancelling incoming garbage.
+    generated: u32,
+
+    /// The garbage rows the player says they sent to their opponent.
+    attack: u32,
+
+    /// Why the game replayed so far can't have been played fairly, if it can't.
+    fault: Option<OnlineValidationError>,
+}
+
+/// Reasons a player's side of an online match is rejected by the server.
+#[derive(Copy, Clone, Debug)]
+pub enum OnlineValidationError {
+    /// A batch went back in time from the player's last batch
+    OutOfOrder,
+    /// An action couldn't happen in a game
+    InvalidAction,
+    /// Game topped out without the player saying so
+    ToppedOut,
+    /// More garbage was sent than the player's line clears allow
+    TooMuchAttack,
+    /// Garbage was added to the game that the server never sent
+    UnsentGarbage,
+    /// Garbage was dropped without clearing lines to cancel it
+    DroppedGarbage,
+}
+
+impl Error for OnlineValidationError {}
+
+impl Display for OnlineValidationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            OnlineValidationError::OutOfOrder => write!(f, "Actions were sent out of order"),
+            OnlineValidationError::InvalidAction => {
+                write!(f, "Actions couldn't have happened in a game")
+            }
+            OnlineValidationError::ToppedOut => {
+                write!(f, "Game topped out without the player saying so")
+            }
+            OnlineValidationError::TooMuchAttack => {
+                write!(f, "More garbage was sent than the line clears allow")
+            }
+            OnlineValidationError::UnsentGarbage => {
+                write!(f, "Game had garbage added that was never sent")
+            }
+            OnlineValidationError::DroppedGarbage => {
+                write!(f, "Garbage was dropped without clearing lines to cancel it")
+            }
+        }
+    }
+}
+
+impl OnlineRecord {
+    /// Start recording a player's game in a match using the given seed and attack table.
+    pub fn new(seed: [u32; 4], attack_table: AttackTable) -> Self {
+        OnlineRecord {
+            player: ReplayPlayer::new(History::new(seed)),
+            attack_table,
+            garbage: Vec::new(),
+            received: 0,
+            received_at_lock: 0,
+            garbage_matched: 0,
+            rows_added: 0,
+            generated: 0,
+            attack: 0,
+            fault: None,
+        }
+    }
+
+    /// Get the history of the player's game, as far as it's been sent.
+    pub fn history(&self) -> &History {
+        self.player.history()
+    }
+
+    /// Add a batch of actions sent by the player to their history, and replay their game up to
+    /// the frame it had reached.
+    ///
+    /// The batch is rejected if it goes back in time, contains an action that can't happen, or
+    /// says the player received more garbage than they were sent.
+    pub fn push_actions(&mut self, batch: &ActionBatch) -> Result<(), OnlineValidationError> {
+        let tick = Tick::from(self.player.tick());
+        batch.check(tick)?;
+
+        if batch.garbage_received < self.received {
+            return Err(OnlineValidationError::OutOfOrder);
+        }
+        if batch.garbage_received as usize > self.garbage.len() {
+            return Err(OnlineValidationError::UnsentGarbage);
+        }
+
+        for &(_, action) in &batch.actions {
+            if let Action::Garbage { rows, hole } = action {
+                self.match_garbage(rows, hole, batch.garbage_received);
+            }
+        }
+
+        self.player.follow(&batch.actions, tick);
+        while self.player.tick() < batch.tick.value() {
+            if self.player.step() == StepResult::GameOver {
+                self.reject(OnlineValidationError::ToppedOut);
+                break;
+            }
+            if let Some(lock) = self.player.last_lock() {
+                let attack = self.attack_table.attack(lock);
+                self.generated = self.generated.saturating_add(attack);
+
+                // Rows received since the last batch may have arrived after this lock
+                if lock.lines_cleared == 0 {
+                    self.received_at_lock = self.received;
+                }
+            }
+        }
+
+        self.received = batch.garbage_received;
+        Ok(())
+    }
+
+    /// Record garbage rows sent to the player, as the hole in each row.
+    pub fn push_garbage(&mut self, holes: &[u8]) {
+        self.garbage.extend(holes);
+    }
+
+    /// Record garbage rows the player says they sent to their opponent.
+    pub fn push_attack(&mut self, rows: u32) {
+        self.attack = self.attack.saturating_add(rows);
+    }
+
+    /// Check the player's game is still going, and that the garbage they received and the attacks
+    /// they sent match how the game was played.
+    ///
+    /// Incoming garbage can be cancelled by clearing lines, so the player may add fewer garbage
+    /// rows than they received, but never any others. Every row they didn't add must have been
+    /// cancelled by attack they didn't send, so they may send less than their line clears are
+    /// worth, but never more.
+    pub fn validate(&self) -> Result<(), OnlineValidationError> {
+        if let Some(fault) = self.fault {
+            return Err(fault);
+        }
+
+        let cancelled = match self.generated.checked_sub(self.attack) {
+            Some(cancelled) => cancelled,
+            None => return Err(OnlineValidationError::TooMuchAttack),
+        };
+
+        if self.received_at_lock.saturating_sub(self.rows_added) > cancelled {
+            return Err(OnlineValidationError::DroppedGarbage);
+        }
+
+        Ok(())
+    }
+
+    /// Match garbage added to the player's game against the rows they received, in order.
+    ///
+    /// Received rows may be skipped, because they could have been cancelled.
+    fn match_garbage(&mut self, rows: u8, hole: u8, received: u32) {
+        let received = &self.garbage[..received as usize];
+
+        for _ in 0..rows {
+            let unmatched = received.get(self.garbage_matched..).unwrap_or_default();
+            match unmatched.iter().position(|&sent_hole| sent_hole == hole) {
+                Some(skipped) => self.garbage_matched += skipped + 1,
+                None => return self.reject(OnlineValidationError::UnsentGarbage),
+            }
+        }
+
+        self.rows_added = self.rows_added.saturating_add(u32::from(rows));
+    }
+
+    /// Remember the first reason the game can't have been played fairly.
+    fn reject(&mut self, fault: OnlineValidationError) {
+        self.fault.get_or_insert(fault);
+    }
+}
+
+/// Lobby state, waiting for the server to find an opponent for an online match.
+pub struct Lobby {
+    connection: Connection,
+}
+
+/// The lobby's connection to the server.
+enum Connection {
+    /// Still connecting, in the background so the game keeps running.
+    Connecting(Connecting),
+    Connected(Box<Socket>),
+    /// The connection couldn't be made or was lost, and why.
+    Failed(String),
+}
+
+impl Lobby {
+    /// Start connecting to the match server at the given `ws://` URL, to wait for an opponent.
+    pub fn connect(url: &str) -> Self {
+        Lobby {
+            connection: Connection::Connecting(Socket::connect(url)),
+        }
+    }
+
+    /// Get why the lobby couldn't connect to the server, or lost its connection.
+    pub fn error(&self) -> Option<&str> {
+        match &self.connection {
+            Connection::Failed(error) => Some(error),
+            _ => None,
+        }
+    }
+
+    /// Check if the lobby has connected, and if the server has found an opponent.
+    ///
+    /// Consumes the lobby and returns the new state, which is an online match once an opponent
+    /// has been found.
+    pub fn update(mut self) -> State {
+        if let Connection::Connecting(connecting) = &mut self.connection {
+            self.connection = match connecting.poll() {
+                Some(Ok(socket)) => Connection::Connected(Box::new(socket)),
+                Some(Err(error)) => Connection::Failed(error),
+                None => return State::Lobby(self),
+            };
+        }
+
+        let socket = match &mut self.connection {
+            Connection::Connected(socket) => socket,
+            _ => return State::Lobby(self),
+        };
+
+        loop {
+            match socket.receive() {
+                Ok(Some(ServerMessage::Start(seed))) => match self.connection {
+                    Connection::Connected(socket) => {
+                        return State::Online(Online::new(seed, *socket))
+                    }
+                    _ => unreachable!("the lobby is connected"),
+                },
+                Ok(Some(_)) => {}
+                Ok(None) => return State::Lobby(self),
+                Err(e) => {
+                    self.connection = Connection::Failed(e.to_string());
+                    return State::Lobby(self);
+                }
+            }
+        }
+    }
+
+    /// Leave the lobby and return to the title screen.
+    pub fn exit(self) -> State {
+        State::title()
+    }
+}
+
+/// An online match against another player, where clearing lines sends garbage to the opponent.
+///
+/// Only the player's own game is played here. Their actions are sent to the server, which passes
+/// them on to the opponent, and the opponent's game is followed from the actions passed back.
+/// Garbage works the same way as in versus mode, except the holes are picked by the server.
+///
+/// The match ends when the server says so, after either player tops out or leaves.
+pub struct Online {
+    game: Game,
+
+    /// The opponent's game, followed from the actions they send.
+    opponent: ReplayPlayer,
+
+    /// The hole of each garbage row waiting for the player, oldest first.
+    incoming: VecDeque<u8>,
+
+    attack_table: AttackTable,
+
+    socket: Socket,
+
+    /// How many of the game's actions have been sent to the server.
+    actions_sent: usize,
+
+    /// The garbage rows received from the server.
+    garbage_received: u32,
+
+    /// The garbage rows received when the last batch of actions was sent.
+    garbage_reported: u32,
+
+    /// The frame the game had reached when the last batch of actions was sent.
+    batch_sent_at: u32,
+
+    /// Whether the player has topped out, so is only waiting for the server to end the match.
+    topped_out: bool,
+}
+
+impl Online {
+    fn new(seed: [u32; 4], socket: Socket) -> Self {
+        Online {
+            game: Game::from_seed(seed),
+            opponent: ReplayPlayer::new(History::new(seed)),
+            incoming: VecDeque::new(),
+            attack_table: AttackTable::default(),
+            socket,
+            actions_sent: 0,
+            garbage_received: 0,
+            garbage_reported: 0,
+            batch_sent_at: 0,
+            topped_out: false,
+        }
+    }
+
+    /// Get the player's game.
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    /// Get the player's game, to press keys in it.
+    pub fn game_mut(&mut self) -> &mut Game {
+        &mut self.game
+    }
+
+    /// Get the opponent's game, as far as it's been followed.
+    pub fn opponent(&self) -> &ReplayPlayer {
+        &self.opponent
+    }
+
+    /// Get the number of garbage rows waiting to be added to the player's board.
+    pub fn incoming_garbage(&self) -> u32 {
+        self.incoming.len() as u32
+    }
+
+    /// Follow the opponent's game and advance the player's game one frame, exchanging garbage
+    /// through the server.
+    ///
+    /// Consumes the match and returns the new state, which is an "online over" state once the
+    /// server ends the match or the connection is lost.
+    pub fn update(mut self) -> State {
+        loop {
+            match self.socket.receive() {
+                Ok(Some(ServerMessage::Opponent(batch))) => {
+                    // The opponent's replay stops at their game over, which may be before their
+                    // last action
+                    let tick = Tick::from(self.opponent.tick());
+                    let since = match self.opponent.history().actions().last() {
+                        Some(&(last_tick, _)) if last_tick > tick => last_tick,
+                        _ => tick,
+                    };
+                    if let Err(e) = batch.check(since) {
+                        return self.disconnect(
+                            format!("Server sent invalid opponent actions: {}", e).into(),
+                        );
+                    }
+                    self.opponent.follow(&batch.actions, batch.tick);
+                }
+                Ok(Some(ServerMessage::Garbage(holes))) => {
+                    if holes.iter().any(|&hole| hole >= Board::WIDTH) {
+                        return self.disconnect("Server sent garbage outside the board".into());
+                    }
+                    self.garbage_received += holes.len() as u32;
+                    self.incoming.extend(holes);
+                }
+                Ok(Some(ServerMessage::Over(result))) => return self.finish(result),
+                Ok(Some(_)) => {}
+                Ok(None) => break,
+                Err(e) => return self.disconnect(e),
+            }
+        }
+
+        if self.topped_out {
+            return State::Online(self);
+        }
+
+        let mut attack = 0;
+        if self.game.step() == StepResult::GameOver {
+            self.topped_out = true;
+        } else {
+            match versus::exchange_garbage(&mut self.game, &mut self.incoming, &self.attack_table) {
+                Some(rows) => attack = rows,
+                None => self.topped_out = true,
+            }
+        }
+
+        match self.send(attack) {
+            Ok(()) => State::Online(self),
+            Err(e) => self.disconnect(e),
+        }
+    }
+
+    /// Send the server any actions taken or garbage received since the last batch, and any
+    /// attack or top out.
+    ///
+    /// A batch is also sent every few frames without any, so the opponent sees pieces fall.
+    fn send(&mut self, attack: u32) -> Result<(), Box<dyn Error>> {
+        let batch = ActionBatch::since(&self.game, self.actions_sent, self.garbage_received);
+        let batch_due = batch.tick.value() - self.batch_sent_at >= BATCH_INTERVAL;
+
+        if !batch.actions.is_empty()
+            || batch.garbage_received != self.garbage_reported
+            || batch_due
+            || attack > 0
+            || self.topped_out
+        {
+            self.actions_sent += batch.actions.len();
+            self.garbage_reported = batch.garbage_received;
+            self.batch_sent_at = batch.tick.value();
+            self.socket.send(&ClientMessage::Actions(batch))?;
+        }
+
+        if attack > 0 {
+            self.socket.send(&ClientMessage::Attack(attack))?;
+        }
+        if self.topped_out {
+            self.socket.send(&ClientMessage::ToppedOut)?;
+        }
+        Ok(())
+    }
+
+    fn disconnect(self, error: Box<dyn Error>) -> State {
+        println!("Lost connection to the server: {}", error);
+        self.finish(MatchResult::Disconnected)
+    }
+
+    fn finish(self, result: MatchResult) -> State {
+        State::OnlineOver(OnlineOver {
+            game: self.game,
+            opponent: self.opponent,
+            result,
+        })
+    }
+}
+
+/// Online over state, where the player can see how the match ended.
+pub struct OnlineOver {
+    game: Game,
+    opponent: ReplayPlayer,
+    result: MatchResult,
+}
+
+impl OnlineOver {
+    /// Get how the match ended for the player.
+    pub fn result(&self) -> MatchResult {
+        self.result
+    }
+
+    /// Get the player's game as it ended.
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    /// Get the opponent's game, as far as it was followed.
+    pub fn opponent(&self) -> &ReplayPlayer {
+        &self.opponent
+    }
+
+    /// Return to the lobby to find another opponent.
+    pub fn rematch(self) -> State {
+        State::online()
+    }
+
+    /// Return to the title screen.
+    pub fn exit(self) -> State {
+        State::title()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ai::Ai;
+    use crate::ai::Weights;
+    use crate::controller::Player;
+
+    use super::*;
+
+    const SEED: [u32; 4] = [1, 2, 3, 4];
+
+    /// Let the AI play a game for the given number of frames.
+    fn play(frames: u32) -> Game {
+        let mut game = Game::from_seed(SEED);
+        let mut player = Player::new(Ai::new(Weights::default(), 0));
+
+        for _ in 0..frames {
+            player.play(&mut game);
+            game.step();
+        }
+
+        game
+    }
+
+    fn batch(tick: u32, garbage_received: u32, actions: &[(u32, Action)]) -> ActionBatch {
+        ActionBatch {
+            tick: Tick::from(tick),
+            actions: actions
+                .iter()
+                .map(|&(tick, action)| (Tick::from(tick), action))
+                .collect(),
+            garbage_received,
+        }
+    }
+
+    #[test]
+    fn following_actions_plays_the_opponents_game() {
+        let game = play(300);
+        let batch = ActionBatch::since(&game, 0, 0);
+
+        let mut opponent = ReplayPlayer::new(History::new(SEED));
+        opponent.follow(&batch.actions, batch.tick);
+
+        assert_eq!(opponent.tick(), 300);
+        assert_eq!(opponent.board(), game.board());
+        assert_eq!(opponent.piece(), game.piece());
+    }
+
+    #[test]
+    fn a_fairly_played_game_is_valid() {
+        let game = play(300);
+        let mut record = OnlineRecord::new(SEED, AttackTable::default());
+
+        record
+            .push_actions(&ActionBatch::since(&game, 0, 0))
+            .unwrap();
+
+        assert!(record.validate().is_ok());
+        assert_eq!(record.history().actions(), game.history().actions());
+    }
+
+    #[test]
+    fn actions_from_before_the_last_batch_are_rejected() {
+        let mut record = OnlineRecord::new(SEED, AttackTable::default());
+        record.push_actions(&batch(10, 0, &[])).unwrap();
+
+        let result = record.push_actions(&batch(12, 0, &[(5, Action::Rotate)]));
+
+        assert!(matches!(result, Err(OnlineValidationError::OutOfOrder)));
+        assert!(record.history().actions().is_empty());
+    }
+
+    #[test]
+    fn a_batch_with_actions_out_of_order_is_rejected() {
+        let batch = batch(12, 0, &[(11, Action::Rotate), (10, Action::MoveLeft)]);
+
+        assert!(matches!(
+            batch.check(Tick::from(0)),
+            Err(OnlineValidationError::OutOfOrder)
+        ));
+    }
+
+    #[test]
+    fn garbage_with_a_hole_outside_the_board_is_rejected() {
+        let mut record = OnlineRecord::new(SEED, AttackTable::default());
+
+        let garbage = Action::Garbage { rows: 1, hole: 10 };
+        let result = record.push_actions(&batch(1, 0, &[(1, garbage)]));
+
+        assert!(matches!(result, Err(OnlineValidationError::InvalidAction)));
+    }
+
+    #[test]
+    fn garbage_can_be_cancelled_but_not_made_up() {
+        let mut record = OnlineRecord::new(SEED, AttackTable::default());
+        record.push_garbage(&[3, 3, 5]);
+        let garbage = Action::Garbage { rows: 1, hole: 5 };
+        record.push_actions(&batch(1, 3, &[(1, garbage)])).unwrap();
+
+        assert!(record.validate().is_ok());
+
+        let garbage = Action::Garbage { rows: 1, hole: 3 };
+        record.push_actions(&batch(2, 3, &[(2, garbage)])).unwrap();
+
+        assert!(matches!(
+            record.validate(),
+            Err(OnlineValidationError::UnsentGarbage)
+        ));
+    }
+
+    #[test]
+    fn attacking_without_clearing_lines_is_rejected() {
+        let mut record = OnlineRecord::new(SEED, AttackTable::default());
+        record.push_actions(&batch(60, 0, &[])).unwrap();
+        record.push_attack(4);
+
+        assert!(matches!(
+            record.validate(),
+            Err(OnlineValidationError::TooMuchAttack)
+        ));
+    }
+
+    #[test]
+    fn a_game_that_topped_out_is_rejected() {
+        let mut game = Game::from_seed(SEED);
+        while game.step() == StepResult::Continue {
+            game.start_hard_drop();
+        }
+        let mut record = OnlineRecord::new(SEED, AttackTable::default());
+
+        record
+            .push_actions(&ActionBatch::since(&game, 0, 0))
+            .unwrap();
+
+        assert!(matches!(
+            record.validate(),
+            Err(OnlineValidationError::ToppedOut)
+        ));
+    }
+
+    #[test]
+    fn dropping_garbage_without_clearing_lines_is_rejected() {
+        let mut game = Game::from_seed(SEED);
+        game.start_hard_drop();
+        while game.pieces_placed() == 0 {
+            game.step();
+        }
+        let mut record = OnlineRecord::new(SEED, AttackTable::default());
+        record.push_garbage(&[3]);
+        record.push_actions(&batch(0, 1, &[])).unwrap();
+
+        record
+            .push_actions(&ActionBatch::since(&game, 0, 1))
+            .unwrap();
+
+        assert!(matches!(
+            record.validate(),
+            Err(OnlineValidationError::DroppedGarbage)
+        ));
+    }
+
+    #[test]
+    fn claiming_garbage_that_was_never_sent_is_rejected() {
+        let mut record = OnlineRecord::new(SEED, AttackTable::default());
+        record.push_garbage(&[3]);
+
+        let result = record.push_actions(&batch(1, 2, &[]));
+
+        assert!(matches!(result, Err(OnlineValidationError::UnsentGarbage)));
+    }
+}
@@ -10,7 +10,7 @@ const INITIAL_X: i16 = Board::WIDTH as i16 / 2 - 2;
 const INITIAL_POS: Pos = Pos::new(INITIAL_X, 0);
 
 /// A tetromino piece in play.
-#[derive(Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub struct Piece {
     /// The shape of the piece.
     pub shape: Shape,
@@ -32,6 +32,39 @@ impl Piece {
         }
     }
 
+    /// Find the position and rotation of the given shape that is made up of exactly the given
+    /// blocks, in any order.
+    pub(crate) fn from_blocks(shape: Shape, blocks: &[Pos]) -> Option<Piece> {
+        let mut blocks = blocks.to_vec();
+        blocks.sort_by_key(|pos| (pos.y(), pos.x()));
+
+        let mut piece = Piece::new(shape);
+
+        for _ in 0..4 {
+            let shape_blocks = shape.block_list(piece.rot);
+
+            if !blocks.is_empty() && shape_blocks.len() == blocks.len() {
+                let offset = Pos::new(
+                    blocks[0].x() - shape_blocks[0].x(),
+                    blocks[0].y() - shape_blocks[0].y(),
+                );
+
+                if shape_blocks
+                    .iter()
+                    .zip(&blocks)
+                    .all(|(&block, &pos)| block + offset == pos)
+                {
+                    piece.pos = offset;
+                    return Some(piece);
+                }
+            }
+
+            piece.rotate_clockwise();
+        }
+
+        None
+    }
+
     /// Rotate the piece clockwise.
     pub fn rotate_clockwise(&mut self) {
         self.rot = self.rot.clockwise();
@@ -62,12 +95,17 @@ impl Piece {
         self.pos = self.pos.down();
     }
 
-    /// Get all the blocks that make up the piece at the current position and rotation.
-    pub fn blocks(&self) -> Vec<Pos> {
-        self.shape
-            .blocks(self.rot)
-            .iter()
-            .map(|pos| *pos + self.pos)
-            .collect()
+    /// Get all the blocks that make up the piece at the current position and rotation, ordered
+    /// from top to bottom and then left to right.
+    pub fn blocks(&self) -> impl ExactSizeIterator<Item = Pos> + Clone {
+        let pos = self.pos;
+        self.shape.blocks(self.rot).map(move |block| block + pos)
+    }
+
+    /// Get the top-left and bottom-right corners of the smallest box containing every block of
+    /// the piece.
+    pub fn bounding_box(&self) -> (Pos, Pos) {
+        let (min, max) = self.shape.bounding_box(self.rot);
+        (min + self.pos, max + self.pos)
     }
 }
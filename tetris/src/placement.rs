@@ -0,0 +1,325 @@
+use std::collections::HashSet;
+use std::collections::VecDeque;
+
+use crate::board::Board;
+use crate::piece::Piece;
+use crate::pos::Pos;
+use crate::shape::Blocks;
+use crate::shape::Rotation;
+use crate::shape::Shape;
+
+/// A single input that moves a piece while it falls.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Move {
+    /// Move the piece one space to the left.
+    Left,
+    /// Move the piece one space to the right.
+    Right,
+    /// Rotate the piece clockwise, with the game's wall-kick.
+    Rotate,
+    /// Drop the piece down one space.
+    Down,
+}
+
+/// A place a piece can come to rest on the board, and the moves to get it there.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Placement {
+    /// The piece at its final position and rotation, resting on the board or the floor.
+    pub piece: Piece,
+
+    /// The moves from where the piece started to its final position.
+    ///
+    /// Moves happen in order, so a placement that can be reached with a hard drop has every
+    /// `Move::Down` at the end.
+    pub moves: Vec<Move>,
+}
+
+impl Placement {
+    /// Find every place the given shape can come to rest on the board, starting from where the
+    /// piece spawns.
+    ///
+    /// This searches every combination of moves, rotations and drops, so includes tucks and spins
+    /// as well as placements reachable with a hard drop. Each placement has the fewest moves
+    /// needed to reach it. Placements that cover the same blocks, such as an O piece at different
+    /// rotations, are only returned once.
+    ///
+    /// Returns nothing if the piece can't spawn.
+    pub fn find_all(board: &Board, shape: Shape) -> Vec<Placement> {
+        Placement::find_all_from(board, Piece::new(shape))
+    }
+
+    /// Find every place the given piece can come to rest on the board, starting from the piece's
+    /// current position, e.g. after it has already fallen part of the way.
+    ///
+    /// Returns nothing if the piece overlaps the board.
+    pub fn find_all_from(board: &Board, start: Piece) -> Vec<Placement> {
+        if board.overlaps(&start) {
+            return Vec::new();
+        }
+
+        // Every piece position found so far, with the index of the position it was reached from
+        let mut found: Vec<(Piece, Option<(usize, Move)>)> = vec![(start.clone(), None)];
+        let mut visited: HashSet<(Pos, Rotation)> = HashSet::new();
+        visited.insert((start.pos, start.rot));
+
+        let mut queue = VecDeque::new();
+        queue.push_back(0);
+
+        let mut placed_blocks: HashSet<Blocks> = HashSet::new();
+        let mut placements = Vec::new();
+
+        while let Some(index) = queue.pop_front() {
+            let piece = found[index].0.clone();
+
+            for &mv in &[Move::Left, Move::Right, Move::Rotate, Move::Down] {
+                if let Some(next) = apply_move(board, &piece, mv) {
+                    if visited.insert((next.pos, next.rot)) {
+                        found.push((next, Some((index, mv))));
+                        queue.push_back(found.len() - 1);
+                    }
+                }
+            }
+
+            if apply_move(board, &piece, Move::Down).is_none()
+                && placed_blocks.insert(sorted_blocks(&piece))
+            {
+                placements.push(Placement {
+                    piece,
+                    moves: path_to(&found, index),
+                });
+            }
+        }
+
+        placements
+    }
+}
+
+/// Follow the moves back from the piece at the given index to where it started.
+fn path_to(found: &[(Piece, Option<(usize, Move)>)], mut index: usize) -> Vec<Move> {
+    let mut moves = Vec::new();
+
+    while let Some((parent, mv)) = found[index].1 {
+        moves.push(mv);
+        index = parent;
+    }
+
+    moves.reverse();
+    moves
+}
+
+/// Apply a move to the piece, the same way the game would, if it doesn't overlap the board.
+pub(crate) fn apply_move(board: &Board, piece: &Piece, mv: Move) -> Option<Piece> {
+    match mv {
+        Move::Left => shift(board, piece, Pos::new(-1, 0)),
+        Move::Right => shift(board, piece, Pos::new(1, 0)),
+        Move::Rotate => rotate(board, piece),
+        Move::Down => shift(board, piece, Pos::new(0, 1)),
+    }
+}
+
+/// Drop the piece as far as it can go.
+pub(crate) fn hard_drop(board: &Board, piece: &Piece) -> Piece {
+    let mut dropped = piece.clone();
+    dropped.pos = dropped.pos + Pos::new(0, board.drop_distance(piece));
+    dropped
+}
+
+/// Get the piece's blocks in a consistent order, to compare pieces by the cells they cover.
+pub(crate) fn sorted_blocks(piece: &Piece) -> Blocks {
+    // A shape's blocks are already sorted from top to bottom and then left to right
+    let mut blocks = piece.shape.block_list(piece.rot);
+    for block in blocks.iter_mut() {
+        *block = *block + piece.pos;
+    }
+    blocks
+}
+
+/// Move the piece by the given offset, if it doesn't overlap the board.
+fn shift(board: &Board, piece: &Piece, offset: Pos) -> Option<Piece> {
+    let mut moved = piece.clone();
+    moved.pos = moved.pos + offset;
+    if board.overlaps(&moved) {
+        None
+    } else {
+        Some(moved)
+    }
+}
+
+/// Rotate the piece clockwise with the same naive wall-kick as the game: first trying one space
+/// to the right, then one space to the left.
+fn rotate(board: &Board, piece: &Piece) -> Option<Piece> {
+    let mut rotated = piece.clone();
+    rotated.rotate_clockwise();
+
+    if board.overlaps(&rotated) {
+        shift(board, &rotated, Pos::new(1, 0)).or_else(|| shift(board, &rotated, Pos::new(-1, 0)))
+    } else {
+        Some(rotated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::Game;
+    use crate::shape::ShapeColor;
+    use crate::state::State;
+
+    use super::*;
+
+    fn shape(color: ShapeColor) -> Shape {
+        Shape::from_color(color).unwrap()
+    }
+
+    fn find(placements: &[Placement], board: &Board, piece: &Piece) -> Placement {
+        placements
+            .iter()
+            .find(|placement| sorted_blocks(&placement.piece) == sorted_blocks(piece))
+            .unwrap_or_else(|| panic!("no placement for\n{}", board.to_string_with_piece(piece)))
+            .clone()
+    }
+
+    #[test]
+    fn an_o_has_one_placement_per_column_on_an_empty_board() {
+        let placements = Placement::find_all(&Board::default(), shape(ShapeColor::O));
+
+        assert_eq!(placements.len(), 9);
+    }
+
+    #[test]
+    fn a_t_has_a_placement_for_every_rotation_and_column_on_an_empty_board() {
+        let placements = Placement::find_all(&Board::default(), shape(ShapeColor::T));
+
+        // Flat rotations fit in 8 columns and upright rotations in 9
+        assert_eq!(placements.len(), 8 + 9 + 8 + 9);
+    }
+
+    #[test]
+    fn every_placement_rests_on_something() {
+        let board: Board = "
+            ..........
+            ....X.....
+            XX..XX..XX
+        "
+        .parse()
+        .unwrap();
+
+        for color in &[ShapeColor::I, ShapeColor::S, ShapeColor::L] {
+            for placement in Placement::find_all(&board, shape(*color)) {
+                assert!(!board.overlaps(&placement.piece));
+                assert!(apply_move(&board, &placement.piece, Move::Down).is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn following_the_moves_reaches_the_placement() {
+        let board: Board = "
+            XXX.......
+            X.........
+            X...XXXXXX
+        "
+        .parse()
+        .unwrap();
+
+        for placement in Placement::find_all(&board, shape(ShapeColor::J)) {
+            let mut piece = Piece::new(shape(ShapeColor::J));
+            for &mv in &placement.moves {
+                piece = apply_move(&board, &piece, mv).unwrap();
+            }
+
+            assert_eq!(piece, placement.piece);
+        }
+    }
+
+    #[test]
+    fn a_tuck_under_an_overhang_is_found() {
+        let (board, piece) = Board::parse_with_piece(
+            "
+            XXXX......
+            ...oo.....
+            ...oo.....
+            ",
+        )
+        .unwrap();
+        let piece = piece.unwrap();
+
+        let placement = find(&Placement::find_all(&board, piece.shape), &board, &piece);
+
+        assert_eq!(placement.moves.last(), Some(&Move::Left));
+    }
+
+    #[test]
+    fn a_t_spin_is_found() {
+        let (board, piece) = Board::parse_with_piece(
+            "
+            XX........
+            XtttXXXXXX
+            XXtXXXXXXX
+            ",
+        )
+        .unwrap();
+        let piece = piece.unwrap();
+
+        let placement = find(&Placement::find_all(&board, piece.shape), &board, &piece);
+
+        assert_eq!(placement.moves.last(), Some(&Move::Rotate));
+    }
+
+    #[test]
+    fn a_placement_reached_with_a_hard_drop_moves_before_dropping() {
+        let placements = Placement::find_all(&Board::default(), shape(ShapeColor::O));
+
+        for placement in placements {
+            let first_drop = placement
+                .moves
+                .iter()
+                .position(|&mv| mv == Move::Down)
+                .unwrap();
+            assert!(placement.moves[first_drop..]
+                .iter()
+                .all(|&mv| mv == Move::Down));
+        }
+    }
+
+    #[test]
+    fn nothing_can_be_placed_if_the_piece_cannot_spawn() {
+        let mut board = Board::default();
+        for x in 0..Board::WIDTH as i16 {
+            board.fill_pos(Pos::new(x, 1), ShapeColor::Garbage);
+        }
+
+        assert!(Placement::find_all(&board, shape(ShapeColor::T)).is_empty());
+    }
+
+    #[test]
+    fn a_placement_found_by_the_search_can_be_played_in_a_game() {
+        let game = Game::default();
+        let placements = Placement::find_all(game.board(), game.piece().shape);
+        let target = placements.last().unwrap().clone();
+
+        let mut game = game;
+        for &mv in &target.moves {
+            match mv {
+                Move::Left => game.move_left(),
+                Move::Right => game.move_right(),
+                Move::Rotate => game.rotate(),
+                Move::Down => break,
+            }
+        }
+        game.start_hard_drop();
+
+        let mut state = State::Play(game);
+        let game = loop {
+            state = state.update();
+            match state {
+                State::Play(ref game) if game.pieces_placed() > 0 => break game,
+                State::Play(_) => {}
+                _ => panic!("expected game to continue"),
+            }
+        };
+
+        let mut expected = Board::default();
+        expected.lock_piece(&target.piece);
+        assert_eq!(game.board(), &expected);
+    }
+}
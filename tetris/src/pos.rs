@@ -1,7 +1,7 @@
 use std::ops::Add;
 
 /// A 2D integer position or vector.
-#[derive(Copy, Clone, PartialEq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub struct Pos {
     x: i16,
     y: i16,
@@ -68,6 +68,7 @@ impl Add for Pos {
 }
 
 #[cfg(test)]
+#[allow(clippy::nonminimal_bool)]
 mod tests {
     use quickcheck::{quickcheck, Arbitrary, Gen};
 
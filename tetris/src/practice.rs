@@ -0,0 +1,250 @@
+use crate::game::GameState;
+
+/// How often, in frames, a practice game is snapshotted so it can be rewound - at 60fps this is
+/// every second.
+const SNAPSHOT_INTERVAL: u32 = 60;
+
+/// How far back, in frames, every snapshot is kept so the game can be rewound - at 60fps this is
+/// a minute. Older snapshots are only kept if they're needed to undo a placement.
+const REWIND_WINDOW: u32 = 60 * 60;
+
+/// How many of the latest placements can be undone.
+const UNDO_LIMIT: u32 = 100;
+
+/// Snapshots of a practice game, so the player can undo piece placements and rewind time.
+#[derive(Default)]
+pub(crate) struct Practice {
+    /// Snapshots in chronological order.
+    snapshots: Vec<Snapshot>,
+}
+
+/// A copy of a game at a point in time.
+#[derive(Clone)]
+pub(crate) struct Snapshot {
+    pub(crate) game_state: GameState,
+
+    /// The number of actions that had been recorded in the game's history at this point.
+    pub(crate) num_actions: usize,
+
+    /// Whether the snapshot was taken when a new piece appeared, so it can be undone to.
+    new_piece: bool,
+}
+
+impl Practice {
+    /// Take a snapshot of the game if one is due, forgetting snapshots that are too old to need.
+    ///
+    /// Snapshots are taken whenever a new piece appears, and every `SNAPSHOT_INTERVAL` frames.
+    pub(crate) fn record(&mut self, game_state: &GameState, num_actions: usize) {
+        let new_piece = match self.snapshots.last() {
+            Some(last) => last.game_state.pieces_placed != game_state.pieces_placed,
+            None => true,
+        };
+
+        if new_piece || game_state.tick.value() % SNAPSHOT_INTERVAL == 0 {
+            self.snapshots.push(Snapshot {
+                game_state: game_state.clone(),
+                num_actions,
+                new_piece,
+            });
+            self.forget_old(game_state);
+        }
+    }
+
+    /// Forget snapshots from before the rewind window, unless they can still be undone to.
+    fn forget_old(&mut self, game_state: &GameState) {
+        let tick = game_state.tick.value();
+        let pieces_placed = game_state.pieces_placed;
+
+        self.snapshots.retain(|snapshot| {
+            let age = tick - snapshot.game_state.tick.value();
+            let pieces_since = pieces_placed - snapshot.game_state.pieces_placed;
+            age <= REWIND_WINDOW || (snapshot.new_piece && pieces_since <= UNDO_LIMIT)
+        });
+    }
+
+    /// Get the snapshot from when the last placed piece appeared, discarding any later snapshots.
+    ///
+    /// If no pieces have been placed, there's nothing to undo.
+    pub(crate) fn undo(&mut self, game_state: &GameState) -> Option<Snapshot> {
+        let pieces_placed = game_state.pieces_placed.checked_sub(1)?;
+
+        let index = self
+            .snapshots
+            .iter()
+            .position(|snapshot| snapshot.game_state.pieces_placed == pieces_placed)?;
+
+        self.restore(index)
+    }
+
+    /// Get the latest snapshot taken at or before the given frame, discarding any later snapshots.
+    pub(crate) fn rewind(&mut self, tick: u32) -> Option<Snapshot> {
+        let index = self
+            .snapshots
+            .iter()
+            .rposition(|snapshot| snapshot.game_state.tick.value() <= tick)?;
+
+        self.restore(index)
+    }
+
+    fn restore(&mut self, index: usize) -> Option<Snapshot> {
+        self.snapshots.truncate(index + 1);
+        self.snapshots.last().cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::Game;
+    use crate::game::GameState;
+    use crate::game::History;
+    use crate::game::Tick;
+    use crate::replay::ReplayPlayer;
+    use crate::state::State;
+    use crate::Board;
+
+    use super::*;
+
+    /// Update the game until the given condition is true.
+    fn update_until(game: Game, condition: impl Fn(&Game) -> bool) -> Game {
+        let mut state = State::Play(game);
+
+        loop {
+            state = state.update();
+            match state {
+                State::Play(ref game) if condition(game) => break,
+                State::Play(_) => {}
+                _ => panic!("expected game to continue"),
+            }
+        }
+
+        match state {
+            State::Play(game) => game,
+            _ => unreachable!(),
+        }
+    }
+
+    fn hard_drop(mut game: Game) -> Game {
+        let board = game.board().clone();
+        game.start_hard_drop();
+        update_until(game, |game| *game.board() != board)
+    }
+
+    #[test]
+    fn undoing_a_placement_removes_the_piece() {
+        let game = Game::practice();
+        let piece = game.piece().clone();
+
+        let mut game = hard_drop(game);
+        game.undo();
+
+        assert_eq!(*game.board(), Board::default());
+        assert_eq!(*game.piece(), piece);
+    }
+
+    #[test]
+    fn undoing_twice_removes_two_pieces() {
+        let game = Game::practice();
+        let game = hard_drop(game);
+        let board = game.board().clone();
+        let piece = game.piece().clone();
+
+        let mut game = hard_drop(game);
+        game = hard_drop(game);
+        game.undo();
+        game.undo();
+
+        assert_eq!(*game.board(), board);
+        assert_eq!(*game.piece(), piece);
+    }
+
+    #[test]
+    fn rewinding_past_the_start_returns_to_the_start() {
+        let game = Game::practice();
+        let mut game = hard_drop(game);
+
+        game.rewind(1000);
+
+        assert_eq!(*game.board(), Board::default());
+    }
+
+    #[test]
+    fn an_undone_game_is_rewound() {
+        let game = Game::practice();
+        let mut game = hard_drop(game);
+
+        assert!(!game.history().is_rewound());
+        game.undo();
+        assert!(game.history().is_rewound());
+    }
+
+    #[test]
+    fn undoing_before_placing_a_piece_does_nothing() {
+        let mut game = Game::practice();
+
+        game.undo();
+
+        assert!(!game.history().is_rewound());
+    }
+
+    #[test]
+    fn a_practice_game_from_a_board_can_be_undone_back_to_the_board() {
+        let board: Board = "XXXX..XXXX".parse().unwrap();
+        let game = Game::practice_with_board(board.clone());
+        let mut game = hard_drop(game);
+
+        game.undo();
+
+        assert_eq!(*game.board(), board);
+    }
+
+    #[test]
+    fn a_practice_game_from_a_board_cannot_be_submitted() {
+        let board: Board = "XXXX..XXXX".parse().unwrap();
+        let game = Game::practice_with_board(board);
+
+        assert!(game.history().has_custom_start());
+    }
+
+    #[test]
+    fn a_practice_game_from_a_board_is_replayed_from_the_board() {
+        let board: Board = "XXXX..XXXX".parse().unwrap();
+        let game = Game::practice_with_board(board);
+        let game = hard_drop(game);
+
+        let mut replay = ReplayPlayer::new(game.history().clone());
+        replay.seek(game.tick().value());
+
+        assert_eq!(replay.board(), game.board());
+    }
+
+    #[test]
+    fn a_normal_game_cannot_be_undone() {
+        let game = Game::default();
+        let mut game = hard_drop(game);
+        let board = game.board().clone();
+
+        game.undo();
+
+        assert_eq!(*game.board(), board);
+        assert!(!game.history().is_rewound());
+    }
+
+    #[test]
+    fn a_long_practice_game_only_keeps_recent_snapshots() {
+        let mut practice = Practice::default();
+        let mut game_state = GameState::start_of(&History::new([1, 2, 3, 4]));
+
+        for tick in 0..100_000 {
+            game_state.tick = Tick::from(tick);
+            game_state.pieces_placed = tick / 30;
+            practice.record(&game_state, 0);
+        }
+
+        let window = REWIND_WINDOW / 30 + REWIND_WINDOW / SNAPSHOT_INTERVAL;
+        assert!(practice.snapshots.len() <= (window + UNDO_LIMIT) as usize + 2);
+        for _ in 0..UNDO_LIMIT {
+            let snapshot = practice.undo(&game_state).unwrap();
+            game_state = snapshot.game_state;
+        }
+    }
+}
@@ -0,0 +1,469 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use serde_derive::Deserialize;
+
+use crate::board::Board;
+use crate::fumen::Fumen;
+use crate::fumen::FumenError;
+use crate::game::GameState;
+use crate::game::StepResult;
+use crate::shape::Shape;
+use crate::shape::ShapeColor;
+use crate::state::State;
+
+/// A puzzle, where the player starts from a predefined board with a fixed sequence of pieces and
+/// must complete an objective.
+///
+/// Puzzles are loaded from JSON like so:
+/// ```json
+/// {
+///   "name": "T-Spin Double",
+///   "board": [
+///     "XX........",
+///     "X...XXXXXX",
+///     "XX.XXXXXXX"
+///   ],
+///   "pieces": "T",
+///   "objective": {"TSpin": 2}
+/// }
+/// ```
+///
+/// The board rows are aligned to the bottom of the board, where `.` is an empty cell and a letter
+/// is a filled cell, e.g. `T` for a T shape or `X` for garbage. The board can also be given as a
+/// fumen string, in which case the board from the first page is used.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(try_from = "SerializedPuzzle")]
+pub struct Puzzle {
+    name: String,
+    board: Board,
+    pieces: Vec<Shape>,
+    objective: Objective,
+}
+
+/// The goal of a puzzle.
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum Objective {
+    /// Clear at least this many lines
+    ClearLines(u32),
+    /// Clear at least this many lines with a single T-spin
+    TSpin(u32),
+    /// Clear every block from the board, using at most this many pieces
+    PerfectClear(u32),
+}
+
+impl Display for Objective {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Objective::ClearLines(1) => write!(f, "Clear 1 line"),
+            Objective::ClearLines(lines) => write!(f, "Clear {} lines", lines),
+            Objective::TSpin(1) => write!(f, "Clear a line with a T-spin"),
+            Objective::TSpin(lines) => write!(f, "Clear {} lines with a T-spin", lines),
+            Objective::PerfectClear(1) => write!(f, "Perfect clear with 1 piece"),
+            Objective::PerfectClear(pieces) => write!(f, "Perfect clear with {} pieces", pieces),
+        }
+    }
+}
+
+/// How far the player has got with a puzzle.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub(crate) enum Progress {
+    /// The objective hasn't been completed yet, but still can be.
+    Unsolved,
+    /// The objective has been completed.
+    Solved,
+    /// The objective can no longer be completed.
+    Failed,
+}
+
+impl Puzzle {
+    /// Parse a puzzle from JSON.
+    pub fn from_json(json: &str) -> serde_json::Result<Puzzle> {
+        serde_json::from_str(json)
+    }
+
+    /// Get the name of the puzzle.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the board the puzzle starts from.
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// Get the shapes the player is given, in order.
+    pub fn pieces(&self) -> Vec<Shape> {
+        self.pieces.clone()
+    }
+
+    /// Get the goal of the puzzle.
+    pub fn objective(&self) -> Objective {
+        self.objective
+    }
+
+    /// Check the progress of a game of this puzzle, after the given step.
+    pub(crate) fn progress(&self, game_state: &GameState, step_result: StepResult) -> Progress {
+        let solved = match self.objective {
+            Objective::ClearLines(lines) => game_state.lines_cleared >= lines,
+            Objective::TSpin(lines) => game_state
+                .last_lock
+                .as_ref()
+                .map_or(false, |lock| lock.t_spin && lock.lines_cleared >= lines),
+            Objective::PerfectClear(_) => game_state
+                .last_lock
+                .as_ref()
+                .map_or(false, |lock| lock.perfect_clear),
+        };
+
+        let max_pieces = match self.objective {
+            Objective::PerfectClear(pieces) => pieces.min(self.pieces.len() as u32),
+            _ => self.pieces.len() as u32,
+        };
+
+        if solved {
+            Progress::Solved
+        } else if step_result == StepResult::GameOver || game_state.pieces_placed >= max_pieces {
+            Progress::Failed
+        } else {
+            Progress::Unsolved
+        }
+    }
+}
+
+/// A puzzle as written in a puzzle file.
+#[derive(Deserialize)]
+struct SerializedPuzzle {
+    name: String,
+    board: SerializedBoard,
+    pieces: String,
+    objective: Objective,
+}
+
+/// The formats a puzzle's board can be written in.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SerializedBoard {
+    /// Rows of cells in the text format from `Board`'s `Display`.
+    Rows(Vec<String>),
+    /// A fumen string.
+    Fumen(String),
+}
+
+impl TryFrom<SerializedPuzzle> for Puzzle {
+    type Error = String;
+
+    fn try_from(puzzle: SerializedPuzzle) -> Result<Self, Self::Error> {
+        let board = match puzzle.board {
+            SerializedBoard::Rows(rows) => Board::from_rows(&rows).map_err(|e| e.to_string())?,
+            SerializedBoard::Fumen(fumen) => {
+                let fumen: Fumen = fumen.parse().map_err(|e: FumenError| e.to_string())?;
+                fumen.pages()[0].board.clone()
+            }
+        };
+
+        let pieces = puzzle
+            .pieces
+            .chars()
+            .map(|letter| {
+                ShapeColor::from_letter(letter)
+                    .and_then(Shape::from_color)
+                    .ok_or_else(|| format!("Unknown piece {:?}", letter))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if pieces.is_empty() {
+            return Err("Puzzle must have at least one piece".to_string());
+        }
+
+        Ok(Puzzle {
+            name: puzzle.name,
+            board,
+            pieces,
+            objective: puzzle.objective,
+        })
+    }
+}
+
+/// Puzzle over state, where the user can see whether they solved the puzzle.
+pub struct PuzzleOver {
+    puzzle: Puzzle,
+    solved: bool,
+}
+
+impl PuzzleOver {
+    /// Create a new puzzle over state for a puzzle that was either solved or failed.
+    pub fn new(puzzle: Puzzle, solved: bool) -> Self {
+        PuzzleOver { puzzle, solved }
+    }
+
+    /// Get the puzzle that was played.
+    pub fn puzzle(&self) -> &Puzzle {
+        &self.puzzle
+    }
+
+    /// Return whether the puzzle was solved.
+    pub fn is_solved(&self) -> bool {
+        self.solved
+    }
+
+    /// Play the same puzzle again from the start.
+    pub fn retry(self) -> State {
+        State::puzzle(self.puzzle)
+    }
+
+    /// Exit the puzzle and return to the title screen.
+    pub fn exit(self) -> State {
+        State::title()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::Game;
+    use crate::game::History;
+    use crate::replay::ReplayPlayer;
+
+    use super::*;
+
+    fn load(json: &str) -> Puzzle {
+        Puzzle::from_json(json).unwrap()
+    }
+
+    fn t_spin_double() -> Puzzle {
+        load(include_str!("../../resources/puzzles/t-spin-double.json"))
+    }
+
+    fn perfect_clear() -> Puzzle {
+        load(include_str!("../../resources/puzzles/perfect-clear.json"))
+    }
+
+    fn tetris() -> Puzzle {
+        load(include_str!("../../resources/puzzles/tetris.json"))
+    }
+
+    /// Update the game until the puzzle is over, or until the given condition is true.
+    fn update_until(game: Game, condition: impl Fn(&Game) -> bool) -> State {
+        let mut state = State::Play(game);
+
+        loop {
+            state = state.update();
+            match state {
+                State::Play(ref game) if condition(game) => return state,
+                State::Play(_) => {}
+                _ => return state,
+            }
+        }
+    }
+
+    fn is_solved(state: State) -> bool {
+        match state {
+            State::PuzzleOver(puzzle_over) => puzzle_over.is_solved(),
+            _ => panic!("expected puzzle to be over"),
+        }
+    }
+
+    #[test]
+    fn the_bundled_puzzles_can_be_loaded() {
+        perfect_clear();
+        t_spin_double();
+        tetris();
+    }
+
+    #[test]
+    fn a_puzzle_starts_from_the_puzzle_board_and_pieces() {
+        let puzzle = t_spin_double();
+        let game = Game::puzzle(puzzle.clone());
+
+        assert_eq!(game.board(), puzzle.board());
+        assert_eq!(game.piece().shape, puzzle.pieces()[0]);
+    }
+
+    /// Play the bundled perfect clear puzzle, dropping an L at the bottom right of the gap and then
+    /// an upside down L over it, returning the game at the moment the second L is dropped.
+    fn play_perfect_clear() -> Game {
+        let mut game = Game::puzzle(perfect_clear());
+        for _ in 0..Board::WIDTH {
+            game.move_left();
+        }
+        game.move_right();
+        game.start_hard_drop();
+
+        let mut game = match update_until(game, |game| game.pieces_placed() == 1) {
+            State::Play(game) => game,
+            _ => panic!("expected game to continue"),
+        };
+        game.stop_drop();
+        game.rotate();
+        game.rotate();
+        for _ in 0..Board::WIDTH {
+            game.move_left();
+        }
+        game.start_hard_drop();
+        game
+    }
+
+    #[test]
+    fn clearing_the_board_solves_a_perfect_clear_puzzle() {
+        let game = play_perfect_clear();
+
+        assert!(is_solved(update_until(game, |_| false)));
+    }
+
+    #[test]
+    fn a_solved_puzzle_can_be_replayed() {
+        let mut game = play_perfect_clear();
+        while game.pieces_placed() < 2 {
+            game.step();
+        }
+
+        let mut replay = ReplayPlayer::new(game.history().clone());
+        replay.seek(game.tick().value());
+
+        assert!(replay.board().is_empty());
+        assert_eq!(replay.board(), game.board());
+        assert_eq!(replay.lines_cleared(), 2);
+    }
+
+    #[test]
+    fn a_puzzle_history_keeps_its_board_and_pieces_when_saved() {
+        let game = Game::puzzle(t_spin_double());
+        let json = serde_json::to_string(game.history()).unwrap();
+        let history = History::from_json(&json).unwrap();
+        let compact = History::from_bytes(&game.history().to_bytes()).unwrap();
+
+        for history in &[history, compact] {
+            assert_eq!(history.board(), game.board());
+            assert_eq!(history.pieces(), &t_spin_double().pieces()[..]);
+        }
+    }
+
+    #[test]
+    fn dropping_an_i_into_a_well_solves_a_tetris_puzzle() {
+        let mut game = Game::puzzle(tetris());
+        game.rotate();
+        for _ in 0..4 {
+            game.move_right();
+        }
+        game.start_hard_drop();
+
+        assert!(is_solved(update_until(game, |_| false)));
+    }
+
+    #[test]
+    fn running_out_of_pieces_fails_a_puzzle() {
+        let mut game = Game::puzzle(tetris());
+        game.start_hard_drop();
+
+        assert!(!is_solved(update_until(game, |_| false)));
+    }
+
+    #[test]
+    fn spinning_a_t_into_a_slot_solves_a_t_spin_puzzle() {
+        let mut game = Game::puzzle(t_spin_double());
+        game.rotate();
+        game.move_left();
+        game.move_left();
+        game.start_soft_drop();
+
+        let state = update_until(game, |game| game.piece().pos.y() == 20);
+        let mut game = match state {
+            State::Play(game) => game,
+            _ => panic!("expected game to continue"),
+        };
+        game.rotate();
+
+        assert!(is_solved(update_until(game, |_| false)));
+    }
+
+    #[test]
+    fn a_t_spin_single_does_not_solve_a_t_spin_double_puzzle() {
+        let puzzle = load(
+            r#"{
+                "name": "T-Spin Single",
+                "board": ["XX........", "X...XXXXXX", "XX.XXXXXX."],
+                "pieces": "T",
+                "objective": {"TSpin": 2}
+            }"#,
+        );
+
+        let mut game = Game::puzzle(puzzle);
+        game.rotate();
+        game.move_left();
+        game.move_left();
+        game.start_soft_drop();
+
+        let state = update_until(game, |game| game.piece().pos.y() == 20);
+        let mut game = match state {
+            State::Play(game) => game,
+            _ => panic!("expected game to continue"),
+        };
+        game.rotate();
+
+        assert!(!is_solved(update_until(game, |_| false)));
+    }
+
+    #[test]
+    fn dropping_a_t_into_a_slot_without_spinning_is_not_a_t_spin() {
+        let puzzle = load(
+            r#"{
+                "name": "Not a T-Spin",
+                "board": ["X...XXXXXX"],
+                "pieces": "T",
+                "objective": {"TSpin": 1}
+            }"#,
+        );
+
+        let mut game = Game::puzzle(puzzle);
+        game.rotate();
+        game.rotate();
+        game.move_left();
+        game.move_left();
+        game.start_hard_drop();
+
+        assert!(!is_solved(update_until(game, |_| false)));
+    }
+
+    #[test]
+    fn a_puzzle_board_can_be_a_fumen() {
+        let puzzle = load(
+            r#"{
+                "name": "Fumen",
+                "board": "v115@9gF8DeF8DeF8DeF8NeAgH",
+                "pieces": "O",
+                "objective": {"ClearLines": 1}
+            }"#,
+        );
+
+        let board: Board = "
+            XXXXXX....
+            XXXXXX....
+            XXXXXX....
+            XXXXXX....
+        "
+        .parse()
+        .unwrap();
+
+        assert_eq!(puzzle.board(), &board);
+    }
+
+    #[test]
+    fn an_unknown_piece_is_an_error() {
+        let result = Puzzle::from_json(
+            r#"{"name": "Bad", "board": [], "pieces": "TQ", "objective": {"TSpin": 1}}"#,
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn a_board_row_of_the_wrong_width_is_an_error() {
+        let result = Puzzle::from_json(
+            r#"{"name": "Bad", "board": ["XX"], "pieces": "T", "objective": {"TSpin": 1}}"#,
+        );
+
+        assert!(result.is_err());
+    }
+}
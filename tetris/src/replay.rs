@@ -0,0 +1,557 @@
+use crate::board::Board;
+use crate::fumen::Fumen;
+use crate::game::Action;
+use crate::game::Game;
+use crate::game::GameState;
+use crate::game::History;
+use crate::game::Lock;
+use crate::game::StepResult;
+use crate::game::Tick;
+use crate::piece::Piece;
+use crate::shape::Shape;
+use crate::state::State;
+use crate::statistics::Statistics;
+
+/// How often, in frames, a copy of the game is kept while replaying - at 60fps this is every ten
+/// seconds.
+///
+/// Seeking backwards restarts from the closest earlier keyframe instead of the start of the game.
+const KEYFRAME_INTERVAL: u32 = 600;
+
+/// Plays back a recorded `History` one frame at a time.
+///
+/// The board, piece and score can be inspected at every frame, and the player can seek to any
+/// frame of the game.
+pub struct ReplayPlayer {
+    history: History,
+    game_state: Box<GameState>,
+
+    /// Index of the next action in the history that has not been applied yet.
+    next_action: usize,
+
+    /// Whether the replayed game has reached a game over.
+    finished: bool,
+
+    /// Copies of the game taken every `KEYFRAME_INTERVAL` frames, in chronological order.
+    keyframes: Vec<Keyframe>,
+}
+
+/// A copy of the replay at a point in time, used to quickly seek backwards.
+#[derive(Clone)]
+struct Keyframe {
+    game_state: GameState,
+    next_action: usize,
+}
+
+impl ReplayPlayer {
+    /// Create a new player, positioned at the start of the given history.
+    pub fn new(history: History) -> Self {
+        let game_state = GameState::start_of(&history);
+
+        let keyframes = vec![Keyframe {
+            game_state: game_state.clone(),
+            next_action: 0,
+        }];
+
+        ReplayPlayer {
+            history,
+            game_state: Box::new(game_state),
+            next_action: 0,
+            finished: false,
+            keyframes,
+        }
+    }
+
+    /// Get the history being replayed.
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// Get the current frame of the replay, counted from the start of the game.
+    pub fn tick(&self) -> u32 {
+        self.game_state.tick.value()
+    }
+
+    /// Get the current piece that the player was placing.
+    pub fn piece(&self) -> &Piece {
+        &self.game_state.piece
+    }
+
+    /// Get the board, made up of blocks from old pieces.
+    pub fn board(&self) -> &Board {
+        &self.game_state.board
+    }
+
+    /// Get the next shape that will be played.
+    pub fn next_shape(&self) -> Shape {
+        self.game_state.bag.peek()
+    }
+
+    /// Get the number of lines that have been cleared.
+    pub fn lines_cleared(&self) -> u32 {
+        self.game_state.lines_cleared
+    }
+
+    /// Get the player's score.
+    pub fn score(&self) -> u32 {
+        self.game_state.score
+    }
+
+    /// Get the number of pieces that have been locked onto the board.
+    pub fn pieces_placed(&self) -> u32 {
+        self.game_state.pieces_placed
+    }
+
+    /// Get statistics about how the game was played up to the current frame.
+    pub fn statistics(&self) -> &Statistics {
+        &self.game_state.statistics
+    }
+
+    /// Get what happened when a piece locked on the current frame, if one did.
+    pub(crate) fn last_lock(&self) -> Option<&Lock> {
+        self.game_state.last_lock.as_ref()
+    }
+
+    /// Get the current frame as a fumen, to share the position.
+    pub fn to_fumen(&self) -> Fumen {
+        Fumen::new(self.board().clone(), Some(self.piece().clone()))
+    }
+
+    /// Return whether the replay has reached the game over.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Advance the replay one frame, applying any actions the player took on this frame.
+    ///
+    /// Once the replay has finished, this does nothing and always returns a game over.
+    pub fn step(&mut self) -> StepResult {
+        if self.finished {
+            return StepResult::GameOver;
+        }
+
+        let actions = self.history.actions();
+
+        while let Some(&(action_tick, action)) = actions.get(self.next_action) {
+            if action_tick > self.game_state.tick {
+                break;
+            }
+            self.game_state.apply_action(action);
+            self.next_action += 1;
+        }
+
+        let step_result = self.game_state.apply_step();
+
+        if step_result == StepResult::GameOver {
+            self.finished = true;
+        } else {
+            self.record_keyframe();
+        }
+
+        step_result
+    }
+
+    /// Move the replay to the given frame.
+    ///
+    /// If the game ends before that frame, the replay stops at the game over.
+    pub fn seek(&mut self, tick: u32) {
+        if tick < self.tick() {
+            let keyframe = self
+                .keyframes
+                .iter()
+                .rev()
+                .find(|keyframe| keyframe.game_state.tick.value() <= tick)
+                .expect("the first keyframe is at the start of the game")
+                .clone();
+
+            *self.game_state = keyframe.game_state;
+            self.next_action = keyframe.next_action;
+            self.finished = false;
+        }
+
+        while self.tick() < tick && !self.finished {
+            self.step();
+        }
+    }
+
+    /// Start a new live game from the current frame of the replay.
+    ///
+    /// The new game's history contains every action from the replay up to this frame, and the
+    /// player's actions are recorded after them, so the branched game can itself be replayed.
+    /// The history is marked as rewound, because the start of the game may not be the player's,
+    /// so it can't be submitted as a score.
+    pub fn branch(&self) -> Game {
+        let mut history = self.history.prefix(self.next_action);
+        history.set_rewound();
+        let mut game = Game::resume((*self.game_state).clone(), history);
+
+        // The player takes over with no keys held, so release any drop from the replay
+        game.stop_drop();
+
+        game
+    }
+
+    /// Add actions the player has just taken to the end of the history, then play up to the frame
+    /// their game has reached, to follow a game while it's being played somewhere else.
+    pub(crate) fn follow(&mut self, actions: &[(Tick, Action)], tick: Tick) {
+        for &(action_tick, action) in actions {
+            self.history.push_action(action_tick, action);
+        }
+        self.seek(tick.value());
+    }
+
+    /// Advance the replay until the game over.
+    pub fn play_to_end(&mut self) {
+        while self.step() == StepResult::Continue {}
+    }
+
+    /// Keep a copy of the game if a keyframe is due and hasn't already been recorded.
+    fn record_keyframe(&mut self) {
+        let tick = self.tick();
+
+        let last_keyframe_tick = self
+            .keyframes
+            .last()
+            .map_or(0, |keyframe| keyframe.game_state.tick.value());
+
+        if tick % KEYFRAME_INTERVAL == 0 && tick > last_keyframe_tick {
+            self.keyframes.push(Keyframe {
+                game_state: (*self.game_state).clone(),
+                next_action: self.next_action,
+            });
+        }
+    }
+}
+
+/// Playback speeds for the replay screen, measured in quarter-frames per update.
+const SPEEDS: [u32; 6] = [1, 2, 4, 8, 16, 32];
+
+/// Index into `SPEEDS` of normal speed, where the replay plays one frame per update.
+const NORMAL_SPEED: usize = 2;
+
+/// How many quarter-frames make up one frame of the replay.
+const QUARTERS_PER_FRAME: u32 = 4;
+
+/// The replay screen, where a user can watch a recorded game.
+pub struct Replay {
+    player: ReplayPlayer,
+
+    /// Whether playback is paused.
+    paused: bool,
+
+    /// Index into `SPEEDS` of the current playback speed.
+    speed: usize,
+
+    /// How far playback has progressed towards the next frame, in quarter-frames.
+    frame_progress: u32,
+}
+
+impl Replay {
+    /// Create a replay screen for the given history, starting from the beginning of the game.
+    pub fn new(history: History) -> Self {
+        Replay {
+            player: ReplayPlayer::new(history),
+            paused: false,
+            speed: NORMAL_SPEED,
+            frame_progress: 0,
+        }
+    }
+
+    /// Get the player for the replayed game, to inspect the current frame.
+    pub fn player(&self) -> &ReplayPlayer {
+        &self.player
+    }
+
+    /// Return whether playback is paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Get the playback speed as a multiple of normal speed.
+    pub fn speed(&self) -> f32 {
+        SPEEDS[self.speed] as f32 / QUARTERS_PER_FRAME as f32
+    }
+
+    /// Pause playback if it is playing, or resume it if it is paused.
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    /// Pause playback and advance exactly one frame.
+    pub fn step_frame(&mut self) {
+        self.paused = true;
+        self.player.step();
+    }
+
+    /// Increase the playback speed, up to a maximum.
+    pub fn speed_up(&mut self) {
+        self.speed = (self.speed + 1).min(SPEEDS.len() - 1);
+    }
+
+    /// Decrease the playback speed, down to a minimum.
+    pub fn slow_down(&mut self) {
+        self.speed = self.speed.saturating_sub(1);
+    }
+
+    /// Move playback to the given frame.
+    pub fn seek(&mut self, tick: u32) {
+        self.player.seek(tick);
+    }
+
+    /// Move playback forwards or backwards by the given number of frames.
+    pub fn skip(&mut self, frames: i32) {
+        let tick = self.player.tick() as i64 + i64::from(frames);
+        self.player.seek(tick.max(0) as u32);
+    }
+
+    /// Take control of the replayed game from the current frame, continuing it as a live game.
+    pub fn take_over(self) -> State {
+        State::live(self.player.branch())
+    }
+
+    /// Exit the replay and return to the title screen.
+    pub fn exit(self) -> State {
+        State::title()
+    }
+
+    /// Advance playback according to the current speed, unless paused.
+    pub fn update(mut self) -> State {
+        if !self.paused {
+            self.frame_progress += SPEEDS[self.speed];
+
+            while self.frame_progress >= QUARTERS_PER_FRAME {
+                self.frame_progress -= QUARTERS_PER_FRAME;
+                self.player.step();
+            }
+        }
+
+        State::Replay(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use crate::config::GameConfig;
+    use crate::game::GarbageError;
+    use crate::game_over::GameOver;
+    use crate::game_over::HighScores;
+    use crate::score::Score;
+    use crate::score::ScoreMessage;
+    use crate::score::ScoreValidationError;
+
+    use super::*;
+
+    fn short_game() -> History {
+        let body = include_str!("../../resources/games/short.json");
+        let message: Value = serde_json::from_str(body).unwrap();
+        serde_json::from_value(message["history"].clone()).unwrap()
+    }
+
+    #[test]
+    fn playing_to_the_end_gives_the_final_score() {
+        let mut player = ReplayPlayer::new(short_game());
+        player.play_to_end();
+
+        assert!(player.is_finished());
+        assert_eq!(player.score(), 1700);
+    }
+
+    #[test]
+    fn seeking_forwards_is_the_same_as_stepping() {
+        let mut stepped = ReplayPlayer::new(short_game());
+        for _ in 0..1000 {
+            stepped.step();
+        }
+
+        let mut seeked = ReplayPlayer::new(short_game());
+        seeked.seek(1000);
+
+        assert_eq!(seeked.tick(), 1000);
+        assert_eq!(seeked.board(), stepped.board());
+        assert_eq!(seeked.piece(), stepped.piece());
+    }
+
+    #[test]
+    fn seeking_backwards_is_the_same_as_stepping() {
+        let mut stepped = ReplayPlayer::new(short_game());
+        for _ in 0..1000 {
+            stepped.step();
+        }
+
+        let mut seeked = ReplayPlayer::new(short_game());
+        seeked.play_to_end();
+        seeked.seek(1000);
+
+        assert_eq!(seeked.tick(), 1000);
+        assert_eq!(seeked.board(), stepped.board());
+        assert_eq!(seeked.piece(), stepped.piece());
+        assert_eq!(seeked.score(), stepped.score());
+    }
+
+    #[test]
+    fn a_replay_at_normal_speed_advances_one_frame_per_update() {
+        let mut state = State::Replay(Replay::new(short_game()));
+        for _ in 0..10 {
+            state = state.update();
+        }
+
+        match state {
+            State::Replay(replay) => assert_eq!(replay.player().tick(), 10),
+            _ => panic!("expected replay state"),
+        }
+    }
+
+    #[test]
+    fn a_slowed_down_replay_advances_less_than_one_frame_per_update() {
+        let mut replay = Replay::new(short_game());
+        replay.slow_down();
+        replay.slow_down();
+
+        let mut state = State::Replay(replay);
+        for _ in 0..10 {
+            state = state.update();
+        }
+
+        match state {
+            State::Replay(replay) => assert_eq!(replay.player().tick(), 2),
+            _ => panic!("expected replay state"),
+        }
+    }
+
+    #[test]
+    fn a_paused_replay_does_not_advance() {
+        let mut replay = Replay::new(short_game());
+        replay.toggle_pause();
+
+        match replay.update() {
+            State::Replay(replay) => assert_eq!(replay.player().tick(), 0),
+            _ => panic!("expected replay state"),
+        }
+    }
+
+    #[test]
+    fn a_branched_game_starts_from_the_same_frame() {
+        let mut player = ReplayPlayer::new(short_game());
+        player.seek(1000);
+
+        let game = player.branch();
+
+        assert_eq!(game.board(), player.board());
+        assert_eq!(game.piece(), player.piece());
+        assert_eq!(game.score(), player.score());
+    }
+
+    #[test]
+    fn a_branched_game_can_be_replayed() {
+        let mut player = ReplayPlayer::new(short_game());
+        player.seek(1000);
+
+        let mut state = State::Play(player.branch());
+
+        for _ in 0..100 {
+            if let State::Play(ref mut game) = state {
+                game.move_left();
+                game.rotate();
+            }
+            state = state.update();
+        }
+
+        let game = match state {
+            State::Play(game) => game,
+            _ => panic!("expected game to continue"),
+        };
+
+        let mut replayed = ReplayPlayer::new(game.history().clone());
+        replayed.seek(1100);
+
+        assert_eq!(replayed.board(), game.board());
+        assert_eq!(replayed.piece(), game.piece());
+    }
+
+    #[test]
+    fn a_taken_over_game_cannot_post_a_score() {
+        let mut player = ReplayPlayer::new(short_game());
+        player.play_to_end();
+        let end = player.tick();
+        player.seek(end - 60);
+
+        let mut game = player.branch();
+        while game.step() == StepResult::Continue {}
+
+        let score = Score::new(game.score(), "AEL".to_string());
+        let game_over = GameOver {
+            hiscores: Some(HighScores {
+                higher_scores: vec![],
+                lower_scores: vec![],
+                has_hiscore: true,
+            }),
+            score: score.clone(),
+            statistics: game.statistics().clone(),
+            history: game.history().clone(),
+        };
+        assert!(!game_over.posting_hiscore());
+
+        let message = ScoreMessage::new(score, game.history().clone());
+        assert!(matches!(
+            message.score(),
+            Err(ScoreValidationError::Rewound)
+        ));
+    }
+
+    #[test]
+    fn garbage_is_added_again_when_replayed() {
+        let mut game = Game::with_config([1, 2, 3, 4], GameConfig::default());
+
+        for frame in 0..300 {
+            if frame % 50 == 0 {
+                game.add_garbage(2, frame as u8 % Board::WIDTH).unwrap();
+            }
+            game.move_left();
+            game.step();
+        }
+
+        let mut replayed = ReplayPlayer::new(game.history().clone());
+        replayed.seek(300);
+
+        assert_eq!(replayed.board(), game.board());
+        assert_eq!(replayed.piece(), game.piece());
+    }
+
+    #[test]
+    fn garbage_outside_the_board_is_an_error_and_is_not_recorded() {
+        let mut game = Game::with_config([1, 2, 3, 4], GameConfig::default());
+
+        assert_eq!(
+            game.add_garbage(1, Board::WIDTH),
+            Err(GarbageError::HoleOutsideBoard(Board::WIDTH))
+        );
+        assert!(game.history().actions().is_empty());
+        assert!(game.board().is_empty());
+    }
+
+    #[test]
+    fn garbage_topping_out_ends_the_replay() {
+        let mut game = Game::with_config([1, 2, 3, 4], GameConfig::default());
+        game.step();
+
+        assert_eq!(game.add_garbage(Board::HEIGHT, 0), Ok(StepResult::GameOver));
+        assert_eq!(game.step(), StepResult::GameOver);
+
+        let mut replayed = ReplayPlayer::new(game.history().clone());
+        replayed.play_to_end();
+
+        assert!(replayed.is_finished());
+        assert_eq!(replayed.tick(), 1);
+    }
+
+    #[test]
+    fn seeking_past_the_end_stops_at_the_game_over() {
+        let mut player = ReplayPlayer::new(short_game());
+        player.seek(u32::MAX);
+
+        assert!(player.is_finished());
+        assert_eq!(player.score(), 1700);
+    }
+}
@@ -6,6 +6,9 @@ use libc;
 #[cfg(not(target_os = "emscripten"))]
 use url::Url;
 
+use crate::dig::DigTime;
+use crate::dig::DigTimeMessage;
+use crate::dig::DIG_TIME_ENDPOINT;
 use crate::score::ScoreMessage;
 use crate::score::{Score, SCORE_ENDPOINT};
 
@@ -16,20 +19,35 @@ lazy_static! {
 type Result<T> = std::result::Result<T, Box<dyn Error>>;
 
 pub fn get_hiscores() -> Result<Vec<Score>> {
-    let body = CLIENT.get_raw_hiscores()?;
+    let body = CLIENT.get_raw(SCORE_ENDPOINT)?;
     let hiscores = serde_json::from_str(&body)?;
     Ok(hiscores)
 }
 
 pub fn post_hiscore(score: &ScoreMessage) {
-    let body = serde_json::to_string(score).unwrap();
-    let response = CLIENT.post_raw_hiscores(&body);
+    let body = score.to_compact_json();
+    let response = CLIENT.post_raw(SCORE_ENDPOINT, &body);
 
     if let Err(e) = response {
         println!("Failed to post hiscores: {}", e);
     }
 }
 
+pub fn get_dig_times() -> Result<Vec<DigTime>> {
+    let body = CLIENT.get_raw(DIG_TIME_ENDPOINT)?;
+    let times = serde_json::from_str(&body)?;
+    Ok(times)
+}
+
+pub fn post_dig_time(time: &DigTimeMessage) {
+    let body = serde_json::to_string(time).unwrap();
+    let response = CLIENT.post_raw(DIG_TIME_ENDPOINT, &body);
+
+    if let Err(e) = response {
+        println!("Failed to post dig time: {}", e);
+    }
+}
+
 #[cfg(not(target_os = "emscripten"))]
 struct Client {
     url: Url,
@@ -45,23 +63,19 @@ impl Client {
         }
     }
 
-    fn scores_endpoint(&self) -> Url {
-        self.url.join(SCORE_ENDPOINT).unwrap()
-    }
-
-    fn get_raw_hiscores(&self) -> Result<String> {
+    fn get_raw(&self, endpoint: &str) -> Result<String> {
         use std::io::Read;
 
         let mut body = String::new();
-        let mut res = self.reqwest_client.get(self.scores_endpoint()).send()?;
+        let mut res = self.reqwest_client.get(self.url.join(endpoint)?).send()?;
         res.read_to_string(&mut body)?;
         Ok(body)
     }
 
-    fn post_raw_hiscores(&self, score: &str) -> Result<()> {
+    fn post_raw(&self, endpoint: &str, body: &str) -> Result<()> {
         self.reqwest_client
-            .post(self.scores_endpoint())
-            .body(score.to_string())
+            .post(self.url.join(endpoint)?)
+            .body(body.to_string())
             .send()?;
         Ok(())
     }
@@ -102,7 +116,7 @@ impl Client {
         }
     }
 
-    fn get_raw_hiscores(&self) -> Result<String> {
+    fn get_raw(&self, endpoint: &str) -> Result<String> {
         let script = format!(
             r#"(function() {{
             var req = new XMLHttpRequest();
@@ -110,20 +124,20 @@ impl Client {
             req.send(null);
             return req.responseText;
         }}())"#,
-            SCORE_ENDPOINT
+            endpoint
         );
 
         Ok(Client::run_script_string(&script))
     }
 
-    fn post_raw_hiscores(&self, score: &str) -> Result<()> {
+    fn post_raw(&self, endpoint: &str, body: &str) -> Result<()> {
         let script = format!(
             r#"(function() {{
             var req = new XMLHttpRequest();
             req.open("POST", "{}", false);
             req.send(JSON.stringify({}));
         }}())"#,
-            SCORE_ENDPOINT, score
+            endpoint, body
         );
 
         Client::run_script(&script);
@@ -55,6 +55,16 @@ pub enum ScoreValidationError {
     NameTooLong(usize),
     /// Name must contain only alphanumeric characters
     NameNotAlphanumeric(String),
+    /// Game was rewound in practice mode or branched from a replay
+    Rewound,
+    /// Game was played by a bot
+    PlayedByBot,
+    /// Game was played with non-default rules
+    CustomRules,
+    /// Game had garbage added to it
+    Garbage,
+    /// Game started from its own board or pieces
+    CustomStart,
     /// Score does not match game history
     UnexpectedScore {
         /// The original score message
@@ -78,6 +88,30 @@ impl Display for ScoreValidationError {
                 "Name must contain only alphanumeric characters, but was {}",
                 name
             ),
+            ScoreValidationError::Rewound => {
+                write!(
+                    f,
+                    "Game was rewound or branched from a replay, so can't be submitted"
+                )
+            }
+            ScoreValidationError::PlayedByBot => {
+                write!(f, "Game was played by a bot, so can't be submitted")
+            }
+            ScoreValidationError::CustomRules => {
+                write!(
+                    f,
+                    "Game was played with custom rules, so can't be submitted"
+                )
+            }
+            ScoreValidationError::Garbage => {
+                write!(f, "Game had garbage added to it, so can't be submitted")
+            }
+            ScoreValidationError::CustomStart => {
+                write!(
+                    f,
+                    "Game didn't start from an empty board, so can't be submitted"
+                )
+            }
             ScoreValidationError::UnexpectedScore {
                 score_message,
                 expected_score,
@@ -96,20 +130,57 @@ impl ScoreMessage {
         ScoreMessage { score, history }
     }
 
+    /// Get the history of the game that achieved the score.
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// Discard the score and return the history of the game.
+    pub fn into_history(self) -> History {
+        self.history
+    }
+
+    /// Serialize the message as JSON, with the history in its compact base64 format.
+    pub fn to_compact_json(&self) -> String {
+        /// A `ScoreMessage` with the history in its compact format.
+        #[derive(Serialize)]
+        struct CompactScoreMessage<'a> {
+            score: &'a Score,
+            history: String,
+        }
+
+        let message = CompactScoreMessage {
+            score: &self.score,
+            history: self.history.to_base64(),
+        };
+
+        serde_json::to_string(&message).unwrap()
+    }
+
     /// Extract the `Score`, but only if it is valid.
     ///
     /// A valid score will have a 3-letter long alphanumeric name and will match the given history.
     pub fn score(self) -> Result<Score, ScoreValidationError> {
-        if self.score.name.is_empty() {
-            return Err(ScoreValidationError::NameEmpty);
+        check_name(&self.score.name)?;
+
+        if self.history.is_rewound() {
+            return Err(ScoreValidationError::Rewound);
+        }
+
+        if self.history.is_played_by_bot() {
+            return Err(ScoreValidationError::PlayedByBot);
         }
 
-        if self.score.name.len() > 3 {
-            return Err(ScoreValidationError::NameTooLong(self.score.name.len()));
+        if !self.history.config().is_default() {
+            return Err(ScoreValidationError::CustomRules);
         }
 
-        if !self.score.name.chars().all(char::is_alphanumeric) {
-            return Err(ScoreValidationError::NameNotAlphanumeric(self.score.name));
+        if self.history.has_garbage() {
+            return Err(ScoreValidationError::Garbage);
+        }
+
+        if self.history.has_custom_start() {
+            return Err(ScoreValidationError::CustomStart);
         }
 
         self.verify_score()
@@ -129,10 +200,32 @@ impl ScoreMessage {
     }
 }
 
+/// Check a name on a leaderboard is 1 to 3 alphanumeric characters long.
+pub(crate) fn check_name(name: &str) -> Result<(), ScoreValidationError> {
+    if name.is_empty() {
+        return Err(ScoreValidationError::NameEmpty);
+    }
+
+    if name.len() > 3 {
+        return Err(ScoreValidationError::NameTooLong(name.len()));
+    }
+
+    if !name.chars().all(char::is_alphanumeric) {
+        return Err(ScoreValidationError::NameNotAlphanumeric(name.to_string()));
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
+#[allow(clippy::needless_borrow, clippy::single_component_path_imports)]
 mod tests {
     use serde_json;
 
+    use crate::board::Board;
+    use crate::config::GameConfig;
+    use crate::game::Action;
+
     use super::*;
 
     #[test]
@@ -145,6 +238,85 @@ mod tests {
         );
     }
 
+    #[test]
+    fn correctly_recognise_a_valid_short_game_in_compact_format() {
+        let body = include_str!("../../resources/games/short.json");
+        let message: ScoreMessage = serde_json::from_str(body).unwrap();
+        let compact: ScoreMessage = serde_json::from_str(&message.to_compact_json()).unwrap();
+        assert_eq!(
+            compact.score().unwrap(),
+            Score::new(1700, "SHT".to_string())
+        );
+    }
+
+    #[test]
+    fn reject_a_rewound_game() {
+        let body = include_str!("../../resources/games/short.json");
+        let mut message: ScoreMessage = serde_json::from_str(body).unwrap();
+        message.history.set_rewound();
+        assert!(matches!(
+            message.score(),
+            Err(ScoreValidationError::Rewound)
+        ));
+    }
+
+    #[test]
+    fn reject_a_game_played_by_a_bot() {
+        let body = include_str!("../../resources/games/short.json");
+        let mut message: ScoreMessage = serde_json::from_str(body).unwrap();
+        message.history.set_played_by_bot();
+        assert!(matches!(
+            message.score(),
+            Err(ScoreValidationError::PlayedByBot)
+        ));
+    }
+
+    #[test]
+    fn reject_a_game_with_custom_rules() {
+        let body = include_str!("../../resources/games/short.json");
+        let mut message: ScoreMessage = serde_json::from_str(body).unwrap();
+        let config = GameConfig {
+            lines_per_level: 1,
+            ..GameConfig::default()
+        };
+        message.history = History::with_config(message.history.seed(), config);
+        assert!(matches!(
+            message.score(),
+            Err(ScoreValidationError::CustomRules)
+        ));
+    }
+
+    #[test]
+    fn reject_a_game_with_a_custom_start() {
+        let body = include_str!("../../resources/games/short.json");
+        let mut message: ScoreMessage = serde_json::from_str(body).unwrap();
+        let board = Board::from_rows(&["XXXXXXXXX."]).unwrap();
+        message.history = History::with_start(
+            message.history.seed(),
+            GameConfig::default(),
+            board,
+            Vec::new(),
+        );
+        assert!(matches!(
+            message.score(),
+            Err(ScoreValidationError::CustomStart)
+        ));
+    }
+
+    #[test]
+    fn reject_a_game_with_garbage() {
+        let body = include_str!("../../resources/games/short.json");
+        let mut message: ScoreMessage = serde_json::from_str(body).unwrap();
+        let (last_tick, _) = *message.history.actions().last().unwrap();
+        message
+            .history
+            .push_action(last_tick, Action::Garbage { rows: 1, hole: 0 });
+        assert!(matches!(
+            message.score(),
+            Err(ScoreValidationError::Garbage)
+        ));
+    }
+
     #[test]
     #[ignore] // TODO: fix whatever causes this to fail
     fn correctly_recognise_a_valid_long_game() {
@@ -1,4 +1,7 @@
+use std::collections::VecDeque;
 use std::fmt;
+use std::ops::Deref;
+use std::ops::DerefMut;
 
 use lazy_static::lazy_static;
 use rand::Rng;
@@ -20,6 +23,8 @@ const NUM_ROTATIONS: i8 = 4;
 /// time.
 #[derive(Clone)]
 pub struct Bag {
+    /// Shapes that are pulled in order before any random shapes, e.g. the pieces in a puzzle.
+    preset: VecDeque<Shape>,
     shapes: [Shape; NUM_SHAPES],
     index: usize,
     rng: XorShiftRng,
@@ -28,6 +33,7 @@ pub struct Bag {
 impl fmt::Debug for Bag {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
         f.debug_struct("Bag")
+            .field("preset", &self.preset)
             .field("tetrominoes", &self.shapes)
             .field("index", &self.index)
             .field("rng", &"<rng>")
@@ -37,8 +43,14 @@ impl fmt::Debug for Bag {
 
 impl Bag {
     /// Create a new bag with the given random number generator
-    pub fn new(mut rng: XorShiftRng) -> Bag {
+    pub fn new(rng: XorShiftRng) -> Bag {
+        Bag::with_preset(rng, Vec::new())
+    }
+
+    /// Create a new bag that returns the given shapes in order, before any random shapes.
+    pub fn with_preset(mut rng: XorShiftRng, preset: Vec<Shape>) -> Bag {
         Bag {
+            preset: preset.into(),
             shapes: Bag::random_sequence(&mut rng),
             index: 0,
             rng,
@@ -47,11 +59,18 @@ impl Bag {
 
     /// Peek at the next shape without removing it.
     pub fn peek(&self) -> Shape {
-        self.shapes[self.index]
+        match self.preset.front() {
+            Some(&shape) => shape,
+            None => self.shapes[self.index],
+        }
     }
 
     /// Remove and return the next shape.
     pub fn pop(&mut self) -> Shape {
+        if let Some(shape) = self.preset.pop_front() {
+            return shape;
+        }
+
         let next = self.shapes[self.index];
 
         self.index += 1;
@@ -85,7 +104,7 @@ impl Bag {
 }
 
 /// The 90-degree rotation of a shape, so can be one of four values.
-#[derive(Copy, Clone, PartialEq, Debug, Default)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default)]
 pub struct Rotation(i8);
 
 impl Rotation {
@@ -122,10 +141,43 @@ pub enum ShapeColor {
     T,
     /// The 'Z' shape.
     Z,
+    /// Blocks that didn't come from a tetromino, such as the starting blocks in a puzzle.
+    Garbage,
+}
+
+impl ShapeColor {
+    /// Get the color from its letter, e.g. `'T'` for a T shape and `'X'` for garbage.
+    pub(crate) fn from_letter(letter: char) -> Option<ShapeColor> {
+        match letter {
+            'O' => Some(ShapeColor::O),
+            'I' => Some(ShapeColor::I),
+            'J' => Some(ShapeColor::J),
+            'L' => Some(ShapeColor::L),
+            'S' => Some(ShapeColor::S),
+            'T' => Some(ShapeColor::T),
+            'Z' => Some(ShapeColor::Z),
+            'X' => Some(ShapeColor::Garbage),
+            _ => None,
+        }
+    }
+
+    /// Get the letter for the color, e.g. `'T'` for a T shape and `'X'` for garbage.
+    pub(crate) fn letter(self) -> char {
+        match self {
+            ShapeColor::O => 'O',
+            ShapeColor::I => 'I',
+            ShapeColor::J => 'J',
+            ShapeColor::L => 'L',
+            ShapeColor::S => 'S',
+            ShapeColor::T => 'T',
+            ShapeColor::Z => 'Z',
+            ShapeColor::Garbage => 'X',
+        }
+    }
 }
 
 /// A tetromino shape, described by its appearance at all rotations, plus its colour.
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Copy, Clone)]
 pub struct Shape {
     /// The shape's appearance across all four rotations, stored in a u16. This is a compact
     /// binary 'bitfield' representation like so:
@@ -137,33 +189,184 @@ pub struct Shape {
     /// let l_shape = 0b_0000_0100_0100_0110;
     /// ```
     rotations: [u16; 4],
+    /// The blocks and bounding box at each rotation, decoded from `rotations` when the shape is
+    /// defined so they never need to be worked out during the game.
+    geometry: &'static [Geometry; 4],
     /// The colour of the shape.
     pub color: ShapeColor,
 }
 
+impl PartialEq for Shape {
+    fn eq(&self, other: &Shape) -> bool {
+        // The geometry is decoded from the rotations, so there's no need to compare it
+        self.rotations == other.rotations && self.color == other.color
+    }
+}
+
+impl fmt::Debug for Shape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("Shape")
+            .field("rotations", &self.rotations)
+            .field("color", &self.color)
+            .finish()
+    }
+}
+
 impl Shape {
     /// The maximum width of a shape.
     pub const WIDTH: u8 = 4;
     /// The maximum height of a shape.
     pub const HEIGHT: u8 = 4;
 
-    /// Get all the blocks that comprise this shape with the given rotation.
-    pub fn blocks(&self, rot: Rotation) -> Vec<Pos> {
-        let mut blocks = Vec::new();
+    /// Get the standard shape with the given color, or `None` for garbage.
+    pub fn from_color(color: ShapeColor) -> Option<Shape> {
+        match color {
+            ShapeColor::O => Some(O_SHAPE),
+            ShapeColor::I => Some(I_SHAPE),
+            ShapeColor::J => Some(J_SHAPE),
+            ShapeColor::L => Some(L_SHAPE),
+            ShapeColor::S => Some(S_SHAPE),
+            ShapeColor::T => Some(T_SHAPE),
+            ShapeColor::Z => Some(Z_SHAPE),
+            ShapeColor::Garbage => None,
+        }
+    }
+
+    /// Get a bitmask of the blocks in one row of this shape with the given rotation, where bit `x`
+    /// is set if there's a block in column `x`.
+    pub(crate) fn row_mask(&self, rot: Rotation, row: u8) -> u16 {
+        (self.rotations[rot.0 as usize] >> (row * Shape::WIDTH)) & ((1 << Shape::WIDTH) - 1)
+    }
 
-        for index in 0..Shape::WIDTH * Shape::HEIGHT {
-            // Look up `index` in `rotations` bit array
-            if self.rotations[rot.0 as usize] & (1 << index) != 0 {
-                let x = index % Shape::WIDTH;
-                let y = index / Shape::WIDTH;
-                blocks.push(Pos::new(i16::from(x), i16::from(y)));
+    /// Get all the blocks that comprise this shape with the given rotation, ordered from top to
+    /// bottom and then left to right.
+    pub fn blocks(&self, rot: Rotation) -> impl ExactSizeIterator<Item = Pos> + Clone {
+        self.geometry[rot.0 as usize].blocks.iter().copied()
+    }
+
+    /// Get the top-left and bottom-right corners of the smallest box containing every block of
+    /// this shape with the given rotation.
+    pub fn bounding_box(&self, rot: Rotation) -> (Pos, Pos) {
+        let geometry = &self.geometry[rot.0 as usize];
+        (geometry.min, geometry.max)
+    }
+
+    /// Get the blocks of this shape with the given rotation as a list that can be modified without
+    /// allocating.
+    pub(crate) fn block_list(&self, rot: Rotation) -> Blocks {
+        self.geometry[rot.0 as usize].blocks
+    }
+}
+
+/// The most blocks in any shape, including the decoy shape in evil mode.
+const MAX_BLOCKS: usize = 5;
+
+/// A short list of blocks, stored inline so it can be copied and compared without allocating.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub(crate) struct Blocks {
+    /// The blocks, where only the first `len` are used and the rest are always at the origin.
+    blocks: [Pos; MAX_BLOCKS],
+    len: u8,
+}
+
+impl Deref for Blocks {
+    type Target = [Pos];
+
+    fn deref(&self) -> &[Pos] {
+        &self.blocks[..self.len as usize]
+    }
+}
+
+impl DerefMut for Blocks {
+    fn deref_mut(&mut self) -> &mut [Pos] {
+        &mut self.blocks[..self.len as usize]
+    }
+}
+
+/// The blocks and bounding box of a shape at one rotation.
+#[derive(Copy, Clone, Debug)]
+struct Geometry {
+    blocks: Blocks,
+    min: Pos,
+    max: Pos,
+}
+
+impl Geometry {
+    /// Decode the geometry from one rotation of a shape's bitfield.
+    ///
+    /// This is only called when defining shapes, where a shape with too many blocks fails to
+    /// compile.
+    const fn from_bits(bits: u16) -> Geometry {
+        let mut blocks = [Pos::new(0, 0); MAX_BLOCKS];
+        let mut len = 0;
+        let (mut min_x, mut min_y) = (Shape::WIDTH as i16, Shape::HEIGHT as i16);
+        let (mut max_x, mut max_y) = (0, 0);
+
+        let mut index = 0;
+        while index < Shape::WIDTH * Shape::HEIGHT {
+            if bits & (1 << index) != 0 {
+                let x = (index % Shape::WIDTH) as i16;
+                let y = (index / Shape::WIDTH) as i16;
+                blocks[len] = Pos::new(x, y);
+                len += 1;
+
+                if x < min_x {
+                    min_x = x;
+                }
+                if y < min_y {
+                    min_y = y;
+                }
+                if x > max_x {
+                    max_x = x;
+                }
+                if y > max_y {
+                    max_y = y;
+                }
             }
+            index += 1;
         }
 
-        blocks
+        Geometry {
+            blocks: Blocks {
+                blocks,
+                len: len as u8,
+            },
+            min: Pos::new(min_x, min_y),
+            max: Pos::new(max_x, max_y),
+        }
     }
 }
 
+/// Decode the geometry of every rotation of a shape.
+const fn geometry(rotations: [u16; 4]) -> [Geometry; 4] {
+    [
+        Geometry::from_bits(rotations[0]),
+        Geometry::from_bits(rotations[1]),
+        Geometry::from_bits(rotations[2]),
+        Geometry::from_bits(rotations[3]),
+    ]
+}
+
+/// Define a shape from its appearance at all rotations, decoding its geometry at compile time.
+///
+/// e.g.
+/// ```ignore
+/// static T_SHAPE: Shape = shape! {
+///     rotations: [tet!(...), tet!(...), tet!(...), tet!(...)],
+///     color: ShapeColor::T,
+/// };
+/// ```
+macro_rules! shape {
+    (rotations: $rotations:expr, color: $color:expr $(,)?) => {{
+        const ROTATIONS: [u16; 4] = $rotations;
+        Shape {
+            rotations: ROTATIONS,
+            geometry: &geometry(ROTATIONS),
+            color: $color,
+        }
+    }};
+}
+
 /// Create a tetromino shape as a compact `u16` bit array.
 ///
 /// e.g.
@@ -208,38 +411,38 @@ macro_rules! tet {
 lazy_static! {
     static ref SHAPES: [Shape; NUM_SHAPES] = {
         if args::evil_mode() {
-
             // MWAHAHAAAAAA
-            let decoy_shape = Shape {
-                rotations: [
-                    tet!(_ _ _ _
-                         X X X X
-                         _ _ _ _
-                         _ _ _ _),
-                    tet!(_ _ X _
-                         _ _ X _
-                         _ _ X _
-                         _ X X _),
-                    tet!(_ _ _ _
-                         _ _ _ _
-                         X X X X
-                         _ _ _ _),
-                    tet!(_ X _ _
-                         _ X _ _
-                         _ X _ _
-                         _ X X _),
-                ],
-                color: ShapeColor::I,
-            };
-
-            [S_SHAPE, S_SHAPE, S_SHAPE, Z_SHAPE, Z_SHAPE, Z_SHAPE, decoy_shape]
+            [S_SHAPE, S_SHAPE, S_SHAPE, Z_SHAPE, Z_SHAPE, Z_SHAPE, DECOY_SHAPE]
         } else {
             [O_SHAPE, I_SHAPE, J_SHAPE, L_SHAPE, S_SHAPE, T_SHAPE, Z_SHAPE]
         }
     };
 }
 
-static O_SHAPE: Shape = Shape {
+/// A shape that looks like an I, until it's too late.
+static DECOY_SHAPE: Shape = shape! {
+    rotations: [
+        tet!(_ _ _ _
+             X X X X
+             _ _ _ _
+             _ _ _ _),
+        tet!(_ _ X _
+             _ _ X _
+             _ _ X _
+             _ X X _),
+        tet!(_ _ _ _
+             _ _ _ _
+             X X X X
+             _ _ _ _),
+        tet!(_ X _ _
+             _ X _ _
+             _ X _ _
+             _ X X _),
+    ],
+    color: ShapeColor::I,
+};
+
+static O_SHAPE: Shape = shape! {
     rotations: [
         tet!(_ _ _ _
              _ X X _
@@ -261,7 +464,7 @@ static O_SHAPE: Shape = Shape {
     color: ShapeColor::O,
 };
 
-static I_SHAPE: Shape = Shape {
+static I_SHAPE: Shape = shape! {
     rotations: [
         tet!(_ _ _ _
              X X X X
@@ -283,7 +486,7 @@ static I_SHAPE: Shape = Shape {
     color: ShapeColor::I,
 };
 
-static J_SHAPE: Shape = Shape {
+static J_SHAPE: Shape = shape! {
     rotations: [
         tet!(_ _ _ _
              X _ _ _
@@ -305,7 +508,7 @@ static J_SHAPE: Shape = Shape {
     color: ShapeColor::J,
 };
 
-static L_SHAPE: Shape = Shape {
+static L_SHAPE: Shape = shape! {
     rotations: [
         tet!(_ _ _ _
              _ _ X _
@@ -327,7 +530,7 @@ static L_SHAPE: Shape = Shape {
     color: ShapeColor::L,
 };
 
-static S_SHAPE: Shape = Shape {
+static S_SHAPE: Shape = shape! {
     rotations: [
         tet!(_ _ _ _
              _ X X _
@@ -349,7 +552,7 @@ static S_SHAPE: Shape = Shape {
     color: ShapeColor::S,
 };
 
-static T_SHAPE: Shape = Shape {
+static T_SHAPE: Shape = shape! {
     rotations: [
         tet!(_ _ _ _
              _ X _ _
@@ -371,7 +574,7 @@ static T_SHAPE: Shape = Shape {
     color: ShapeColor::T,
 };
 
-static Z_SHAPE: Shape = Shape {
+static Z_SHAPE: Shape = shape! {
     rotations: [
         tet!(_ _ _ _
              X X _ _
@@ -438,6 +641,7 @@ mod tests {
                 ShapeColor::S,
                 ShapeColor::T,
                 ShapeColor::Z,
+                ShapeColor::Garbage,
             ])
             .unwrap()
         }
@@ -471,7 +675,50 @@ mod tests {
         );
     }
 
+    #[test]
+    fn bag_returns_preset_shapes_first() {
+        let rng = XorShiftRng::new_unseeded();
+        let mut bag = Bag::with_preset(rng, vec![T_SHAPE, T_SHAPE, I_SHAPE]);
+
+        assert_eq!(bag.pop(), T_SHAPE);
+        assert_eq!(bag.pop(), T_SHAPE);
+        assert_eq!(bag.peek(), I_SHAPE);
+        assert_eq!(bag.pop(), I_SHAPE);
+    }
+
+    #[test]
+    fn the_decoy_shape_has_five_blocks_when_upright() {
+        assert_eq!(DECOY_SHAPE.blocks(Rotation(0)).len(), 4);
+        assert_eq!(DECOY_SHAPE.blocks(Rotation(1)).len(), 5);
+        assert_eq!(
+            DECOY_SHAPE.bounding_box(Rotation(1)),
+            (Pos::new(1, 0), Pos::new(2, 3))
+        );
+    }
+
     quickcheck! {
+        fn blocks_are_the_set_bits_of_the_bitfield(shape: &'static Shape, rot: Rotation) -> bool {
+            let bits = (0..Shape::HEIGHT).flat_map(|y| {
+                (0..Shape::WIDTH)
+                    .filter(move |&x| shape.row_mask(rot, y) & (1 << x) != 0)
+                    .map(move |x| Pos::new(i16::from(x), i16::from(y)))
+            });
+            shape.blocks(rot).eq(bits)
+        }
+
+        fn bounding_box_is_the_smallest_box_containing_every_block(
+            shape: &'static Shape, rot: Rotation) -> bool {
+            let (min, max) = shape.bounding_box(rot);
+            let mut blocks = shape.blocks(rot);
+
+            blocks.clone().all(|pos| {
+                min.x() <= pos.x() && pos.x() <= max.x() && min.y() <= pos.y() && pos.y() <= max.y()
+            }) && blocks.clone().any(|pos| pos.x() == min.x())
+                && blocks.clone().any(|pos| pos.x() == max.x())
+                && blocks.clone().any(|pos| pos.y() == min.y())
+                && blocks.any(|pos| pos.y() == max.y())
+        }
+
         fn bag_always_returns_a_valid_shape(bag: Bag) -> bool {
             let mut bag = bag;
             let shape = bag.pop();
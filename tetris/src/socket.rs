@@ -0,0 +1,149 @@
+use std::error::Error;
+
+#[cfg(not(target_os = "emscripten"))]
+use std::io;
+#[cfg(not(target_os = "emscripten"))]
+use std::net::TcpStream;
+#[cfg(not(target_os = "emscripten"))]
+use std::sync::mpsc;
+#[cfg(not(target_os = "emscripten"))]
+use std::sync::mpsc::Receiver;
+#[cfg(not(target_os = "emscripten"))]
+use std::sync::mpsc::TryRecvError;
+#[cfg(not(target_os = "emscripten"))]
+use std::thread;
+
+#[cfg(not(target_os = "emscripten"))]
+use tungstenite::stream::MaybeTlsStream;
+#[cfg(not(target_os = "emscripten"))]
+use tungstenite::Message;
+#[cfg(not(target_os = "emscripten"))]
+use tungstenite::WebSocket;
+
+use crate::online::ClientMessage;
+use crate::online::ServerMessage;
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// A WebSocket connection to the online match server.
+///
+/// Once connected, the socket never blocks, so it can be checked for messages every frame.
+#[cfg(not(target_os = "emscripten"))]
+pub(crate) struct Socket {
+    socket: WebSocket<MaybeTlsStream<TcpStream>>,
+}
+
+/// A connection to the match server being made in the background, so the game keeps running
+/// while it waits for the server.
+#[cfg(not(target_os = "emscripten"))]
+pub(crate) struct Connecting {
+    receiver: Receiver<std::result::Result<Socket, String>>,
+}
+
+#[cfg(not(target_os = "emscripten"))]
+impl Socket {
+    /// Start connecting to the server at the given `ws://` URL.
+    pub(crate) fn connect(url: &str) -> Connecting {
+        let (sender, receiver) = mpsc::channel();
+        let url = url.to_string();
+
+        thread::spawn(move || {
+            let socket = Socket::connect_blocking(&url).map_err(|e| e.to_string());
+            // The lobby may have been left already, in which case nobody wants the socket
+            let _ = sender.send(socket);
+        });
+
+        Connecting { receiver }
+    }
+
+    fn connect_blocking(url: &str) -> Result<Self> {
+        let (mut socket, _) = tungstenite::connect(url)?;
+
+        if let MaybeTlsStream::Plain(stream) = socket.get_mut() {
+            stream.set_nonblocking(true)?;
+        }
+
+        Ok(Socket { socket })
+    }
+
+    /// Queue a message to send to the server, writing as much as it can without blocking.
+    pub(crate) fn send(&mut self, message: &ClientMessage) -> Result<()> {
+        let text = serde_json::to_string(message)?;
+
+        match self.socket.send(Message::Text(text)) {
+            // The message is queued, and is written when the socket is next flushed
+            Err(e) if would_block(&e) => Ok(()),
+            result => Ok(result?),
+        }
+    }
+
+    /// Get the next message from the server, or `None` if there isn't one yet.
+    ///
+    /// Any queued messages are written to the server first.
+    pub(crate) fn receive(&mut self) -> Result<Option<ServerMessage>> {
+        match self.socket.flush() {
+            Err(e) if would_block(&e) => {}
+            result => result?,
+        }
+
+        loop {
+            match self.socket.read() {
+                Ok(Message::Text(text)) => return Ok(Some(serde_json::from_str(&text)?)),
+                Ok(Message::Close(_)) => return Err("Server closed the connection".into()),
+                Ok(_) => {}
+                Err(e) if would_block(&e) => return Ok(None),
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "emscripten"))]
+impl Connecting {
+    /// Get the socket once it's connected, or why it couldn't connect, or `None` if it's still
+    /// connecting.
+    pub(crate) fn poll(&mut self) -> Option<std::result::Result<Socket, String>> {
+        match self.receiver.try_recv() {
+            Ok(socket) => Some(socket),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err("Failed to connect".to_string())),
+        }
+    }
+}
+
+/// Return whether the socket stopped only because it couldn't go on without blocking.
+#[cfg(not(target_os = "emscripten"))]
+fn would_block(error: &tungstenite::Error) -> bool {
+    match error {
+        tungstenite::Error::Io(e) => e.kind() == io::ErrorKind::WouldBlock,
+        _ => false,
+    }
+}
+
+#[cfg(target_os = "emscripten")]
+pub(crate) struct Socket;
+
+#[cfg(target_os = "emscripten")]
+pub(crate) struct Connecting;
+
+#[cfg(target_os = "emscripten")]
+impl Socket {
+    pub(crate) fn connect(_url: &str) -> Connecting {
+        Connecting
+    }
+
+    pub(crate) fn send(&mut self, _message: &ClientMessage) -> Result<()> {
+        Ok(())
+    }
+
+    pub(crate) fn receive(&mut self) -> Result<Option<ServerMessage>> {
+        Ok(None)
+    }
+}
+
+#[cfg(target_os = "emscripten")]
+impl Connecting {
+    pub(crate) fn poll(&mut self) -> Option<std::result::Result<Socket, String>> {
+        Some(Err("Online play isn't available in the browser".to_string()))
+    }
+}
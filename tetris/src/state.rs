@@ -1,5 +1,21 @@
+use crate::board::Board;
+use crate::config::GameConfig;
+use crate::cpu::Difficulty;
+use crate::dig::DigOver;
+use crate::dig::DigRace;
 use crate::game::Game;
+use crate::game::History;
 use crate::game_over::GameOver;
+use crate::online;
+use crate::online::Lobby;
+use crate::online::Online;
+use crate::online::OnlineOver;
+use crate::puzzle::Puzzle;
+use crate::puzzle::PuzzleOver;
+use crate::replay::Replay;
+use crate::versus::AttackTable;
+use crate::versus::Versus;
+use crate::versus::VersusOver;
 
 /// The state of the entire Tetris application.
 pub enum State {
@@ -11,6 +27,22 @@ pub enum State {
     Paused(Paused),
     /// The game over screen.
     GameOver(GameOver),
+    /// The replay screen, playing back a recorded game.
+    Replay(Replay),
+    /// The puzzle over screen, after a puzzle is solved or failed.
+    PuzzleOver(PuzzleOver),
+    /// The two-player screen, with both players' games side by side.
+    Versus(Versus),
+    /// The versus over screen, after one of the players tops out.
+    VersusOver(VersusOver),
+    /// The dig race over screen, after all the garbage is cleared or the player tops out.
+    DigOver(DigOver),
+    /// The lobby screen, waiting for an opponent to play online.
+    Lobby(Lobby),
+    /// The online match screen, with the player's and opponent's games side by side.
+    Online(Online),
+    /// The online match over screen, after the server ends the match.
+    OnlineOver(OnlineOver),
 }
 
 impl State {
@@ -21,7 +53,55 @@ impl State {
 
     /// Create a game-play state.
     pub fn play() -> State {
-        State::Play(Game::default())
+        State::live(Game::default())
+    }
+
+    /// Create a practice game-play state, where the player can undo and rewind.
+    pub fn practice() -> State {
+        State::live(Game::practice())
+    }
+
+    /// Create a practice game-play state, starting from the given board.
+    pub fn practice_with_board(board: Board) -> State {
+        State::live(Game::practice_with_board(board))
+    }
+
+    /// Create a puzzle game-play state, starting the given puzzle.
+    pub fn puzzle(puzzle: Puzzle) -> State {
+        State::live(Game::puzzle(puzzle))
+    }
+
+    /// Create a dig race game-play state, starting with the race's garbage.
+    pub fn dig(race: DigRace) -> State {
+        State::live(Game::dig(race))
+    }
+
+    /// Create a game-play state for a game the player is about to play, analysing their finesse
+    /// to show it to them.
+    pub(crate) fn live(mut game: Game) -> State {
+        game.analyse_finesse();
+        State::Play(game)
+    }
+
+    /// Create a two-player versus state, with a random seed and the standard rules.
+    pub fn versus() -> State {
+        State::Versus(Versus::default())
+    }
+
+    /// Create a versus state against a CPU opponent of the given difficulty, with a random seed and
+    /// the standard rules.
+    pub fn versus_cpu(difficulty: Difficulty) -> State {
+        State::Versus(Versus::against_cpu(
+            rand::random(),
+            GameConfig::default(),
+            AttackTable::default(),
+            difficulty,
+        ))
+    }
+
+    /// Create a lobby state, connecting to the server to find an opponent for an online match.
+    pub fn online() -> State {
+        State::Lobby(Lobby::connect(online::SERVER_URL))
     }
 
     /// Create a paused state for the given game.
@@ -29,10 +109,19 @@ impl State {
         State::Paused(Paused(game))
     }
 
+    /// Create a replay state, playing back the given history.
+    pub fn replay(history: History) -> State {
+        State::Replay(Replay::new(history))
+    }
+
     /// Update the given state, ticking time forward once.
     pub fn update(self) -> Self {
         match self {
             State::Play(game) => game.update(),
+            State::Replay(replay) => replay.update(),
+            State::Versus(versus) => versus.update(),
+            State::Lobby(lobby) => lobby.update(),
+            State::Online(online) => online.update(),
             _ => self,
         }
     }
@@ -52,6 +141,31 @@ impl Title {
     pub fn start_game(self) -> State {
         State::play()
     }
+
+    /// Start a practice game from the title screen.
+    pub fn start_practice(self) -> State {
+        State::practice()
+    }
+
+    /// Start a versus match against a CPU opponent from the title screen.
+    pub fn start_versus_cpu(self, difficulty: Difficulty) -> State {
+        State::versus_cpu(difficulty)
+    }
+
+    /// Start the standard dig race from the title screen.
+    pub fn start_dig(self) -> State {
+        State::dig(DigRace::default())
+    }
+
+    /// Start a two-player versus match from the title screen.
+    pub fn start_versus(self) -> State {
+        State::versus()
+    }
+
+    /// Look for an opponent to play online from the title screen.
+    pub fn start_online(self) -> State {
+        State::online()
+    }
 }
 
 /// The paused state for the underlying game.
@@ -0,0 +1,259 @@
+use std::time::Duration;
+
+use crate::finesse::Finesse;
+use crate::game::Action;
+use crate::game::Lock;
+use crate::shape::ShapeColor;
+
+/// The rate the game is played at, used to convert frames to time.
+const FRAMES_PER_SECOND: u32 = 60;
+
+/// The number of different block colours, including garbage.
+const NUM_COLORS: usize = 8;
+
+/// The most lines a single piece can clear.
+const MAX_LINES_PER_PIECE: usize = 4;
+
+/// Statistics about how a game was played, updated as the game goes on.
+#[derive(Clone, Default, Debug)]
+pub struct Statistics {
+    /// The number of pieces placed of each shape, indexed by `shape_index`.
+    pieces: [u32; NUM_COLORS],
+
+    /// The number of keys pressed, not counting releasing a drop.
+    keys: u32,
+
+    /// The number of pieces that cleared one, two, three and four lines.
+    line_clears: [u32; MAX_LINES_PER_PIECE],
+
+    /// The number of T-spins, whether or not they cleared any lines.
+    t_spins: u32,
+
+    /// The number of pieces in a row that have cleared lines.
+    combo: u32,
+
+    /// The most pieces in a row that cleared lines.
+    max_combo: u32,
+
+    /// The number of frames played.
+    frames: u32,
+
+    /// Whether each piece was placed with as few inputs as possible.
+    pub(crate) finesse: Finesse,
+}
+
+fn shape_index(color: ShapeColor) -> usize {
+    match color {
+        ShapeColor::O => 0,
+        ShapeColor::I => 1,
+        ShapeColor::J => 2,
+        ShapeColor::L => 3,
+        ShapeColor::S => 4,
+        ShapeColor::T => 5,
+        ShapeColor::Z => 6,
+        ShapeColor::Garbage => 7,
+    }
+}
+
+impl Statistics {
+    /// Get the number of pieces placed of the given shape.
+    pub fn pieces_of_shape(&self, color: ShapeColor) -> u32 {
+        self.pieces[shape_index(color)]
+    }
+
+    /// Get the total number of pieces placed.
+    pub fn pieces(&self) -> u32 {
+        self.pieces.iter().sum()
+    }
+
+    /// Get the number of keys pressed, not counting releasing a drop.
+    pub fn keys(&self) -> u32 {
+        self.keys
+    }
+
+    /// Get the average number of pieces placed each second.
+    pub fn pieces_per_second(&self) -> f32 {
+        let seconds = self.time_played().as_secs_f32();
+        if seconds == 0.0 {
+            0.0
+        } else {
+            self.pieces() as f32 / seconds
+        }
+    }
+
+    /// Get the average number of keys pressed for each piece placed.
+    pub fn keys_per_piece(&self) -> f32 {
+        match self.pieces() {
+            0 => 0.0,
+            pieces => self.keys as f32 / pieces as f32,
+        }
+    }
+
+    /// Get the number of pieces that cleared exactly one line.
+    pub fn singles(&self) -> u32 {
+        self.line_clears[0]
+    }
+
+    /// Get the number of pieces that cleared exactly two lines.
+    pub fn doubles(&self) -> u32 {
+        self.line_clears[1]
+    }
+
+    /// Get the number of pieces that cleared exactly three lines.
+    pub fn triples(&self) -> u32 {
+        self.line_clears[2]
+    }
+
+    /// Get the number of pieces that cleared four lines.
+    pub fn tetrises(&self) -> u32 {
+        self.line_clears[3]
+    }
+
+    /// Get the number of T-spins, whether or not they cleared any lines.
+    pub fn t_spins(&self) -> u32 {
+        self.t_spins
+    }
+
+    /// Get the most pieces in a row that cleared lines.
+    pub fn max_combo(&self) -> u32 {
+        self.max_combo
+    }
+
+    /// Get the number of frames the game has been played for.
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    /// Get how long the game has been played for.
+    pub fn time_played(&self) -> Duration {
+        let millis = u64::from(self.frames) * 1000 / u64::from(FRAMES_PER_SECOND);
+        Duration::from_millis(millis)
+    }
+
+    /// Get the player's finesse.
+    pub fn finesse(&self) -> &Finesse {
+        &self.finesse
+    }
+
+    /// Record that the game advanced one frame.
+    pub(crate) fn record_frame(&mut self) {
+        self.frames += 1;
+    }
+
+    /// Record that the player pressed a key.
+    pub(crate) fn record_action(&mut self, action: Action) {
+        match action {
+            Action::StopDrop | Action::Garbage { .. } => {}
+            Action::MoveLeft | Action::MoveRight | Action::Rotate => {
+                self.keys += 1;
+                self.finesse.record_input();
+            }
+            Action::StartSoftDrop | Action::StartHardDrop => self.keys += 1,
+        }
+    }
+
+    /// Record that a piece locked onto the board.
+    pub(crate) fn record_lock(&mut self, lock: &Lock) {
+        self.pieces[shape_index(lock.piece.shape.color)] += 1;
+
+        if lock.t_spin {
+            self.t_spins += 1;
+        }
+
+        match lock.lines_cleared as usize {
+            0 => self.combo = 0,
+            lines => {
+                self.line_clears[lines.min(MAX_LINES_PER_PIECE) - 1] += 1;
+                self.combo += 1;
+                self.max_combo = self.max_combo.max(self.combo);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::piece::Piece;
+    use crate::shape::Shape;
+
+    use super::*;
+
+    fn lock(color: ShapeColor, lines_cleared: u32) -> Lock {
+        Lock {
+            piece: Piece::new(Shape::from_color(color).unwrap()),
+            lines_cleared,
+            t_spin: false,
+            perfect_clear: false,
+        }
+    }
+
+    #[test]
+    fn pieces_are_counted_by_shape() {
+        let mut statistics = Statistics::default();
+        statistics.record_lock(&lock(ShapeColor::T, 0));
+        statistics.record_lock(&lock(ShapeColor::T, 0));
+        statistics.record_lock(&lock(ShapeColor::I, 0));
+
+        assert_eq!(statistics.pieces_of_shape(ShapeColor::T), 2);
+        assert_eq!(statistics.pieces_of_shape(ShapeColor::I), 1);
+        assert_eq!(statistics.pieces_of_shape(ShapeColor::O), 0);
+        assert_eq!(statistics.pieces(), 3);
+    }
+
+    #[test]
+    fn line_clears_are_counted_by_size() {
+        let mut statistics = Statistics::default();
+        statistics.record_lock(&lock(ShapeColor::I, 4));
+        statistics.record_lock(&lock(ShapeColor::T, 1));
+        statistics.record_lock(&lock(ShapeColor::J, 2));
+
+        assert_eq!(statistics.singles(), 1);
+        assert_eq!(statistics.doubles(), 1);
+        assert_eq!(statistics.triples(), 0);
+        assert_eq!(statistics.tetrises(), 1);
+    }
+
+    #[test]
+    fn the_max_combo_is_the_most_line_clears_in_a_row() {
+        let mut statistics = Statistics::default();
+        for &lines in &[1, 1, 0, 1, 2, 1, 0, 0] {
+            statistics.record_lock(&lock(ShapeColor::I, lines));
+        }
+
+        assert_eq!(statistics.max_combo(), 3);
+    }
+
+    #[test]
+    fn pieces_per_second_uses_the_time_played() {
+        let mut statistics = Statistics::default();
+        for _ in 0..FRAMES_PER_SECOND * 2 {
+            statistics.record_frame();
+        }
+        for _ in 0..3 {
+            statistics.record_lock(&lock(ShapeColor::O, 0));
+        }
+
+        assert_eq!(statistics.time_played(), Duration::from_secs(2));
+        assert_eq!(statistics.pieces_per_second(), 1.5);
+    }
+
+    #[test]
+    fn releasing_a_drop_is_not_a_key_press() {
+        let mut statistics = Statistics::default();
+        statistics.record_action(Action::MoveLeft);
+        statistics.record_action(Action::StartSoftDrop);
+        statistics.record_action(Action::StopDrop);
+        statistics.record_lock(&lock(ShapeColor::O, 0));
+
+        assert_eq!(statistics.keys(), 2);
+        assert_eq!(statistics.keys_per_piece(), 2.0);
+    }
+
+    #[test]
+    fn an_empty_game_has_no_rates() {
+        let statistics = Statistics::default();
+
+        assert_eq!(statistics.pieces_per_second(), 0.0);
+        assert_eq!(statistics.keys_per_piece(), 0.0);
+    }
+}
@@ -0,0 +1,705 @@
+use std::error::Error;
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Write;
+use std::process::Child;
+use std::process::ChildStdin;
+use std::process::ChildStdout;
+use std::process::Command;
+use std::process::Stdio;
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::board::Board;
+use crate::controller::Controller;
+use crate::game::Game;
+use crate::placement::sorted_blocks;
+use crate::placement::Placement;
+use crate::pos::Pos;
+use crate::shape::ShapeColor;
+
+/// The number of rows in a board sent to a bot, which includes space above the top of the board.
+const TBP_BOARD_HEIGHT: usize = 40;
+
+/// An external bot that chooses where to place pieces, speaking the Tetris Bot Protocol (TBP).
+///
+/// Bots receive one JSON message per line, and reply the same way. Usually the bot is a separate
+/// program, started with `TbpBot::spawn`, that reads from stdin and writes to stdout.
+///
+/// The bot is restarted with the whole board for every piece, so it only needs to support the
+/// `start`, `suggest` and `stop` messages. The game has no hold, so suggestions that would hold
+/// are ignored, as are any the game's rotation system can't reach.
+pub struct TbpBot<C: BufRead + Write> {
+    connection: C,
+    info: BotInfo,
+
+    /// What went wrong talking to the bot, after which it isn't asked for any more suggestions.
+    error: Option<TbpError>,
+}
+
+/// Describes a bot, as the bot reports itself.
+#[derive(Clone, PartialEq, Debug)]
+pub struct BotInfo {
+    /// The name of the bot.
+    pub name: String,
+    /// The version of the bot.
+    pub version: String,
+    /// Who made the bot.
+    pub author: String,
+}
+
+/// A problem talking to a bot.
+#[derive(Debug)]
+pub enum TbpError {
+    /// Couldn't read from or write to the bot
+    Io(io::Error),
+    /// The bot sent a message that isn't valid TBP
+    InvalidMessage(serde_json::Error),
+    /// The bot sent a valid message, but not the one expected
+    UnexpectedMessage(String),
+    /// The bot reported an error, with the given reason
+    Bot(String),
+}
+
+impl Error for TbpError {}
+
+impl Display for TbpError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            TbpError::Io(e) => write!(f, "Failed to talk to bot: {}", e),
+            TbpError::InvalidMessage(e) => write!(f, "Bot sent an invalid message: {}", e),
+            TbpError::UnexpectedMessage(message) => {
+                write!(f, "Bot sent an unexpected message: {}", message)
+            }
+            TbpError::Bot(reason) => write!(f, "Bot reported an error: {}", reason),
+        }
+    }
+}
+
+impl From<io::Error> for TbpError {
+    fn from(e: io::Error) -> Self {
+        TbpError::Io(e)
+    }
+}
+
+/// A message sent from the game to a bot.
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum FrontendMessage {
+    Rules {},
+    Start {
+        hold: Option<TbpPiece>,
+        queue: Vec<TbpPiece>,
+        combo: u32,
+        back_to_back: bool,
+        board: Vec<Vec<Option<TbpPiece>>>,
+    },
+    Suggest,
+    Stop,
+    Quit,
+}
+
+/// A message sent from a bot to the game.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum BotMessage {
+    Info {
+        name: String,
+        version: String,
+        author: String,
+    },
+    Ready,
+    Error {
+        reason: String,
+    },
+    Suggestion {
+        moves: Vec<TbpMove>,
+    },
+}
+
+/// A piece type, or `G` for garbage on the board.
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+enum TbpPiece {
+    I,
+    O,
+    T,
+    L,
+    J,
+    S,
+    Z,
+    G,
+}
+
+#[derive(Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+enum Orientation {
+    North,
+    East,
+    South,
+    West,
+}
+
+/// A placement suggested by a bot.
+#[derive(Deserialize, Debug)]
+struct TbpMove {
+    location: PieceLocation,
+}
+
+/// Where a piece is placed, by the position of its centre cell with `y` counting up from the
+/// bottom of the board.
+#[derive(Deserialize, Debug)]
+struct PieceLocation {
+    #[serde(rename = "type")]
+    piece: TbpPiece,
+    orientation: Orientation,
+    x: i16,
+    y: i16,
+}
+
+impl From<ShapeColor> for TbpPiece {
+    fn from(color: ShapeColor) -> Self {
+        match color {
+            ShapeColor::O => TbpPiece::O,
+            ShapeColor::I => TbpPiece::I,
+            ShapeColor::J => TbpPiece::J,
+            ShapeColor::L => TbpPiece::L,
+            ShapeColor::S => TbpPiece::S,
+            ShapeColor::T => TbpPiece::T,
+            ShapeColor::Z => TbpPiece::Z,
+            ShapeColor::Garbage => TbpPiece::G,
+        }
+    }
+}
+
+impl PieceLocation {
+    /// Get the cells covered by the piece on the game's board, sorted the same way as
+    /// `sorted_blocks`.
+    fn blocks(&self) -> Vec<Pos> {
+        // Cells relative to the centre, facing north with y counting up, as in the TBP spec
+        let cells: &[(i16, i16)] = match self.piece {
+            TbpPiece::I => &[(-1, 0), (0, 0), (1, 0), (2, 0)],
+            TbpPiece::O => &[(0, 0), (1, 0), (0, 1), (1, 1)],
+            TbpPiece::T => &[(-1, 0), (0, 0), (1, 0), (0, 1)],
+            TbpPiece::L => &[(-1, 0), (0, 0), (1, 0), (1, 1)],
+            TbpPiece::J => &[(-1, 0), (0, 0), (1, 0), (-1, 1)],
+            TbpPiece::S => &[(-1, 0), (0, 0), (0, 1), (1, 1)],
+            TbpPiece::Z => &[(-1, 1), (0, 1), (0, 0), (1, 0)],
+            TbpPiece::G => &[],
+        };
+
+        let mut blocks: Vec<Pos> = cells
+            .iter()
+            .map(|&(x, y)| match self.orientation {
+                Orientation::North => (x, y),
+                Orientation::East => (y, -x),
+                Orientation::South => (-x, -y),
+                Orientation::West => (-y, x),
+            })
+            .map(|(x, y)| Pos::new(self.x + x, i16::from(Board::HEIGHT) - 1 - (self.y + y)))
+            .collect();
+
+        blocks.sort_by_key(|pos| (pos.y(), pos.x()));
+        blocks
+    }
+}
+
+/// Get the board as rows of cells from the bottom up, padded to the height bots expect.
+fn tbp_board(board: &Board) -> Vec<Vec<Option<TbpPiece>>> {
+    let mut rows: Vec<Vec<Option<TbpPiece>>> = board
+        .grid()
+        .iter()
+        .rev()
+        .map(|row| row.iter().map(|cell| cell.map(TbpPiece::from)).collect())
+        .collect();
+
+    rows.resize(TBP_BOARD_HEIGHT, vec![None; Board::WIDTH as usize]);
+    rows
+}
+
+impl TbpBot<BotProcess> {
+    /// Start a bot by running the given command, talking to it over its stdin and stdout.
+    pub fn spawn(command: &mut Command) -> Result<Self, TbpError> {
+        TbpBot::new(BotProcess::spawn(command)?)
+    }
+}
+
+impl<C: BufRead + Write> TbpBot<C> {
+    /// Start talking to a bot over the given connection, waiting until it's ready.
+    pub fn new(connection: C) -> Result<Self, TbpError> {
+        let mut bot = TbpBot {
+            connection,
+            info: BotInfo {
+                name: String::new(),
+                version: String::new(),
+                author: String::new(),
+            },
+            error: None,
+        };
+
+        bot.info = match bot.receive()? {
+            BotMessage::Info {
+                name,
+                version,
+                author,
+            } => BotInfo {
+                name,
+                version,
+                author,
+            },
+            message => return Err(TbpError::UnexpectedMessage(format!("{:?}", message))),
+        };
+
+        bot.send(&FrontendMessage::Rules {})?;
+
+        match bot.receive()? {
+            BotMessage::Ready => Ok(bot),
+            BotMessage::Error { reason } => Err(TbpError::Bot(reason)),
+            message => Err(TbpError::UnexpectedMessage(format!("{:?}", message))),
+        }
+    }
+
+    /// Get the bot's description of itself.
+    pub fn info(&self) -> &BotInfo {
+        &self.info
+    }
+
+    /// Get what went wrong talking to the bot, if anything has.
+    ///
+    /// After an error the bot isn't asked for any more suggestions, so every piece is hard dropped.
+    pub fn error(&self) -> Option<&TbpError> {
+        self.error.as_ref()
+    }
+
+    /// Ask the bot where to place the game's current piece.
+    fn suggest(&mut self, game: &Game) -> Result<Option<Placement>, TbpError> {
+        let piece = TbpPiece::from(game.piece().shape.color);
+
+        self.send(&FrontendMessage::Start {
+            hold: None,
+            queue: vec![piece, TbpPiece::from(game.next_shape().color)],
+            combo: 0,
+            back_to_back: false,
+            board: tbp_board(game.board()),
+        })?;
+        self.send(&FrontendMessage::Suggest)?;
+
+        let moves = match self.receive()? {
+            BotMessage::Suggestion { moves } => moves,
+            message => return Err(TbpError::UnexpectedMessage(format!("{:?}", message))),
+        };
+
+        self.send(&FrontendMessage::Stop)?;
+
+        let placements = Placement::find_all_from(game.board(), game.piece().clone());
+
+        // Use the bot's favourite suggestion that the game can actually play
+        let placement = moves
+            .iter()
+            .filter(|mv| mv.location.piece == piece)
+            .find_map(|mv| {
+                let blocks = mv.location.blocks();
+                placements
+                    .iter()
+                    .find(|placement| sorted_blocks(&placement.piece)[..] == blocks[..])
+            });
+
+        Ok(placement.cloned())
+    }
+
+    fn send(&mut self, message: &FrontendMessage) -> Result<(), TbpError> {
+        let mut line = serde_json::to_string(message).map_err(TbpError::InvalidMessage)?;
+        line.push('\n');
+        self.connection.write_all(line.as_bytes())?;
+        self.connection.flush()?;
+        Ok(())
+    }
+
+    fn receive(&mut self) -> Result<BotMessage, TbpError> {
+        let mut line = String::new();
+
+        while line.trim().is_empty() {
+            line.clear();
+            if self.connection.read_line(&mut line)? == 0 {
+                return Err(TbpError::Io(io::ErrorKind::UnexpectedEof.into()));
+            }
+        }
+
+        serde_json::from_str(&line).map_err(TbpError::InvalidMessage)
+    }
+}
+
+impl<C: BufRead + Write> Controller for TbpBot<C> {
+    fn choose(&mut self, game: &Game) -> Option<Placement> {
+        if self.error.is_some() {
+            return None;
+        }
+
+        self.suggest(game).unwrap_or_else(|error| {
+            self.error = Some(error);
+            None
+        })
+    }
+}
+
+impl<C: BufRead + Write> Drop for TbpBot<C> {
+    /// Tell the bot to quit.
+    fn drop(&mut self) {
+        let _ = self.send(&FrontendMessage::Quit);
+    }
+}
+
+/// A bot running as a separate program, talking over its stdin and stdout.
+///
+/// The program is killed when this is dropped.
+pub struct BotProcess {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl BotProcess {
+    fn spawn(command: &mut Command) -> io::Result<Self> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+
+        Ok(BotProcess {
+            child,
+            stdin,
+            stdout,
+        })
+    }
+}
+
+impl Read for BotProcess {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stdout.read(buf)
+    }
+}
+
+impl BufRead for BotProcess {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.stdout.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.stdout.consume(amt)
+    }
+}
+
+impl Write for BotProcess {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stdin.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stdin.flush()
+    }
+}
+
+impl Drop for BotProcess {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::rc::Rc;
+
+    use serde_json::json;
+    use serde_json::Value;
+
+    use crate::controller::Player;
+    use crate::puzzle::Puzzle;
+    use crate::state::State;
+
+    use super::*;
+
+    /// Decides how a fake bot replies to a message.
+    type Reply = Box<dyn FnMut(&Value) -> Vec<Value>>;
+
+    /// A bot that runs in memory, replying to each message it receives with canned messages.
+    struct FakeBot {
+        /// Bytes written to the bot that don't make a whole line yet.
+        input: Vec<u8>,
+
+        /// Bytes waiting to be read from the bot.
+        output: VecDeque<u8>,
+
+        /// Every message the bot has received.
+        received: Rc<RefCell<Vec<Value>>>,
+
+        reply: Reply,
+    }
+
+    impl FakeBot {
+        fn new(reply: impl FnMut(&Value) -> Vec<Value> + 'static) -> Self {
+            let mut bot = FakeBot {
+                input: Vec::new(),
+                output: VecDeque::new(),
+                received: Rc::new(RefCell::new(Vec::new())),
+                reply: Box::new(reply),
+            };
+            bot.push(json!({
+                "type": "info",
+                "name": "Fake",
+                "version": "1.0",
+                "author": "Test",
+                "features": []
+            }));
+            bot
+        }
+
+        /// Create a bot that always makes the same suggestions.
+        fn suggesting(moves: Value) -> Self {
+            FakeBot::new(move |message| match message["type"].as_str() {
+                Some("rules") => vec![json!({"type": "ready"})],
+                Some("suggest") => vec![json!({"type": "suggestion", "moves": moves})],
+                _ => vec![],
+            })
+        }
+
+        fn push(&mut self, message: Value) {
+            self.output.extend(message.to_string().bytes());
+            self.output.push_back(b'\n');
+        }
+    }
+
+    impl Read for FakeBot {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = self.fill_buf()?.len().min(buf.len());
+            buf[..len].copy_from_slice(&self.fill_buf()?[..len]);
+            self.consume(len);
+            Ok(len)
+        }
+    }
+
+    impl BufRead for FakeBot {
+        fn fill_buf(&mut self) -> io::Result<&[u8]> {
+            Ok(self.output.as_slices().0)
+        }
+
+        fn consume(&mut self, amt: usize) {
+            self.output.drain(..amt);
+        }
+    }
+
+    impl Write for FakeBot {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.input.extend_from_slice(buf);
+
+            while let Some(end) = self.input.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = self.input.drain(..=end).collect();
+                let message: Value = serde_json::from_slice(&line).unwrap();
+
+                for reply in (self.reply)(&message) {
+                    self.push(reply);
+                }
+                self.received.borrow_mut().push(message);
+            }
+
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn tetris_puzzle() -> Puzzle {
+        Puzzle::from_json(include_str!("../../resources/puzzles/tetris.json")).unwrap()
+    }
+
+    /// A move putting an upright I in the right-most column, at the bottom of the board.
+    fn i_in_the_well() -> Value {
+        json!({
+            "location": {"type": "I", "orientation": "east", "x": 9, "y": 2},
+            "spin": "none"
+        })
+    }
+
+    #[test]
+    fn a_bot_describes_itself_when_started() {
+        let bot = TbpBot::new(FakeBot::suggesting(json!([]))).unwrap();
+
+        assert_eq!(bot.info().name, "Fake");
+        assert_eq!(bot.info().author, "Test");
+    }
+
+    #[test]
+    fn a_bot_that_rejects_the_rules_fails_to_start() {
+        let fake = FakeBot::new(|_| vec![json!({"type": "error", "reason": "unsupported_rules"})]);
+
+        match TbpBot::new(fake) {
+            Err(TbpError::Bot(reason)) => assert_eq!(reason, "unsupported_rules"),
+            _ => panic!("expected the bot to fail to start"),
+        }
+    }
+
+    #[test]
+    fn the_board_is_sent_from_the_bottom_row_up() {
+        let fake = FakeBot::suggesting(json!([]));
+        let received = fake.received.clone();
+        let mut bot = TbpBot::new(fake).unwrap();
+
+        let game = Game::puzzle(tetris_puzzle());
+        bot.choose(&game);
+
+        let received = received.borrow();
+        let start = received
+            .iter()
+            .find(|message| message["type"] == "start")
+            .unwrap();
+
+        let board = start["board"].as_array().unwrap();
+        assert_eq!(board.len(), TBP_BOARD_HEIGHT);
+        assert_eq!(
+            board[0],
+            json!(["G", "G", "G", "G", "G", "G", "G", "G", "G", null])
+        );
+        assert_eq!(board[4], Value::Array(vec![Value::Null; 10]));
+        assert_eq!(start["queue"][0], "I");
+        assert_eq!(start["hold"], Value::Null);
+    }
+
+    #[test]
+    fn a_location_is_converted_to_blocks_on_the_board() {
+        let location = PieceLocation {
+            piece: TbpPiece::T,
+            orientation: Orientation::North,
+            x: 4,
+            y: 0,
+        };
+
+        let (_, piece) = Board::parse_with_piece(
+            "
+            ....t.....
+            ...ttt....
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(location.blocks(), sorted_blocks(&piece.unwrap()).to_vec());
+    }
+
+    #[test]
+    fn a_location_can_be_rotated() {
+        let location = PieceLocation {
+            piece: TbpPiece::L,
+            orientation: Orientation::West,
+            x: 1,
+            y: 1,
+        };
+
+        let (_, piece) = Board::parse_with_piece(
+            "
+            ll........
+            .l........
+            .l........
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(location.blocks(), sorted_blocks(&piece.unwrap()).to_vec());
+    }
+
+    #[test]
+    fn a_bot_can_solve_a_puzzle() {
+        let bot = TbpBot::new(FakeBot::suggesting(json!([i_in_the_well()]))).unwrap();
+        let mut player = Player::new(bot);
+
+        let mut state = State::Play(Game::puzzle(tetris_puzzle()));
+        let solved = loop {
+            state = match state {
+                State::Play(mut game) => {
+                    player.play(&mut game);
+                    game.update()
+                }
+                State::PuzzleOver(puzzle_over) => break puzzle_over.is_solved(),
+                _ => panic!("expected the puzzle to be played"),
+            };
+        };
+
+        assert!(solved);
+        assert!(player.controller().error().is_none());
+    }
+
+    #[test]
+    fn a_game_played_by_a_bot_is_marked_as_played_by_a_bot() {
+        let bot = TbpBot::new(FakeBot::suggesting(json!([i_in_the_well()]))).unwrap();
+        let mut player = Player::new(bot);
+
+        let mut game = Game::puzzle(tetris_puzzle());
+        assert!(!game.history().is_played_by_bot());
+
+        player.play(&mut game);
+
+        assert!(game.history().is_played_by_bot());
+    }
+
+    #[test]
+    fn suggestions_for_other_pieces_are_ignored() {
+        let moves = json!([
+            {"location": {"type": "O", "orientation": "north", "x": 0, "y": 4}},
+            i_in_the_well()
+        ]);
+        let mut bot = TbpBot::new(FakeBot::suggesting(moves)).unwrap();
+
+        let game = Game::puzzle(tetris_puzzle());
+        let placement = bot.choose(&game).unwrap();
+
+        let (_, piece) = Board::parse_with_piece(
+            "
+            .........i
+            .........i
+            .........i
+            .........i
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(
+            sorted_blocks(&placement.piece),
+            sorted_blocks(&piece.unwrap())
+        );
+    }
+
+    #[test]
+    fn an_unreachable_suggestion_is_not_used() {
+        // This location overlaps the garbage
+        let moves = json!([{"location": {"type": "I", "orientation": "north", "x": 4, "y": 0}}]);
+        let mut bot = TbpBot::new(FakeBot::suggesting(moves)).unwrap();
+
+        let game = Game::puzzle(tetris_puzzle());
+
+        assert_eq!(bot.choose(&game), None);
+        assert!(bot.error().is_none());
+    }
+
+    #[test]
+    fn a_bot_that_stops_responding_is_an_error() {
+        let fake = FakeBot::new(|message| match message["type"].as_str() {
+            Some("rules") => vec![json!({"type": "ready"})],
+            _ => vec![],
+        });
+        let mut bot = TbpBot::new(fake).unwrap();
+
+        let game = Game::puzzle(tetris_puzzle());
+
+        assert_eq!(bot.choose(&game), None);
+        assert!(bot.error().is_some());
+    }
+}
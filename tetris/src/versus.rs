@@ -0,0 +1,485 @@
+use std::collections::VecDeque;
+
+use serde_derive::{Deserialize, Serialize};
+
+use crate::board::Board;
+use crate::config::GameConfig;
+use crate::cpu::Cpu;
+use crate::cpu::Difficulty;
+use crate::game::Game;
+use crate::game::Lock;
+use crate::game::StepResult;
+use crate::garbage;
+use crate::garbage::GarbageHoles;
+use crate::garbage::HolePlacement;
+use crate::state::State;
+
+/// How many garbage rows are sent to the opponent for clearing lines.
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+#[serde(default)]
+pub struct AttackTable {
+    /// The rows sent for clearing one, two, three or four lines at once.
+    pub lines: [u32; 4],
+
+    /// The rows sent for clearing one, two or three lines with a T-spin, instead of `lines`.
+    pub t_spin: [u32; 3],
+
+    /// The extra rows sent for clearing every block from the board.
+    pub perfect_clear: u32,
+}
+
+impl Default for AttackTable {
+    fn default() -> Self {
+        AttackTable {
+            lines: [0, 1, 2, 4],
+            t_spin: [2, 4, 6],
+            perfect_clear: 10,
+        }
+    }
+}
+
+impl AttackTable {
+    /// Parse an attack table from JSON, where any missing fields take their default value.
+    pub fn from_json(json: &str) -> serde_json::Result<AttackTable> {
+        serde_json::from_str(json)
+    }
+
+    /// Get the number of rows sent for a piece locking.
+    pub(crate) fn attack(&self, lock: &Lock) -> u32 {
+        let lines = lock.lines_cleared.min(4) as usize;
+        if lines == 0 {
+            return 0;
+        }
+
+        let attack = if lock.t_spin {
+            self.t_spin[lines.min(3) - 1]
+        } else {
+            self.lines[lines - 1]
+        };
+
+        if lock.perfect_clear {
+            attack + self.perfect_clear
+        } else {
+            attack
+        }
+    }
+}
+
+/// A match between two players, each playing their own game, where clearing lines sends garbage to
+/// the opponent.
+///
+/// Both games use the same seed, so both players get the same pieces. Garbage sent to a player
+/// waits in a queue until they lock a piece without clearing any lines, when it's pushed up from
+/// the bottom of their board. Clearing lines first cancels garbage in their own queue, and only
+/// what's left over is sent on.
+///
+/// Player two can be a CPU opponent, which plays its own game alongside player one.
+///
+/// The match ends when either player tops out.
+pub struct Versus {
+    games: [Game; 2],
+
+    /// The hole of each garbage row waiting for each player, oldest first.
+    incoming: [VecDeque<u8>; 2],
+
+    attack_table: AttackTable,
+
+    /// Picks the hole in each garbage row.
+    holes: GarbageHoles,
+
+    /// The CPU playing player two's game, if player two isn't a person.
+    cpu: Option<Cpu>,
+}
+
+impl Versus {
+    /// Start a match where both games use the given seed and rules.
+    pub fn new(seed: [u32; 4], config: GameConfig, attack_table: AttackTable) -> Self {
+        let games = [
+            Game::with_config(seed, config),
+            Game::with_config(seed, config),
+        ];
+        Versus::with_games(games, seed, attack_table)
+    }
+
+    fn with_games(games: [Game; 2], seed: [u32; 4], attack_table: AttackTable) -> Self {
+        Versus {
+            games,
+            incoming: [VecDeque::new(), VecDeque::new()],
+            attack_table,
+            holes: GarbageHoles::new(seed, HolePlacement::Clean),
+            cpu: None,
+        }
+    }
+
+    /// Start a match against a CPU opponent of the given difficulty, which plays as player two.
+    pub fn against_cpu(
+        seed: [u32; 4],
+        config: GameConfig,
+        attack_table: AttackTable,
+        difficulty: Difficulty,
+    ) -> Self {
+        Versus {
+            cpu: Some(Cpu::new(difficulty, seed)),
+            ..Versus::new(seed, config, attack_table)
+        }
+    }
+
+    /// Get the CPU playing as player two, if there is one.
+    pub fn cpu(&self) -> Option<&Cpu> {
+        self.cpu.as_ref()
+    }
+
+    /// Get both players' games, player one first.
+    pub fn games(&self) -> &[Game; 2] {
+        &self.games
+    }
+
+    /// Get the game of the given player, `0` or `1`, to press keys in it.
+    pub fn game_mut(&mut self, player: usize) -> &mut Game {
+        &mut self.games[player]
+    }
+
+    /// Get the number of garbage rows waiting to be added to the given player's board.
+    pub fn incoming_garbage(&self, player: usize) -> u32 {
+        self.incoming[player].len() as u32
+    }
+
+    /// Advance both games one frame, exchanging garbage for any pieces that locked.
+    ///
+    /// Consumes the match and returns the new state, which is a "versus over" state once either
+    /// player has topped out.
+    pub fn update(mut self) -> State {
+        if let Some(cpu) = &mut self.cpu {
+            cpu.play(&mut self.games[1]);
+        }
+
+        let mut topped_out = [false; 2];
+
+        for (player, topped_out) in topped_out.iter_mut().enumerate() {
+            *topped_out = self.games[player].step() == StepResult::GameOver;
+        }
+
+        for (player, topped_out) in topped_out.iter_mut().enumerate() {
+            if !*topped_out {
+                *topped_out = self.exchange_garbage(player) == StepResult::GameOver;
+            }
+        }
+
+        let winner = match topped_out {
+            [false, false] => return State::Versus(self),
+            [false, true] => Some(0),
+            [true, false] => Some(1),
+            [true, true] => None,
+        };
+
+        State::VersusOver(VersusOver {
+            games: self.games,
+            winner,
+            attack_table: self.attack_table,
+            cpu: self.cpu.as_ref().map(Cpu::difficulty),
+        })
+    }
+
+    /// Send or receive garbage if the given player locked a piece on the last frame.
+    ///
+    /// Returns whether receiving garbage topped the player out.
+    fn exchange_garbage(&mut self, player: usize) -> StepResult {
+        let game = &mut self.games[player];
+        match exchange_garbage(game, &mut self.incoming[player], &self.attack_table) {
+            Some(attack) => {
+                let holes = self.holes.pick(attack);
+                self.incoming[1 - player].extend(holes);
+                StepResult::Continue
+            }
+            None => StepResult::GameOver,
+        }
+    }
+}
+
+/// Receive or cancel garbage if the game locked a piece on the last frame.
+///
+/// Locking without clearing lines adds every incoming garbage row to the board, while clearing
+/// lines cancels incoming rows instead. Returns the rows left over to send to the opponent, or
+/// `None` if receiving garbage topped the player out.
+///
+/// Every incoming hole must be inside the board.
+pub(crate) fn exchange_garbage(
+    game: &mut Game,
+    incoming: &mut VecDeque<u8>,
+    attack_table: &AttackTable,
+) -> Option<u32> {
+    let (lines_cleared, attack) = match game.last_lock() {
+        Some(lock) => (lock.lines_cleared, attack_table.attack(lock)),
+        None => return Some(0),
+    };
+
+    if lines_cleared == 0 {
+        // Anything more than a board's worth of garbage would be pushed off the top anyway
+        let rows = incoming.len().min(usize::from(Board::HEIGHT));
+        let holes: Vec<u8> = incoming.drain(..).take(rows).collect();
+
+        for (rows, hole) in garbage::runs(&holes) {
+            let step_result = game
+                .add_garbage(rows, hole)
+                .expect("incoming garbage has its holes inside the board");
+            if step_result == StepResult::GameOver {
+                return None;
+            }
+        }
+        return Some(0);
+    }
+
+    let cancelled = attack.min(incoming.len() as u32);
+    incoming.drain(..cancelled as usize);
+    Some(attack - cancelled)
+}
+
+impl Default for Versus {
+    /// Start a match with a random seed and the standard rules.
+    fn default() -> Self {
+        Versus::new(
+            rand::random(),
+            GameConfig::default(),
+            AttackTable::default(),
+        )
+    }
+}
+
+/// Versus over state, where the players can see who won the match.
+pub struct VersusOver {
+    games: [Game; 2],
+    winner: Option<usize>,
+    attack_table: AttackTable,
+
+    /// The difficulty of the CPU that played as player two, if there was one.
+    cpu: Option<Difficulty>,
+}
+
+impl VersusOver {
+    /// Get the player who won, `0` or `1`, or `None` if both topped out at once.
+    pub fn winner(&self) -> Option<usize> {
+        self.winner
+    }
+
+    /// Get both players' games as they ended, player one first.
+    pub fn games(&self) -> &[Game; 2] {
+        &self.games
+    }
+
+    /// Get the difficulty of the CPU that played as player two, if there was one.
+    pub fn cpu(&self) -> Option<Difficulty> {
+        self.cpu
+    }
+
+    /// Start another match with the same rules, opponent and a new seed.
+    pub fn rematch(self) -> State {
+        let config = *self.games[0].config();
+        let seed = rand::random();
+
+        let versus = match self.cpu {
+            Some(difficulty) => Versus::against_cpu(seed, config, self.attack_table, difficulty),
+            None => Versus::new(seed, config, self.attack_table),
+        };
+        State::Versus(versus)
+    }
+
+    /// Return to the title screen.
+    pub fn exit(self) -> State {
+        State::title()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::XorShiftRng;
+
+    use crate::game::GameState;
+    use crate::game::History;
+    use crate::shape::Bag;
+    use crate::shape::Shape;
+    use crate::shape::ShapeColor;
+
+    use super::*;
+
+    const SEED: [u32; 4] = [1, 2, 3, 4];
+
+    fn game(rows: &[&str]) -> Game {
+        let i_shape = Shape::from_color(ShapeColor::I).unwrap();
+        let bag = Bag::with_preset(XorShiftRng::new_unseeded(), vec![i_shape; 10]);
+        let board = Board::from_rows(rows).unwrap();
+        Game::resume(GameState::with_board(board, bag), History::new(SEED))
+    }
+
+    fn lock(lines_cleared: u32, t_spin: bool, perfect_clear: bool) -> Lock {
+        Lock {
+            piece: crate::piece::Piece::new(Shape::from_color(ShapeColor::T).unwrap()),
+            lines_cleared,
+            t_spin,
+            perfect_clear,
+        }
+    }
+
+    /// Drop an I piece upright into the rightmost column, then play until it locks.
+    fn drop_i_on_the_right(versus: Versus, player: usize) -> State {
+        let mut versus = versus;
+        let game = versus.game_mut(player);
+        game.rotate();
+        for _ in 0..4 {
+            game.move_right();
+        }
+        game.start_hard_drop();
+
+        let pieces_placed = game.pieces_placed();
+        let mut state = State::Versus(versus);
+        while let State::Versus(versus) = &state {
+            if versus.games()[player].pieces_placed() > pieces_placed {
+                break;
+            }
+            state = state.update();
+        }
+        state
+    }
+
+    /// A board where an I dropped on the right clears four lines, but isn't a perfect clear.
+    fn tetris_ready() -> Vec<&'static str> {
+        let mut rows = vec!["XXXXXXXXX."; 4];
+        rows.push("XXXX.XXXXX");
+        rows
+    }
+
+    #[test]
+    fn the_attack_depends_on_how_lines_were_cleared() {
+        let table = AttackTable::default();
+
+        assert_eq!(table.attack(&lock(0, false, false)), 0);
+        assert_eq!(table.attack(&lock(1, false, false)), 0);
+        assert_eq!(table.attack(&lock(4, false, false)), 4);
+        assert_eq!(table.attack(&lock(2, true, false)), 4);
+        assert_eq!(table.attack(&lock(4, false, true)), 14);
+    }
+
+    #[test]
+    fn missing_attack_table_fields_are_the_defaults() {
+        let table = AttackTable::from_json(r#"{ "perfect_clear": 6 }"#).unwrap();
+
+        assert_eq!(table.lines, AttackTable::default().lines);
+        assert_eq!(table.perfect_clear, 6);
+    }
+
+    #[test]
+    fn clearing_lines_sends_garbage_to_the_opponent() {
+        let versus = Versus::with_games(
+            [game(&tetris_ready()), game(&[])],
+            SEED,
+            AttackTable::default(),
+        );
+
+        match drop_i_on_the_right(versus, 0) {
+            State::Versus(versus) => {
+                assert_eq!(versus.games()[0].lines_cleared(), 4);
+                assert_eq!(versus.incoming_garbage(1), 4);
+                assert_eq!(versus.incoming_garbage(0), 0);
+            }
+            _ => panic!("the match should continue"),
+        }
+    }
+
+    #[test]
+    fn clearing_lines_cancels_incoming_garbage_first() {
+        let mut versus = Versus::with_games(
+            [game(&tetris_ready()), game(&[])],
+            SEED,
+            AttackTable::default(),
+        );
+        versus.incoming[0].extend(&[0; 3]);
+
+        match drop_i_on_the_right(versus, 0) {
+            State::Versus(versus) => {
+                assert_eq!(versus.incoming_garbage(0), 0);
+                assert_eq!(versus.incoming_garbage(1), 1);
+            }
+            _ => panic!("the match should continue"),
+        }
+    }
+
+    #[test]
+    fn garbage_is_received_when_a_piece_locks_without_clearing_lines() {
+        let mut versus = Versus::with_games([game(&[]), game(&[])], SEED, AttackTable::default());
+        versus.incoming[1].extend(&[3; 2]);
+
+        match drop_i_on_the_right(versus, 1) {
+            State::Versus(versus) => {
+                assert_eq!(versus.incoming_garbage(1), 0);
+                let grid = versus.games()[1].board().grid();
+                let bottom = Board::HEIGHT as usize - 1;
+                assert_eq!(grid[bottom][3], None);
+                assert_eq!(grid[bottom][0], Some(ShapeColor::Garbage));
+            }
+            _ => panic!("the match should continue"),
+        }
+    }
+
+    #[test]
+    fn the_player_who_tops_out_loses() {
+        let mut versus = Versus::with_games(
+            [game(&[]), game(&vec![".XXXXXXXX."; 19])],
+            SEED,
+            AttackTable::default(),
+        );
+        versus.incoming[1].extend(&[0; 4]);
+
+        match drop_i_on_the_right(versus, 1) {
+            State::VersusOver(versus_over) => assert_eq!(versus_over.winner(), Some(0)),
+            _ => panic!("the match should be over"),
+        }
+    }
+
+    #[test]
+    fn the_cpu_plays_player_two_while_player_one_waits() {
+        let mut state = State::Versus(Versus::against_cpu(
+            SEED,
+            GameConfig::default(),
+            AttackTable::default(),
+            Difficulty::Hard,
+        ));
+
+        for _ in 0..300 {
+            state = state.update();
+        }
+
+        match state {
+            State::Versus(versus) => {
+                let [human, cpu] = versus.games();
+                assert!(cpu.pieces_placed() > human.pieces_placed());
+                assert!(human.history().actions().is_empty());
+            }
+            _ => panic!("the match should continue"),
+        }
+    }
+
+    #[test]
+    fn a_rematch_is_against_the_same_cpu() {
+        let versus_over = VersusOver {
+            games: [game(&[]), game(&[])],
+            winner: Some(0),
+            attack_table: AttackTable::default(),
+            cpu: Some(Difficulty::Easy),
+        };
+
+        match versus_over.rematch() {
+            State::Versus(versus) => {
+                assert_eq!(versus.cpu().map(Cpu::difficulty), Some(Difficulty::Easy))
+            }
+            _ => panic!("a rematch should start a match"),
+        }
+    }
+
+    #[test]
+    fn both_players_get_the_same_pieces() {
+        let versus = Versus::new(SEED, GameConfig::default(), AttackTable::default());
+
+        let [first, second] = versus.games();
+        assert_eq!(first.piece(), second.piece());
+        assert_eq!(first.next_shape(), second.next_shape());
+    }
+}